@@ -0,0 +1,197 @@
+//! The background (or "paper") that gets drawn underneath the animation.
+
+use druid::kurbo::{Circle, Line};
+use druid::{Color, Data, Rect, RenderContext};
+use serde::{Deserialize, Serialize};
+
+use crate::curve::serde_color;
+
+// All of these distances are in drawing coordinates, where the width of the image is 1.0.
+const LINE_THICKNESS: f64 = 0.001;
+const RULED_SPACING: f64 = 0.04;
+const GRAPH_SPACING: f64 = 0.025;
+const DOT_SPACING: f64 = 0.025;
+const DOT_RADIUS: f64 = 0.0015;
+const STAFF_LINE_SPACING: f64 = 0.012;
+// The distance from the top line of one staff to the top line of the next one.
+const STAFF_SPACING: f64 = 0.12;
+
+/// A pattern that gets drawn on top of the background color.
+#[derive(Clone, Copy, Data, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum PaperTemplate {
+    Plain,
+    Ruled,
+    Graph,
+    DotGrid,
+    MusicStaff,
+}
+
+/// The background of an animation: a solid color, possibly with a template drawn on top.
+#[derive(Clone, Data, Debug, Deserialize, Serialize)]
+pub struct Background {
+    #[serde(with = "serde_color")]
+    pub color: Color,
+    pub template: PaperTemplate,
+}
+
+impl PaperTemplate {
+    pub const ALL: [PaperTemplate; 5] = [
+        PaperTemplate::Plain,
+        PaperTemplate::Ruled,
+        PaperTemplate::Graph,
+        PaperTemplate::DotGrid,
+        PaperTemplate::MusicStaff,
+    ];
+
+    /// A human-readable name for this template.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaperTemplate::Plain => "Plain",
+            PaperTemplate::Ruled => "Ruled",
+            PaperTemplate::Graph => "Graph paper",
+            PaperTemplate::DotGrid => "Dot grid",
+            PaperTemplate::MusicStaff => "Music staff",
+        }
+    }
+}
+
+impl Default for PaperTemplate {
+    fn default() -> PaperTemplate {
+        PaperTemplate::Plain
+    }
+}
+
+impl Default for Background {
+    fn default() -> Background {
+        Background {
+            color: Color::WHITE,
+            template: PaperTemplate::Plain,
+        }
+    }
+}
+
+// piet::Color doesn't implement PartialEq, so we can't derive this.
+impl PartialEq for Background {
+    fn eq(&self, other: &Background) -> bool {
+        self.color.as_rgba_u32() == other.color.as_rgba_u32() && self.template == other.template
+    }
+}
+
+// Returns the indices `i` such that `i * spacing` (plus or minus `slop`) is between `min` and
+// `max`.
+fn indices(min: f64, max: f64, spacing: f64, slop: f64) -> std::ops::RangeInclusive<i64> {
+    let start = ((min - slop) / spacing).floor() as i64;
+    let end = ((max + slop) / spacing).ceil() as i64;
+    start..=end
+}
+
+impl Background {
+    /// Is this a dark background (so that the template should be drawn in a light color)?
+    pub fn is_dark(&self) -> bool {
+        let (r, g, b, _) = self.color.as_rgba();
+        // Rec. 709 luma.
+        0.2126 * r + 0.7152 * g + 0.0722 * b < 0.5
+    }
+
+    /// The color in which the template gets drawn.
+    pub fn template_color(&self) -> Color {
+        if self.is_dark() {
+            Color::rgba8(0xff, 0xff, 0xff, 0x50)
+        } else {
+            Color::rgba8(0x40, 0x70, 0xc0, 0x60)
+        }
+    }
+
+    /// Renders the background.
+    ///
+    /// `region` is the part (in drawing coordinates) that needs to be drawn. The template extends
+    /// indefinitely in all directions, so `region` can be anything; it is only used to figure out
+    /// which parts of the template are visible.
+    pub fn render<R: RenderContext>(&self, ctx: &mut R, region: Rect) {
+        ctx.fill(region, &self.color);
+
+        let color = self.template_color();
+        let hline = |ctx: &mut R, y: f64| {
+            ctx.stroke(
+                Line::new((region.x0, y), (region.x1, y)),
+                &color,
+                LINE_THICKNESS,
+            );
+        };
+        let vline = |ctx: &mut R, x: f64| {
+            ctx.stroke(
+                Line::new((x, region.y0), (x, region.y1)),
+                &color,
+                LINE_THICKNESS,
+            );
+        };
+        let slop = LINE_THICKNESS;
+
+        match self.template {
+            PaperTemplate::Plain => {}
+            PaperTemplate::Ruled => {
+                for i in indices(region.y0, region.y1, RULED_SPACING, slop) {
+                    hline(ctx, i as f64 * RULED_SPACING);
+                }
+            }
+            PaperTemplate::Graph => {
+                for i in indices(region.y0, region.y1, GRAPH_SPACING, slop) {
+                    hline(ctx, i as f64 * GRAPH_SPACING);
+                }
+                for i in indices(region.x0, region.x1, GRAPH_SPACING, slop) {
+                    vline(ctx, i as f64 * GRAPH_SPACING);
+                }
+            }
+            PaperTemplate::DotGrid => {
+                for i in indices(region.y0, region.y1, DOT_SPACING, DOT_RADIUS) {
+                    for j in indices(region.x0, region.x1, DOT_SPACING, DOT_RADIUS) {
+                        let center = (j as f64 * DOT_SPACING, i as f64 * DOT_SPACING);
+                        ctx.fill(Circle::new(center, DOT_RADIUS), &color);
+                    }
+                }
+            }
+            PaperTemplate::MusicStaff => {
+                let staff_height = 4.0 * STAFF_LINE_SPACING;
+                // Staves start half a gap down from the top, so that the first one isn't jammed
+                // against the edge of the paper.
+                let offset = (STAFF_SPACING - staff_height) / 2.0;
+                for i in indices(
+                    region.y0 - offset - staff_height,
+                    region.y1 - offset,
+                    STAFF_SPACING,
+                    slop,
+                ) {
+                    let top = offset + i as f64 * STAFF_SPACING;
+                    for k in 0..5 {
+                        hline(ctx, top + k as f64 * STAFF_LINE_SPACING);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde() {
+        let bg = Background {
+            color: Color::rgb8(0x20, 0x30, 0x20),
+            template: PaperTemplate::MusicStaff,
+        };
+        let written = serde_cbor::to_vec(&bg).unwrap();
+        let read: Background = serde_cbor::from_slice(&written[..]).unwrap();
+        assert_eq!(bg, read);
+        assert!(read.is_dark());
+        assert!(!Background::default().is_dark());
+    }
+
+    #[test]
+    fn index_range() {
+        assert_eq!(indices(0.0, 1.0, 0.25, 0.0), 0..=4);
+        assert_eq!(indices(0.1, 0.9, 0.25, 0.0), 0..=4);
+        assert_eq!(indices(-0.1, 0.2, 0.25, 0.0), -1..=1);
+    }
+}
@@ -0,0 +1,196 @@
+//! The camera determines which part of the (unbounded) canvas is visible in the animation.
+
+use druid::im::OrdMap;
+use druid::kurbo::TranslateScale;
+use druid::{Data, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::time::{Time, TimeDiff, TimeSpan};
+
+/// The part of the canvas that is visible.
+///
+/// The canvas uses drawing coordinates, in which the default page has width 1.0 and its
+/// top-left corner at the origin. The height of the viewport isn't stored, because it is determined
+/// by the aspect ratio of the animation.
+#[derive(Clone, Copy, Data, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Viewport {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+}
+
+/// A sequence of viewport key-frames. Between key-frames, the viewport is linearly interpolated.
+#[derive(Clone, Data, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct CameraTrack {
+    keyframes: OrdMap<Time, Viewport>,
+}
+
+impl Default for Viewport {
+    fn default() -> Viewport {
+        Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+        }
+    }
+}
+
+impl Viewport {
+    /// The visible rectangle, given the aspect ratio (width divided by height) of the animation.
+    pub fn rect(&self, aspect_ratio: f64) -> Rect {
+        Rect::from_origin_size((self.x, self.y), (self.width, self.width / aspect_ratio))
+    }
+
+    /// The transformation that maps this viewport onto the default page (the one with width 1.0
+    /// and its top-left corner at the origin).
+    pub fn to_page(&self) -> TranslateScale {
+        TranslateScale::scale(1.0 / self.width)
+            * TranslateScale::translate(-Vec2::new(self.x, self.y))
+    }
+
+    /// Linearly interpolates between two viewports. When `t` is zero, this returns `self` and when
+    /// `t` is one, it returns `other`.
+    pub fn interpolate(&self, other: &Viewport, t: f64) -> Viewport {
+        Viewport {
+            x: self.x + (other.x - self.x) * t,
+            y: self.y + (other.y - self.y) * t,
+            width: self.width + (other.width - self.width) * t,
+        }
+    }
+}
+
+impl CameraTrack {
+    /// Returns true if there are no key-frames (in which case the viewport is always the default
+    /// page).
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    /// Returns the viewport at the given time.
+    pub fn viewport_at(&self, time: Time) -> Viewport {
+        match (
+            self.keyframes.get_prev(&time),
+            self.keyframes.get_next(&time),
+        ) {
+            (Some((_, vp)), None) | (None, Some((_, vp))) => *vp,
+            (Some((t0, vp0)), Some((t1, vp1))) => {
+                if t0 == t1 {
+                    *vp0
+                } else {
+                    let ratio = (time - *t0).as_micros() as f64 / (*t1 - *t0).as_micros() as f64;
+                    vp0.interpolate(vp1, ratio)
+                }
+            }
+            (None, None) => Viewport::default(),
+        }
+    }
+
+    /// Returns a new camera track with an extra key-frame (replacing any existing key-frame at
+    /// exactly the same time).
+    pub fn with_keyframe(&self, time: Time, viewport: Viewport) -> CameraTrack {
+        CameraTrack {
+            keyframes: self.keyframes.update(time, viewport),
+        }
+    }
+
+    /// Returns a new camera track without any key-frames in the interval `[start, end)`.
+    pub fn without_keyframes_in(&self, start: Time, end: Time) -> CameraTrack {
+        CameraTrack {
+            keyframes: self
+                .keyframes
+                .iter()
+                .filter(|(t, _)| **t < start || **t >= end)
+                .map(|(t, vp)| (*t, *vp))
+                .collect(),
+        }
+    }
+
+    /// Returns a new camera track in which all the key-frames at or after `from` are shifted by
+    /// `shift`.
+    pub fn shifted_after(&self, from: Time, shift: TimeDiff) -> CameraTrack {
+        CameraTrack {
+            keyframes: self
+                .keyframes
+                .iter()
+                .map(|(t, vp)| {
+                    if *t >= from {
+                        (*t + shift, *vp)
+                    } else {
+                        (*t, *vp)
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns a new camera track in which the key-frames in the interval `[start, end]` are
+    /// linearly rescaled to `[start, new_end]`, and the ones after it are shifted to match.
+    pub fn with_scaled_range(&self, start: Time, end: Time, new_end: Time) -> CameraTrack {
+        let span = TimeSpan::new(start, end);
+        let new_span = TimeSpan::new(start, new_end);
+        CameraTrack {
+            keyframes: self
+                .keyframes
+                .iter()
+                .map(|(t, vp)| {
+                    if *t >= end {
+                        (*t + (new_end - end), *vp)
+                    } else if *t > start {
+                        (span.interpolate_to(*t, new_span), *vp)
+                    } else {
+                        (*t, *vp)
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Iterates over the times of all the key-frames.
+    pub fn keyframe_times(&self) -> impl Iterator<Item = Time> + '_ {
+        self.keyframes.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vp(x: f64, y: f64, width: f64) -> Viewport {
+        Viewport { x, y, width }
+    }
+
+    #[test]
+    fn interpolation() {
+        let t = Time::from_micros;
+        let empty = CameraTrack::default();
+        assert_eq!(empty.viewport_at(t(100)), Viewport::default());
+
+        let track = empty
+            .with_keyframe(t(100), vp(0.0, 0.0, 1.0))
+            .with_keyframe(t(200), vp(1.0, 2.0, 0.5));
+        assert_eq!(track.viewport_at(t(0)), vp(0.0, 0.0, 1.0));
+        assert_eq!(track.viewport_at(t(100)), vp(0.0, 0.0, 1.0));
+        assert_eq!(track.viewport_at(t(150)), vp(0.5, 1.0, 0.75));
+        assert_eq!(track.viewport_at(t(200)), vp(1.0, 2.0, 0.5));
+        assert_eq!(track.viewport_at(t(300)), vp(1.0, 2.0, 0.5));
+    }
+
+    #[test]
+    fn to_page() {
+        let v = vp(1.0, 2.0, 0.5);
+        let rect = v.to_page() * v.rect(2.0);
+        assert_eq!(rect, Rect::new(0.0, 0.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn serde() {
+        let t = Time::from_micros;
+        let track = CameraTrack::default()
+            .with_keyframe(t(100), vp(0.0, 0.0, 1.0))
+            .with_keyframe(t(200), vp(1.0, 2.0, 0.5));
+        let written = serde_cbor::to_vec(&track).unwrap();
+        let read: CameraTrack = serde_cbor::from_slice(&written[..]).unwrap();
+        assert_eq!(track, read);
+    }
+}
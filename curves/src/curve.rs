@@ -10,7 +10,7 @@ use std::sync::Arc;
 use crate::effect::Effects;
 use crate::time::Time;
 
-mod serde_color {
+pub(crate) mod serde_color {
     use super::*;
 
     pub fn serialize<S: Serializer>(c: &Color, ser: S) -> Result<S::Ok, S::Error> {
@@ -4,7 +4,7 @@ use druid::{Data, Rect, RenderContext};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::sync::Arc;
 
-use crate::{span_cursor, Lerp, StrokeSeq, Time, TimeDiff};
+use crate::{span_cursor, Lerp, StrokeSeq, Time, TimeDiff, TimeSpan};
 
 /// Snippets are identified by unique ids.
 #[derive(Deserialize, Serialize, Clone, Copy, Data, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -127,6 +127,69 @@ impl DrawSnippet {
         }
     }
 
+    /// Returns a new snippet in which the time interval `[start, end)` has been deleted. Anything
+    /// that was drawn during that interval will appear all at once at `start`, and everything that
+    /// was drawn after it will happen `end - start` earlier.
+    ///
+    /// Returns `None` if the snippet was only visible during the deleted interval.
+    pub fn with_deleted_range(&self, start: Time, end: Time) -> Option<DrawSnippet> {
+        if let Some(snip_end) = self.end {
+            if start <= self.start_time() && snip_end <= end {
+                return None;
+            }
+        }
+
+        let len = end - start;
+        let lerp = self.lerp.with_deleted_range(start, end);
+        let times = lerp_times(&self.strokes, &lerp);
+        Some(DrawSnippet {
+            strokes: Arc::clone(&self.strokes),
+            lerp: Arc::new(lerp),
+            times: Arc::new(times),
+            end: self
+                .end
+                .map(|t| if t >= end { t - len } else { t.min(start) }),
+        })
+    }
+
+    /// Returns a new snippet in which the interval `[start, end]` has been rescaled to
+    /// `[start, new_end]`, and everything after it has been shifted to match. Unlike
+    /// [`DrawSnippet::with_deleted_range`], nothing that was drawn in the interval disappears.
+    pub fn with_scaled_range(&self, start: Time, end: Time, new_end: Time) -> DrawSnippet {
+        let lerp = self.lerp.with_scaled_range(start, end, new_end);
+        let times = lerp_times(&self.strokes, &lerp);
+        let span = TimeSpan::new(start, end);
+        let new_span = TimeSpan::new(start, new_end);
+        DrawSnippet {
+            strokes: Arc::clone(&self.strokes),
+            lerp: Arc::new(lerp),
+            times: Arc::new(times),
+            end: self.end.map(|t| {
+                if t >= end {
+                    t + (new_end - end)
+                } else if t > start {
+                    span.interpolate_to(t, new_span)
+                } else {
+                    t
+                }
+            }),
+        }
+    }
+
+    /// Returns a new snippet in which a gap of length `len` has been inserted at time `at`.
+    /// Everything that was drawn after `at` will happen `len` later, and during the gap the
+    /// snippet will look the way it used to look at `at`.
+    pub fn with_inserted_gap(&self, at: Time, len: TimeDiff) -> DrawSnippet {
+        let lerp = self.lerp.with_inserted_gap(at, len);
+        let times = lerp_times(&self.strokes, &lerp);
+        DrawSnippet {
+            strokes: Arc::clone(&self.strokes),
+            lerp: Arc::new(lerp),
+            times: Arc::new(times),
+            end: self.end.map(|t| if t > at { t + len } else { t }),
+        }
+    }
+
     pub fn start_time(&self) -> Time {
         self.times[0][0]
     }
@@ -161,6 +224,18 @@ impl DrawSnippets {
         ret
     }
 
+    /// Returns a new collection with the snippet `snip` at `id`, replacing any snippet that was
+    /// already there. Unlike [`with_replacement_snippet`], `id` doesn't need to have been handed
+    /// out by this collection; this is useful for replaying changes that were recorded elsewhere.
+    ///
+    /// [`with_replacement_snippet`]: DrawSnippets::with_replacement_snippet
+    pub fn with_snippet(&self, id: DrawSnippetId, snip: DrawSnippet) -> DrawSnippets {
+        let mut ret = self.clone();
+        ret.last_id = ret.last_id.max(id.0);
+        ret.snippets.insert(id, snip);
+        ret
+    }
+
     pub fn without_snippet(&self, id: DrawSnippetId) -> DrawSnippets {
         let mut ret = self.clone();
         if ret.snippets.remove(&id).is_none() {
@@ -185,6 +260,44 @@ impl DrawSnippets {
         self.with_replacement_snippet(id, snip)
     }
 
+    /// Returns a new collection in which the time interval `[start, end)` has been deleted from
+    /// every snippet (see [`DrawSnippet::with_deleted_range`]).
+    pub fn with_deleted_range(&self, start: Time, end: Time) -> DrawSnippets {
+        let mut ret = self.clone();
+        for (id, snip) in self.snippets() {
+            match snip.with_deleted_range(start, end) {
+                Some(snip) => ret.snippets.insert(id, snip),
+                None => ret.snippets.remove(&id),
+            };
+        }
+        ret
+    }
+
+    /// Returns a new collection in which the interval `[start, end]` has been rescaled to
+    /// `[start, new_end]` in every snippet (see [`DrawSnippet::with_scaled_range`]).
+    pub fn with_scaled_range(&self, start: Time, end: Time, new_end: Time) -> DrawSnippets {
+        let mut ret = self.clone();
+        for (id, snip) in self.snippets() {
+            ret.snippets
+                .insert(id, snip.with_scaled_range(start, end, new_end));
+        }
+        ret
+    }
+
+    /// Returns a new collection in which a gap of length `len` has been inserted at time `at` in
+    /// every snippet (see [`DrawSnippet::with_inserted_gap`]).
+    pub fn with_inserted_gap(&self, at: Time, len: TimeDiff) -> DrawSnippets {
+        let mut ret = self.clone();
+        for (id, snip) in self.snippets() {
+            ret.snippets.insert(id, snip.with_inserted_gap(at, len));
+        }
+        ret
+    }
+
+    pub fn has_snippet(&self, id: DrawSnippetId) -> bool {
+        self.snippets.contains_key(&id)
+    }
+
     pub fn snippet(&self, id: DrawSnippetId) -> &DrawSnippet {
         self.snippets.get(&id).unwrap()
     }
@@ -323,4 +436,20 @@ mod tests {
         let read: DrawSnippet = serde_cbor::from_slice(&written[..]).unwrap();
         assert_eq!(snip.lerp, read.lerp);
     }
+
+    #[test]
+    fn scaled_range_keeps_strokes() {
+        let t = Time::from_micros;
+        let snip = DrawSnippet::new(crate::curve::tests::basic_curve());
+        let (snips, id) = DrawSnippets::default().with_new_snippet(snip);
+
+        // The second stroke is drawn from 6 to 8, in the middle of the interval [4, 10] that
+        // gets squished to [4, 7].
+        let out = snips.with_scaled_range(t(4), t(10), t(7));
+        assert!(out.has_snippet(id));
+        let times = &out.snippet(id).times;
+        assert_eq!(times[0], snips.snippet(id).times[0]);
+        assert_eq!(times[1].first(), Some(&t(5)));
+        assert_eq!(times[1].last(), Some(&t(6)));
+    }
 }
@@ -244,6 +244,119 @@ impl Lerp {
         ret.add_lerp(time_from, time_to);
         ret
     }
+
+    // Adds a key-frame at `time` (in the range of the mapping) without changing the mapping. This
+    // only does anything if `time` is strictly inside the range, and not already a key-frame.
+    fn insert_key_frame(&mut self, time: Time) {
+        if time <= self.first()
+            || time >= self.last()
+            || self.lerped_values.binary_search(&time).is_ok()
+        {
+            return;
+        }
+        // Since `time` isn't a key-frame, it lies in the interior of an interval that gets
+        // mapped bijectively, so this is the unique time that gets mapped to it.
+        let original = self.unlerp_clamped(time);
+        let idx = self.lerped_values.partition_point(|&v| v < time);
+        self.original_values.insert(idx, original);
+        self.lerped_values.insert(idx, time);
+    }
+
+    /// Returns a clone of this `Lerp` in which the interval `[start, end)` has been deleted from
+    /// the range of the mapping. Anything that used to get mapped into that interval gets mapped
+    /// to `start` instead, and anything that used to get mapped after it gets mapped `end - start`
+    /// earlier.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(0), t(100)).with_deleted_range(t(20), t(50));
+    /// assert_eq!(lerp.lerp(t(10)), Some(t(10)));
+    /// assert_eq!(lerp.lerp(t(30)), Some(t(20)));
+    /// assert_eq!(lerp.lerp(t(60)), Some(t(30)));
+    /// assert_eq!(lerp.last(), t(70));
+    /// ```
+    pub fn with_deleted_range(&self, start: Time, end: Time) -> Lerp {
+        let mut ret = self.clone();
+        ret.insert_key_frame(start);
+        ret.insert_key_frame(end);
+        let len = end - start;
+        for v in &mut ret.lerped_values {
+            *v = if *v >= end { *v - len } else { (*v).min(start) };
+        }
+        ret
+    }
+
+    /// Returns a clone of this `Lerp` in which the interval `[start, end]` of the range of the
+    /// mapping has been linearly rescaled to `[start, new_end]`. Anything that used to get mapped
+    /// after `end` gets shifted by `new_end - end`. Unlike [`Lerp::with_deleted_range`], this
+    /// keeps distinct times distinct (as long as `new_end > start`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(0), t(100)).with_scaled_range(t(20), t(60), t(30));
+    /// assert_eq!(lerp.lerp(t(10)), Some(t(10)));
+    /// assert_eq!(lerp.lerp(t(40)), Some(t(25)));
+    /// assert_eq!(lerp.lerp(t(80)), Some(t(50)));
+    /// assert_eq!(lerp.last(), t(70));
+    /// ```
+    pub fn with_scaled_range(&self, start: Time, end: Time, new_end: Time) -> Lerp {
+        let mut ret = self.clone();
+        ret.insert_key_frame(start);
+        ret.insert_key_frame(end);
+        let old_span = TimeSpan::new(start, end);
+        let new_span = TimeSpan::new(start, new_end);
+        for v in &mut ret.lerped_values {
+            if *v >= end {
+                *v = *v + (new_end - end);
+            } else if *v > start {
+                *v = old_span.interpolate_to(*v, new_span);
+            }
+        }
+        ret
+    }
+
+    /// Returns a clone of this `Lerp` in which a gap of length `len` has been inserted into the
+    /// range of the mapping at time `at`. Anything that used to get mapped after `at` gets mapped
+    /// `len` later, and whatever used to get mapped to `at` stays there for the duration of the
+    /// gap.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time, TimeDiff};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(0), t(100)).with_inserted_gap(t(50), TimeDiff::from_micros(30));
+    /// assert_eq!(lerp.lerp(t(25)), Some(t(25)));
+    /// assert_eq!(lerp.lerp(t(75)), Some(t(105)));
+    /// assert_eq!(lerp.unlerp(t(60)), Some(t(50)));
+    /// assert_eq!(lerp.last(), t(130));
+    /// ```
+    pub fn with_inserted_gap(&self, at: Time, len: TimeDiff) -> Lerp {
+        let mut ret = self.clone();
+        if at <= ret.first() {
+            for v in &mut ret.lerped_values {
+                *v = *v + len;
+            }
+        } else if at < ret.last() {
+            ret.insert_key_frame(at);
+            // This is the last key-frame at `at`. We duplicate it, so that the time that was
+            // mapped to `at` gets mapped to the whole gap, and then we shift everything after.
+            let idx = ret.lerped_values.partition_point(|&v| v <= at) - 1;
+            for v in &mut ret.lerped_values[(idx + 1)..] {
+                *v = *v + len;
+            }
+            let original = ret.original_values[idx];
+            ret.original_values.insert(idx + 1, original);
+            ret.lerped_values.insert(idx + 1, at + len);
+        }
+        ret
+    }
 }
 
 enum LerpResult {
@@ -360,6 +473,66 @@ mod tests {
         assert_eq!(out.lerped_values, tvec![0, 150, 200]);
     }
 
+    #[test]
+    fn deleted_range() {
+        let lerp = Lerp::new(tvec![0, 100], tvec![0, 100]);
+
+        let out = lerp.with_deleted_range(t(20), t(50));
+        assert_eq!(out.original_values, tvec![0, 20, 50, 100]);
+        assert_eq!(out.lerped_values, tvec![0, 20, 20, 70]);
+
+        // Deleting something that starts before the mapping just shifts it.
+        let lerp = Lerp::new(tvec![0, 100], tvec![100, 200]);
+        let out = lerp.with_deleted_range(t(20), t(50));
+        assert_eq!(out.original_values, tvec![0, 100]);
+        assert_eq!(out.lerped_values, tvec![70, 170]);
+
+        // Deleting something that covers the whole mapping squishes it to a point.
+        let out = lerp.with_deleted_range(t(50), t(250));
+        assert_eq!(out.original_values, tvec![0, 100]);
+        assert_eq!(out.lerped_values, tvec![50, 50]);
+    }
+
+    #[test]
+    fn scaled_range() {
+        let lerp = Lerp::new(tvec![0, 100], tvec![0, 100]);
+
+        let out = lerp.with_scaled_range(t(20), t(60), t(30));
+        assert_eq!(out.original_values, tvec![0, 20, 60, 100]);
+        assert_eq!(out.lerped_values, tvec![0, 20, 30, 70]);
+
+        // Key-frames inside the range get scaled along with everything else.
+        let lerp = Lerp::new(tvec![0, 40, 100], tvec![0, 40, 100]);
+        let out = lerp.with_scaled_range(t(20), t(60), t(30));
+        assert_eq!(out.original_values, tvec![0, 20, 40, 60, 100]);
+        assert_eq!(out.lerped_values, tvec![0, 20, 25, 30, 70]);
+
+        // Scaling something that starts before the mapping scales the beginning of it.
+        let lerp = Lerp::new(tvec![0, 100], tvec![100, 200]);
+        let out = lerp.with_scaled_range(t(0), t(200), t(100));
+        assert_eq!(out.original_values, tvec![0, 100]);
+        assert_eq!(out.lerped_values, tvec![50, 100]);
+    }
+
+    #[test]
+    fn inserted_gap() {
+        let len = TimeDiff::from_micros(30);
+        let lerp = Lerp::new(tvec![0, 100], tvec![0, 100]);
+
+        let out = lerp.with_inserted_gap(t(50), len);
+        assert_eq!(out.original_values, tvec![0, 50, 50, 100]);
+        assert_eq!(out.lerped_values, tvec![0, 50, 80, 130]);
+
+        // Inserting before (or at) the beginning shifts everything.
+        let out = lerp.with_inserted_gap(t(0), len);
+        assert_eq!(out.original_values, tvec![0, 100]);
+        assert_eq!(out.lerped_values, tvec![30, 130]);
+
+        // Inserting at (or after) the end does nothing.
+        let out = lerp.with_inserted_gap(t(100), len);
+        assert_eq!(out, lerp);
+    }
+
     #[test]
     fn unlerp() {
         let lerp = Lerp::new(tvec![1, 101], tvec![201, 301]);
@@ -1,7 +1,10 @@
+mod background;
+mod camera;
 mod curve;
 mod draw_snippet;
 mod effect;
 mod lerp;
+mod render;
 pub mod save;
 mod shape_detect;
 mod simplify;
@@ -9,9 +12,12 @@ mod smooth;
 mod span_cursor;
 mod time;
 
+pub use crate::background::{Background, PaperTemplate};
+pub use crate::camera::{CameraTrack, Viewport};
 pub use crate::curve::{StrokeInProgress, StrokeRef, StrokeSeq, StrokeStyle};
 pub use crate::draw_snippet::{DrawCursor, DrawSnippet, DrawSnippetId, DrawSnippets};
 pub use crate::effect::{Effect, Effects, FadeEffect};
 pub use crate::lerp::Lerp;
+pub use crate::render::render_frame;
 pub use crate::span_cursor::{Cursor, Span};
 pub use crate::time::{Time, TimeDiff, TimeSpan};
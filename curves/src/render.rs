@@ -0,0 +1,75 @@
+//! Rendering single frames of an animation, without any UI.
+
+use druid::kurbo::TranslateScale;
+use druid::piet::{self, Device, ImageFormat};
+use druid::{Rect, RenderContext};
+
+use crate::{Background, CameraTrack, DrawSnippets, Time};
+
+/// Renders the animation at the given time into an RGBA buffer of the given size.
+///
+/// The returned buffer has `4 * width * height` bytes, in row-major order. The frame shows
+/// whatever the camera sees at `time`, scaled to fill `width`; if the aspect ratio of `width` and
+/// `height` doesn't match the animation's aspect ratio, the bottom of the frame will be either
+/// cropped or extended.
+///
+/// Since the background is opaque, it doesn't matter whether you interpret the alpha channel as
+/// premultiplied or not.
+pub fn render_frame(
+    snippets: &DrawSnippets,
+    background: &Background,
+    camera: &CameraTrack,
+    time: Time,
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, piet::Error> {
+    let mut device = Device::new()?;
+    let mut bitmap = device.bitmap_target(width, height, 1.0)?;
+    let transform = TranslateScale::scale(width as f64) * camera.viewport_at(time).to_page();
+    let frame = Rect::new(0.0, 0.0, width as f64, height as f64);
+
+    {
+        let mut ctx = bitmap.render_context();
+        ctx.with_save(|ctx| {
+            ctx.clip(frame);
+            ctx.transform(transform.into());
+            background.render(ctx, transform.inverse() * frame);
+            for (_, snip) in snippets.snippets() {
+                snip.render(ctx, time);
+            }
+            Ok(())
+        })?;
+        ctx.finish()?;
+    }
+
+    let mut buf = vec![0; width * height * 4];
+    bitmap.copy_raw_pixels(ImageFormat::RgbaPremul, &mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use druid::Color;
+
+    #[test]
+    fn empty_frame() {
+        let background = Background {
+            color: Color::rgb8(10, 20, 30),
+            ..Background::default()
+        };
+        let buf = render_frame(
+            &DrawSnippets::default(),
+            &background,
+            &CameraTrack::default(),
+            Time::ZERO,
+            8,
+            6,
+        )
+        .unwrap();
+        assert_eq!(buf.len(), 8 * 6 * 4);
+        for pixel in buf.chunks(4) {
+            assert_eq!(pixel, &[10, 20, 30, 255]);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use druid::im::HashMap;
 use druid::{Data, Lens, LocalizedString, WidgetExt, WindowDesc, WindowId};
 
+use crate::autosave::Recovery;
 use crate::menus;
 use crate::widgets::Editor;
 use crate::EditorState;
@@ -31,13 +32,19 @@ impl Lens<AppState, EditorState> for EditorLens {
 }
 
 impl AppState {
-    pub fn add_editor(&mut self, state: EditorState) -> WindowDesc<AppState> {
+    /// Adds a new editor window. If there are some unsaved changes left over from last time, the
+    /// editor will offer to restore them.
+    pub fn add_editor(
+        &mut self,
+        state: EditorState,
+        recovery: Option<Recovery>,
+    ) -> WindowDesc<AppState> {
         let id = self.next_editor_id;
         self.next_editor_id += 1;
 
         self.editors.insert(id, state.clone());
 
-        let ret = WindowDesc::new(Editor::new().lens(EditorLens(id)))
+        let ret = WindowDesc::new(Editor::new().with_recovery(recovery).lens(EditorLens(id)))
             .title(LocalizedString::new("Scribl"))
             .menu(|id, data, _env| menus::make_menu(id, data))
             .window_size((800.0, 600.0));
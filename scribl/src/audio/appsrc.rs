@@ -8,6 +8,7 @@ use gstreamer_audio as gst_audio;
 
 use scribl_curves::{Cursor, Time};
 
+use super::snippets::write_limited;
 use super::{OutputData, SAMPLE_RATE};
 
 /// Creates a gstreamer AppSrc element that mixes our audio and provides it to a gstreamer
@@ -24,60 +25,75 @@ pub fn create_appsrc(rx: Receiver<OutputData>, name: &str) -> Result<gst::Elemen
 
     let mut data = OutputData::new();
     let mut cursor = Cursor::empty(0);
-    let mut need_audio_data_inner = move |src: &gst_app::AppSrc,
-                                          size_hint: u32|
-          -> anyhow::Result<()> {
-        for new_data in rx.try_iter() {
-            data = new_data;
-            let idx = data.start_time.as_audio_idx(SAMPLE_RATE);
-            cursor = Cursor::new(data.snips.snippet_spans(), idx, idx);
-        }
-        if data.forwards() && cursor.is_finished() || !data.forwards() && cursor.current().1 == 0 {
-            let _ = src.end_of_stream();
-            return Ok(());
-        }
-
-        let size = size_hint as usize / 2;
-
-        // gstreamer buffers seem to only ever hand out [u8], but we prefer to work with
-        // [i16]s. Here, we're doing an extra copy to handle endian-ness and avoid unsafe.
-        let mut buf = vec![0i16; size];
-        if data.forwards() {
-            let prev_end = cursor.current().1;
-            cursor.advance_to(prev_end, prev_end + buf.len());
-        } else {
-            let prev_start = cursor.current().0;
-            cursor.advance_to(prev_start.saturating_sub(buf.len()), prev_start);
-        }
-        data.snips.mix_to(&cursor, &mut buf[..]);
-        let time = Time::from_audio_idx(cursor.current().0, SAMPLE_RATE);
+    let mut music_cursor = Cursor::empty(0);
+    let mut need_audio_data_inner =
+        move |src: &gst_app::AppSrc, size_hint: u32| -> anyhow::Result<()> {
+            for new_data in rx.try_iter() {
+                data = new_data;
+                let idx = data.start_time.as_audio_idx(SAMPLE_RATE);
+                cursor = Cursor::new(data.snips.snippet_spans(), idx, idx);
+                music_cursor = Cursor::new(data.music.snippet_spans(), idx, idx);
+            }
+            let finished = if data.forwards() {
+                cursor.is_finished() && music_cursor.is_finished()
+            } else {
+                cursor.current().1 == 0
+            };
+            if finished {
+                let _ = src.end_of_stream();
+                return Ok(());
+            }
 
-        let mut gst_buffer = gst::Buffer::with_size(size * 2)?;
-        {
-            let gst_buffer_ref = gst_buffer
-                .get_mut()
-                .ok_or(anyhow!("couldn't get mut buffer"))?;
+            let size = size_hint as usize / 2;
 
-            let time = if data.forwards() {
-                time
+            // gstreamer buffers seem to only ever hand out [u8], but we prefer to work with
+            // [i16]s. Here, we're doing an extra copy to handle endian-ness and avoid unsafe.
+            let mut buf = vec![0i16; size];
+            let (start, end) = if data.forwards() {
+                let prev_end = cursor.current().1;
+                (prev_end, prev_end + buf.len())
             } else {
-                data.start_time + (data.start_time - time)
+                let prev_start = cursor.current().0;
+                (prev_start.saturating_sub(buf.len()), prev_start)
             };
-            gst_buffer_ref.set_pts(gst::ClockTime::from_useconds(time.as_micros() as u64));
-            let mut gst_buf = gst_buffer_ref.map_writable()?;
-            if data.forwards() {
-                for (idx, bytes) in gst_buf.as_mut_slice().chunks_mut(2).enumerate() {
-                    bytes.copy_from_slice(&buf[idx].to_le_bytes());
-                }
-            } else {
-                for (idx, bytes) in gst_buf.as_mut_slice().chunks_mut(2).rev().enumerate() {
-                    bytes.copy_from_slice(&buf[idx].to_le_bytes());
+            cursor.advance_to(start, end);
+            music_cursor.advance_to(start, end);
+
+            // Everything goes through the limiter together, after the gain is applied.
+            let mut acc = vec![0.0f32; size];
+            data.mix_to(&cursor, &music_cursor, &mut acc);
+            for x in &mut acc {
+                *x *= data.gain;
+            }
+            write_limited(&acc, &mut buf);
+            let time = Time::from_audio_idx(cursor.current().0, SAMPLE_RATE);
+
+            let mut gst_buffer = gst::Buffer::with_size(size * 2)?;
+            {
+                let gst_buffer_ref = gst_buffer
+                    .get_mut()
+                    .ok_or(anyhow!("couldn't get mut buffer"))?;
+
+                let time = if data.forwards() {
+                    time
+                } else {
+                    data.start_time + (data.start_time - time)
+                };
+                gst_buffer_ref.set_pts(gst::ClockTime::from_useconds(time.as_micros() as u64));
+                let mut gst_buf = gst_buffer_ref.map_writable()?;
+                if data.forwards() {
+                    for (idx, bytes) in gst_buf.as_mut_slice().chunks_mut(2).enumerate() {
+                        bytes.copy_from_slice(&buf[idx].to_le_bytes());
+                    }
+                } else {
+                    for (idx, bytes) in gst_buf.as_mut_slice().chunks_mut(2).rev().enumerate() {
+                        bytes.copy_from_slice(&buf[idx].to_le_bytes());
+                    }
                 }
             }
-        }
-        let _ = src.push_buffer(gst_buffer);
-        Ok(())
-    };
+            let _ = src.push_buffer(gst_buffer);
+            Ok(())
+        };
 
     let need_audio_data = move |src: &gst_app::AppSrc, size_hint: u32| {
         if let Err(e) = need_audio_data_inner(src, size_hint) {
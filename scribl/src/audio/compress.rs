@@ -0,0 +1,303 @@
+//! Lossless compression for audio buffers.
+//!
+//! This is a simplified version of what FLAC does: we split the audio into blocks, and for each
+//! block we choose one of a few fixed polynomial predictors. The prediction residuals are then
+//! stored using Rice codes. Speech compresses to about half its original size this way, and
+//! (unlike with general-purpose compressors) both compression and decompression are fast.
+//!
+//! The compressed format is:
+//!
+//! - the number of samples, as a little-endian `u64`;
+//! - a bitstream (most significant bit first) containing, for each block:
+//!   - the predictor order, in 2 bits;
+//!   - the Rice parameter, in 5 bits;
+//!   - the first `order` samples of the block, verbatim, in 16 bits each;
+//!   - the remaining residuals, Rice-encoded.
+//!
+//! This format is part of the save file, so it needs to remain stable.
+
+/// The number of samples in each compressed block.
+const BLOCK_SIZE: usize = 4096;
+
+/// The maximum order of the polynomial predictors.
+const MAX_ORDER: usize = 3;
+
+/// If the quotient of a Rice-encoded value is at least this large, we write it in a fixed-length
+/// "escaped" form instead.
+const ESCAPE_QUOTIENT: u32 = 32;
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid compressed audio: {0}")]
+pub struct DecompressError(&'static str);
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    // Bits that haven't been written to `bytes` yet, aligned to the least significant bit.
+    acc: u64,
+    acc_len: u32,
+}
+
+impl BitWriter {
+    fn new(bytes: Vec<u8>) -> BitWriter {
+        BitWriter {
+            bytes,
+            acc: 0,
+            acc_len: 0,
+        }
+    }
+
+    // Writes the `len` least significant bits of `val`. `len` must be at most 32.
+    fn write(&mut self, val: u32, len: u32) {
+        debug_assert!(len <= 32);
+        if len == 0 {
+            return;
+        }
+        let mask = (1u64 << len) - 1;
+        self.acc = (self.acc << len) | (val as u64 & mask);
+        self.acc_len += len;
+        while self.acc_len >= 8 {
+            self.acc_len -= 8;
+            self.bytes.push((self.acc >> self.acc_len) as u8);
+        }
+    }
+
+    fn write_unary(&mut self, mut q: u32) {
+        while q >= 32 {
+            self.write(u32::MAX, 32);
+            q -= 32;
+        }
+        // q ones, followed by a zero.
+        self.write(((1u64 << (q + 1)) - 2) as u32, q + 1);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.acc_len > 0 {
+            let pad = 8 - self.acc_len;
+            self.write(0, pad);
+        }
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    acc: u64,
+    acc_len: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            bytes,
+            pos: 0,
+            acc: 0,
+            acc_len: 0,
+        }
+    }
+
+    fn read(&mut self, len: u32) -> Result<u32, DecompressError> {
+        debug_assert!(len <= 32);
+        while self.acc_len < len {
+            let byte = *self
+                .bytes
+                .get(self.pos)
+                .ok_or(DecompressError("unexpected end of data"))?;
+            self.pos += 1;
+            self.acc = (self.acc << 8) | byte as u64;
+            self.acc_len += 8;
+        }
+        self.acc_len -= len;
+        let mask = (1u64 << len) - 1;
+        Ok(((self.acc >> self.acc_len) & mask) as u32)
+    }
+
+    fn read_unary(&mut self) -> Result<u32, DecompressError> {
+        let mut q = 0;
+        while self.read(1)? == 1 {
+            q += 1;
+        }
+        Ok(q)
+    }
+}
+
+fn zigzag(x: i32) -> u32 {
+    ((x << 1) ^ (x >> 31)) as u32
+}
+
+fn unzigzag(x: u32) -> i32 {
+    ((x >> 1) as i32) ^ -((x & 1) as i32)
+}
+
+// Predicts the sample at `idx` using the fixed polynomial predictor of the given order. Requires
+// `idx >= order`.
+fn predict(samples: &[i32], idx: usize, order: usize) -> i32 {
+    match order {
+        0 => 0,
+        1 => samples[idx - 1],
+        2 => 2 * samples[idx - 1] - samples[idx - 2],
+        _ => 3 * samples[idx - 1] - 3 * samples[idx - 2] + samples[idx - 3],
+    }
+}
+
+// Chooses the Rice parameter for a block of zigzagged residuals.
+fn rice_parameter(residuals: &[u32]) -> u32 {
+    if residuals.is_empty() {
+        return 0;
+    }
+    let mean = residuals.iter().map(|&r| r as u64).sum::<u64>() / residuals.len() as u64;
+    // The optimal parameter is roughly log2 of the mean.
+    (64 - mean.leading_zeros()).saturating_sub(1).min(31)
+}
+
+fn write_block(out: &mut BitWriter, block: &[i32]) {
+    let order = MAX_ORDER.min(block.len());
+    let mut best_order = 0;
+    let mut best_residuals = Vec::new();
+    let mut best_cost = u64::MAX;
+    for order in 0..=order {
+        let residuals: Vec<u32> = (order..block.len())
+            .map(|i| zigzag(block[i] - predict(block, i, order)))
+            .collect();
+        let cost = residuals.iter().map(|&r| r as u64).sum();
+        if cost < best_cost {
+            best_cost = cost;
+            best_order = order;
+            best_residuals = residuals;
+        }
+    }
+
+    let k = rice_parameter(&best_residuals);
+    out.write(best_order as u32, 2);
+    out.write(k, 5);
+    for &s in &block[..best_order] {
+        out.write(s as u16 as u32, 16);
+    }
+    for r in best_residuals {
+        let q = r >> k;
+        if q >= ESCAPE_QUOTIENT {
+            out.write_unary(ESCAPE_QUOTIENT);
+            out.write(r, 32);
+        } else {
+            out.write_unary(q);
+            out.write(r, k);
+        }
+    }
+}
+
+/// Compresses a buffer of audio samples.
+pub fn compress(samples: &[i16]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(samples.len() + 8);
+    out.extend_from_slice(&(samples.len() as u64).to_le_bytes());
+    let mut out = BitWriter::new(out);
+
+    let mut block = Vec::with_capacity(BLOCK_SIZE);
+    for chunk in samples.chunks(BLOCK_SIZE) {
+        block.clear();
+        block.extend(chunk.iter().map(|&s| s as i32));
+        write_block(&mut out, &block);
+    }
+    out.finish()
+}
+
+/// Decompresses a buffer of audio samples that was compressed using [`compress`].
+pub fn decompress(data: &[u8]) -> Result<Vec<i16>, DecompressError> {
+    if data.len() < 8 {
+        return Err(DecompressError("missing length"));
+    }
+    let mut len_bytes = [0u8; 8];
+    len_bytes.copy_from_slice(&data[..8]);
+    let len = u64::from_le_bytes(len_bytes) as usize;
+    let mut input = BitReader::new(&data[8..]);
+
+    // Every sample takes at least one bit, so this is a cheap sanity check that protects us from
+    // allocating huge buffers for corrupted data.
+    if len > (data.len() - 8).saturating_mul(8) {
+        return Err(DecompressError("length is too large"));
+    }
+
+    let mut ret = Vec::with_capacity(len);
+    let mut block = Vec::with_capacity(BLOCK_SIZE);
+    while ret.len() < len {
+        let block_len = BLOCK_SIZE.min(len - ret.len());
+        let order = input.read(2)? as usize;
+        let k = input.read(5)?;
+        if order > block_len {
+            return Err(DecompressError("invalid predictor order"));
+        }
+
+        block.clear();
+        for _ in 0..order {
+            block.push(input.read(16)? as u16 as i16 as i32);
+        }
+        for i in order..block_len {
+            let q = input.read_unary()?;
+            let r = if q >= ESCAPE_QUOTIENT {
+                input.read(32)?
+            } else {
+                (q << k) | input.read(k)?
+            };
+            let sample = predict(&block, i, order).wrapping_add(unzigzag(r));
+            if sample < i16::MIN as i32 || sample > i16::MAX as i32 {
+                return Err(DecompressError("sample out of range"));
+            }
+            block.push(sample);
+        }
+        ret.extend(block.iter().map(|&s| s as i16));
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_round_trip(samples: &[i16]) {
+        let compressed = compress(samples);
+        assert_eq!(decompress(&compressed).unwrap(), samples);
+    }
+
+    // A simple deterministic pseudo-random number generator, so that we don't need to depend on
+    // `rand`.
+    fn noise(len: usize, seed: u32) -> Vec<i16> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        check_round_trip(&[]);
+        check_round_trip(&[0]);
+        check_round_trip(&[1, -1]);
+        check_round_trip(&[i16::MIN, i16::MAX, i16::MIN, i16::MAX, 0, i16::MIN]);
+        check_round_trip(&vec![0; 3 * BLOCK_SIZE + 17]);
+        check_round_trip(&noise(BLOCK_SIZE - 1, 1));
+        check_round_trip(&noise(5 * BLOCK_SIZE + 3, 2));
+
+        let sine: Vec<i16> = (0..(2 * BLOCK_SIZE + 100))
+            .map(|i| ((i as f64 / 20.0).sin() * 20000.0) as i16)
+            .collect();
+        check_round_trip(&sine);
+    }
+
+    #[test]
+    fn compresses_smooth_audio() {
+        let sine: Vec<i16> = (0..48000)
+            .map(|i| ((i as f64 / 50.0).sin() * 10000.0) as i16)
+            .collect();
+        let compressed = compress(&sine);
+        assert!(compressed.len() < sine.len());
+    }
+
+    #[test]
+    fn corrupted() {
+        let compressed = compress(&noise(1000, 3));
+        assert!(decompress(&compressed[..compressed.len() / 2]).is_err());
+        assert!(decompress(&compressed[..4]).is_err());
+    }
+}
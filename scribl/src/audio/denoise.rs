@@ -0,0 +1,151 @@
+use nnnoiseless::DenoiseState;
+
+use super::InputConfig;
+
+// We don't simply drop frames where voice was not detected: doing so tends to cut off consonants
+// like "t". Instead, we do some "smoothing in time": if no voice was detected within
+// `VOICELESS_FRAME_LAG` frames (either forwards or backwards in time) of the current frame, we
+// drop the current frame.
+const VOICELESS_FRAME_LAG: usize = 10;
+
+/// Runs the noise removal on some raw audio, returning the denoised audio and (for every frame of
+/// size `DenoiseState::FRAME_SIZE`) an estimate of how likely that frame was to contain speech.
+///
+/// If `config` says not to remove noise, the audio is returned unchanged and every frame is
+/// considered to contain speech.
+fn denoise(raw: &[i16], config: &InputConfig, progress: impl FnMut(f64)) -> (Vec<i16>, Vec<f32>) {
+    if !config.remove_noise {
+        let frames = (raw.len() + DenoiseState::FRAME_SIZE - 1) / DenoiseState::FRAME_SIZE;
+        return (raw.to_owned(), vec![1.0; frames]);
+    }
+    denoise_with_progress(raw, progress)
+}
+
+/// Runs the noise removal on some raw audio, regardless of the config.
+///
+/// This can take a while on long snippets, so every now and then we call `progress` with the
+/// fraction of the work that has been done so far.
+pub fn denoise_with_progress(raw: &[i16], mut progress: impl FnMut(f64)) -> (Vec<i16>, Vec<f32>) {
+    let mut state = DenoiseState::new();
+    let mut in_buf = vec![0.0; DenoiseState::FRAME_SIZE];
+    let mut out_buf = vec![0.0; DenoiseState::FRAME_SIZE];
+    let mut buf = Vec::with_capacity(raw.len());
+    let mut vad = Vec::new();
+    let frames = raw.chunks(DenoiseState::FRAME_SIZE).len();
+    for (i, frame) in raw.chunks(DenoiseState::FRAME_SIZE).enumerate() {
+        if i % 100 == 0 {
+            progress(i as f64 / frames as f64);
+        }
+        // The last frame might be short, so we pad it with silence.
+        for (dst, src) in in_buf
+            .iter_mut()
+            .zip(frame.iter().chain(std::iter::repeat(&0)))
+        {
+            *dst = *src as f32;
+        }
+        vad.push(state.process_frame(&mut out_buf, &in_buf));
+        buf.extend(out_buf[..frame.len()].iter().map(|x| x.round() as i16));
+    }
+    (buf, vad)
+}
+
+/// Silences the frames of `buf` that (according to the voice detection probabilities in `vad`)
+/// don't contain speech, fading in and out at the boundaries.
+pub fn gate(buf: &mut [i16], vad: &[f32], vad_threshold: f32) {
+    // Which frames are worth keeping, according to voice detection?
+    let mut keep: Vec<_> = vad.iter().map(|&v| v > vad_threshold).collect();
+    keep.push(false);
+    let mut weights = vec![0.0f32; keep.len()];
+    convolve_bools(&keep[..], &mut weights[..], VOICELESS_FRAME_LAG);
+
+    // Windows for fading in and out when voice is detected or not.
+    let constant = vec![1.0; DenoiseState::FRAME_SIZE];
+    let fade_out: Vec<_> = (0..DenoiseState::FRAME_SIZE)
+        .rev()
+        .map(|x| x as f32 / DenoiseState::FRAME_SIZE as f32)
+        .collect();
+    let fade_in: Vec<_> = (0..DenoiseState::FRAME_SIZE)
+        .map(|x| x as f32 / DenoiseState::FRAME_SIZE as f32)
+        .collect();
+
+    for (frame, w) in buf
+        .chunks_mut(DenoiseState::FRAME_SIZE)
+        .zip(weights.windows(2))
+    {
+        let window = if w[0] < w[1] {
+            &fade_in
+        } else if w[0] > w[1] {
+            &fade_out
+        } else {
+            &constant
+        };
+        let lo = w[0].min(w[1]);
+        let hi = w[0].max(w[1]);
+        for (x, &y) in frame.iter_mut().zip(window) {
+            let weight = lo + (lo - hi) * y;
+            *x = (*x as f32 * weight).round() as i16;
+        }
+    }
+}
+
+/// Runs the noise removal and voice detection on some raw audio, calling `progress` every now and
+/// then with the fraction of the work that's done.
+pub fn process(raw: &[i16], config: &InputConfig, progress: impl FnMut(f64)) -> Vec<i16> {
+    let (mut buf, vad) = denoise(raw, config, progress);
+    gate(&mut buf, &vad, config.vad_threshold);
+    buf
+}
+
+/// Given a slice of bools, modifies it so that everything within `width` of a `true` is set to
+/// `true`.
+fn convolve_bools(xs: &[bool], out: &mut [f32], width: usize) {
+    let mut count = width;
+    let next_count = |x, count| if x { 0 } else { count + 1 };
+    let step = 1.0 / width as f32;
+
+    let mut weight = 0.0f32;
+    for (&x, y) in xs.iter().zip(&mut out[..]) {
+        count = next_count(x, count);
+        if count <= width {
+            *y = 1.0;
+            weight = 1.0;
+        } else {
+            weight = (weight - step).max(0.0);
+            *y = weight;
+        }
+    }
+
+    count = width;
+    weight = 0.0;
+    for (&x, y) in xs.iter().zip(&mut out[..]).rev() {
+        count = next_count(x, count);
+        if count <= width {
+            *y = 1.0;
+            weight = 1.0;
+        } else {
+            weight = (weight - step).max(0.0);
+            *y = y.max(weight);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gating() {
+        let buf: Vec<i16> = (0..(DenoiseState::FRAME_SIZE * 3) as i16).collect();
+        let config = InputConfig {
+            remove_noise: false,
+            ..InputConfig::default()
+        };
+        // Without noise removal, everything counts as speech and so nothing changes.
+        assert_eq!(process(&buf, &config, |_| {}), buf);
+
+        // If nothing is speech, everything gets silenced.
+        let mut gated = buf.clone();
+        gate(&mut gated, &[0.0, 0.0, 0.0], 0.5);
+        assert!(gated.iter().all(|&x| x == 0));
+    }
+}
@@ -0,0 +1,56 @@
+//! Finding the audio devices (microphones, speakers, etc.) that are available.
+
+use gstreamer as gst;
+use gstreamer::prelude::*;
+
+const INPUT_CLASS: &str = "Audio/Source";
+const OUTPUT_CLASS: &str = "Audio/Sink";
+
+/// The names of the audio devices that are available on this system.
+#[derive(Clone, Debug, Default)]
+pub struct AudioDevices {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+fn devices(class: &str) -> Vec<gst::Device> {
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some(class), None);
+    if let Err(e) = monitor.start() {
+        log::error!("failed to start the device monitor: {}", e);
+        return Vec::new();
+    }
+    let ret = monitor.get_devices();
+    monitor.stop();
+    ret
+}
+
+fn find_device(class: &str, name: &str) -> Option<gst::Device> {
+    devices(class)
+        .into_iter()
+        .find(|d| d.get_display_name() == name)
+}
+
+/// Lists the audio devices that are currently available.
+pub fn list_devices() -> AudioDevices {
+    let names = |class| {
+        devices(class)
+            .iter()
+            .map(|d| d.get_display_name().to_string())
+            .collect()
+    };
+    AudioDevices {
+        inputs: names(INPUT_CLASS),
+        outputs: names(OUTPUT_CLASS),
+    }
+}
+
+/// Finds the audio input device with the given name.
+pub fn find_input(name: &str) -> Option<gst::Device> {
+    find_device(INPUT_CLASS, name)
+}
+
+/// Finds the audio output device with the given name.
+pub fn find_output(name: &str) -> Option<gst::Device> {
+    find_device(OUTPUT_CLASS, name)
+}
@@ -0,0 +1,116 @@
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+
+use scribl_curves::TimeDiff;
+
+use super::{TalkSnippets, SAMPLE_RATE};
+
+/// Settings for "ducking" the music (i.e. turning it down) while someone is talking.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Data, Debug, Deserialize, Lens, PartialEq, Serialize)]
+pub struct Ducking {
+    /// How much (in dB) to turn down the music while someone is talking. This should be negative
+    /// (or zero, to turn off ducking).
+    pub depth: f32,
+    /// How long before the talking starts that the music starts getting quieter. (Since we know
+    /// ahead of time when the talking will start, the music can be fully ducked by the time it
+    /// does.)
+    pub attack: TimeDiff,
+    /// How long after the talking stops that the music takes to get back to full volume.
+    pub release: TimeDiff,
+}
+
+impl Default for Ducking {
+    fn default() -> Ducking {
+        Ducking {
+            depth: -12.0,
+            attack: TimeDiff::from_micros(200_000),
+            release: TimeDiff::from_micros(500_000),
+        }
+    }
+}
+
+impl Ducking {
+    /// Turns down the music in `buf` wherever a talk snippet in `talk` is active. The first sample
+    /// in `buf` has index `start`.
+    ///
+    /// The amount of ducking only depends on the sample's position (and not on what was
+    /// ducked before), so it doesn't matter which direction we're playing in, or how the output
+    /// gets divided into buffers.
+    pub fn apply(&self, talk: &TalkSnippets, start: usize, buf: &mut [f32]) {
+        let attack = self.attack.as_audio_idx(SAMPLE_RATE).max(0) as usize;
+        let release = self.release.as_audio_idx(SAMPLE_RATE).max(0) as usize;
+        let end = start + buf.len();
+
+        // For each sample, how much it should be ducked (0.0 is not at all, 1.0 is fully).
+        let mut amount = vec![0.0f32; buf.len()];
+        for span in talk.snippet_spans() {
+            let talk_start = span.start;
+            let talk_end = span.end.unwrap_or(usize::MAX);
+            let lo = talk_start.saturating_sub(attack).max(start);
+            let hi = talk_end.saturating_add(release).min(end);
+            for idx in lo..hi {
+                let a = if idx < talk_start {
+                    1.0 - (talk_start - idx) as f32 / (attack + 1) as f32
+                } else if idx < talk_end {
+                    1.0
+                } else {
+                    1.0 - (idx - talk_end + 1) as f32 / (release + 1) as f32
+                };
+                let x = &mut amount[idx - start];
+                *x = x.max(a);
+            }
+        }
+
+        let floor = 10.0f32.powf(self.depth.min(0.0) / 20.0);
+        for (x, a) in buf.iter_mut().zip(amount) {
+            *x *= 1.0 - a * (1.0 - floor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::snippets::tests::ms;
+    use crate::audio::TalkSnippet;
+    use scribl_curves::Time;
+
+    #[test]
+    fn duck() {
+        let talk = TalkSnippets::default()
+            .with_new_snippet(TalkSnippet::new(
+                vec![1; ms(2)],
+                Time::from_micros(2000),
+                1.0,
+            ))
+            .0;
+        let ducking = Ducking {
+            depth: -20.0,
+            attack: TimeDiff::from_micros(1000),
+            release: TimeDiff::from_micros(1000),
+        };
+        let mut buf = vec![1.0; 300];
+        ducking.apply(&talk, 0, &mut buf);
+
+        // Before the attack, and after the release, nothing changes.
+        assert_eq!(buf[0], 1.0);
+        assert_eq!(buf[ms(1) - 1], 1.0);
+        assert_eq!(buf[ms(5)], 1.0);
+        // While talking, the music is 20dB quieter.
+        assert!((buf[ms(2)] - 0.1).abs() < 1e-6);
+        assert!((buf[ms(4) - 1] - 0.1).abs() < 1e-6);
+        // During the attack and release, it's in between.
+        let attack = ms(3) / 2;
+        let release = ms(9) / 2;
+        assert!(buf[attack] > 0.1 && buf[attack] < 1.0);
+        assert!(buf[release] > 0.1 && buf[release] < 1.0);
+        assert!(buf[release] > buf[release - 16]);
+
+        // Ducking part of the buffer gives the same result.
+        let mut part = vec![1.0; 100];
+        ducking.apply(&talk, 70, &mut part);
+        assert_eq!(&part[..], &buf[70..170]);
+    }
+}
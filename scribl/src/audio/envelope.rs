@@ -0,0 +1,156 @@
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+use std::sync::Arc;
+
+/// A piecewise-linear gain curve, for adjusting the volume of different parts of a snippet.
+///
+/// The envelope is described by a list of points, each consisting of a sample index (relative to
+/// the start of the snippet) and a gain. Between two points, the gain is interpolated linearly.
+/// Before the first point and after the last point, the gain is constant. An envelope with no
+/// points has gain 1 everywhere.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Data, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Envelope {
+    points: Arc<[(usize, f32)]>,
+}
+
+impl Default for Envelope {
+    fn default() -> Envelope {
+        Envelope {
+            points: Vec::new().into(),
+        }
+    }
+}
+
+impl Envelope {
+    fn from_points(mut points: Vec<(usize, f32)>) -> Envelope {
+        points.dedup_by_key(|p| p.0);
+        // If the envelope is flat at 1, we may as well not have it at all.
+        if points.iter().all(|p| p.1 == 1.0) {
+            points.clear();
+        }
+        Envelope {
+            points: points.into(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The gain at the sample with index `idx`.
+    pub fn gain(&self, idx: usize) -> f32 {
+        let next = self.points.partition_point(|p| p.0 <= idx);
+        if next == 0 {
+            self.points.first().map_or(1.0, |p| p.1)
+        } else if next == self.points.len() {
+            self.points[next - 1].1
+        } else {
+            let (i0, g0) = self.points[next - 1];
+            let (i1, g1) = self.points[next];
+            let t = (idx - i0) as f32 / (i1 - i0) as f32;
+            g0 + t * (g1 - g0)
+        }
+    }
+
+    /// Returns a new envelope, for a snippet in which the samples with indices in `[from, to)`
+    /// have been deleted.
+    pub fn with_deleted(&self, from: usize, to: usize) -> Envelope {
+        if self.is_empty() || from >= to {
+            return self.clone();
+        }
+
+        let mut points: Vec<_> = self
+            .points
+            .iter()
+            .copied()
+            .take_while(|p| p.0 < from)
+            .collect();
+        if from > 0 {
+            points.push((from - 1, self.gain(from - 1)));
+        }
+        points.push((from, self.gain(to)));
+        points.extend(
+            self.points
+                .iter()
+                .filter(|p| p.0 > to)
+                .map(|&(idx, gain)| (idx - (to - from), gain)),
+        );
+        Envelope::from_points(points)
+    }
+
+    /// Returns a new envelope in which the gain on the samples `[from, to)` has been multiplied by
+    /// `factor`. The change in gain is ramped in (and out) over `ramp` samples, starting at `from`
+    /// (and ending at `to`).
+    pub fn with_scaled_range(&self, from: usize, to: usize, factor: f32, ramp: usize) -> Envelope {
+        if from >= to {
+            return self.clone();
+        }
+        let ramp = ramp.min((to - from) / 2);
+        let scale = |idx: usize| {
+            if idx < from || idx >= to {
+                1.0
+            } else if idx < from + ramp {
+                1.0 + (factor - 1.0) * (idx - from) as f32 / ramp as f32
+            } else if idx + ramp >= to {
+                1.0 + (factor - 1.0) * (to - idx) as f32 / ramp as f32
+            } else {
+                factor
+            }
+        };
+
+        let mut idxs: Vec<_> = self.points.iter().map(|p| p.0).collect();
+        idxs.extend_from_slice(&[from, from + ramp, to - ramp, to]);
+        idxs.sort_unstable();
+        idxs.dedup();
+        let points = idxs
+            .into_iter()
+            .map(|idx| (idx, self.gain(idx) * scale(idx)))
+            .collect();
+        Envelope::from_points(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gain() {
+        let env = Envelope::from_points(vec![(10, 1.0), (20, 0.0)]);
+        assert_eq!(env.gain(0), 1.0);
+        assert_eq!(env.gain(15), 0.5);
+        assert_eq!(env.gain(20), 0.0);
+        assert_eq!(env.gain(100), 0.0);
+        assert_eq!(Envelope::default().gain(5), 1.0);
+    }
+
+    #[test]
+    fn deleted() {
+        let env = Envelope::from_points(vec![(10, 1.0), (20, 0.0), (30, 1.0)]);
+        let deleted = env.with_deleted(15, 25);
+        assert_eq!(deleted.gain(14), env.gain(14));
+        assert_eq!(deleted.gain(15), env.gain(25));
+        assert_eq!(deleted.gain(20), env.gain(30));
+
+        let deleted = env.with_deleted(0, 20);
+        assert_eq!(deleted.gain(0), 0.0);
+        assert_eq!(deleted.gain(10), 1.0);
+    }
+
+    #[test]
+    fn scaled_range() {
+        let env = Envelope::default().with_scaled_range(10, 30, 0.5, 4);
+        assert_eq!(env.gain(5), 1.0);
+        assert_eq!(env.gain(12), 0.75);
+        assert_eq!(env.gain(20), 0.5);
+        assert_eq!(env.gain(28), 0.75);
+        assert_eq!(env.gain(30), 1.0);
+
+        let env = env.with_scaled_range(10, 30, 2.0, 4);
+        assert!(env.is_empty());
+    }
+}
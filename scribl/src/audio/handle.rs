@@ -4,7 +4,7 @@ use druid::{ExtEventSink, Target};
 use scribl_curves::Time;
 
 use super::thread::{audio_loop, Cmd};
-use super::{OutputData, TalkSnippets};
+use super::OutputData;
 use crate::config::AudioInput as InputConfig;
 use crate::data::AudioState as State;
 
@@ -22,12 +22,40 @@ impl AudioHandle {
     ///
     /// TODO: figure out, and describe here, the conditions under which the audio thread shuts
     /// down.
-    pub fn initialize_audio(sink: ExtEventSink, target: Target) -> AudioHandle {
+    ///
+    /// The audio thread records from `input_device` and plays to `output_device` (or the default
+    /// devices, if these are `None`).
+    pub fn initialize_audio(
+        sink: ExtEventSink,
+        target: Target,
+        input_device: Option<String>,
+        output_device: Option<String>,
+    ) -> AudioHandle {
         let (tx, rx) = unbounded();
-        std::thread::spawn(move || audio_loop(rx, sink, target));
+        std::thread::spawn(move || audio_loop(rx, sink, target, input_device, output_device));
         AudioHandle { cmd_tx: tx }
     }
 
+    /// Switches to a different audio input device (or the default one, if `name` is `None`).
+    ///
+    /// If the device can't be found, the audio thread falls back to the default one and sends an
+    /// `AUDIO_DEVICE_MISSING` command.
+    pub fn set_input_device(&self, name: Option<String>) {
+        if let Err(e) = self.cmd_tx.send(Cmd::SetInputDevice(name)) {
+            log::error!("audio thread exited unexpectedly: {}", e);
+        }
+    }
+
+    /// Switches to a different audio output device (or the default one, if `name` is `None`).
+    ///
+    /// If the device can't be found, the audio thread falls back to the default one and sends an
+    /// `AUDIO_DEVICE_MISSING` command.
+    pub fn set_output_device(&self, name: Option<String>) {
+        if let Err(e) = self.cmd_tx.send(Cmd::SetOutputDevice(name)) {
+            log::error!("audio thread exited unexpectedly: {}", e);
+        }
+    }
+
     /// Changes the state of the audio (e.g. from idle to playing or recording).
     ///
     /// If the old state and the new state are the same, this does nothing (and does it pretty
@@ -42,16 +70,21 @@ impl AudioHandle {
         // A special case if we're keeping playing but just changing the speed.
         if let (
             Playing {
-                snips: old_snips, ..
+                snips: old_snips,
+                music: old_music,
+                ducking: old_ducking,
+                ..
             },
             Playing {
                 start_time,
                 velocity,
                 snips,
+                music,
+                ducking,
             },
         ) = (&old_state, &new_state)
         {
-            if snips == old_snips {
+            if snips == old_snips && music == old_music && ducking == old_ducking {
                 self.seek(*start_time, *velocity);
                 return;
             }
@@ -66,21 +99,26 @@ impl AudioHandle {
         match new_state {
             Playing {
                 snips,
+                music,
+                ducking,
+                start_time,
+                velocity,
+            } => self.play(OutputData {
+                snips,
+                music,
+                ducking,
+                gain: 1.0,
                 start_time,
                 velocity,
-            } => self.play(snips, start_time, velocity),
+            }),
             Recording { config, .. } => self.start_recording(config),
             Idle => {}
         }
     }
 
     /// Start playing audio.
-    fn play(&self, snips: TalkSnippets, start_time: Time, velocity: f64) {
-        if let Err(e) = self.cmd_tx.send(Cmd::Play(OutputData {
-            snips,
-            start_time,
-            velocity,
-        })) {
+    fn play(&self, data: OutputData) {
+        if let Err(e) = self.cmd_tx.send(Cmd::Play(data)) {
             log::error!("audio thread exited unexpectedly: {}", e);
         }
     }
@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use gstreamer_audio::{AudioFormat, AudioInfo};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::SAMPLE_RATE;
+
+// How long to wait for decoded samples before checking whether something went wrong.
+const POLL_INTERVAL: gst::ClockTime = gst::ClockTime(Some(100_000_000));
+
+/// Decodes the audio file at `path` (which can be in any format that gstreamer knows about, like
+/// WAV, FLAC, MP3 or OGG), returning its samples as mono audio at `SAMPLE_RATE`.
+///
+/// This blocks until the whole file is decoded, so it should probably be called on its own thread.
+pub fn decode_file(path: &Path) -> Result<Vec<i16>> {
+    let pipeline = gst::Pipeline::new(None);
+    let src = gst::ElementFactory::make("filesrc", Some("import-source"))?;
+    let decode = gst::ElementFactory::make("decodebin", Some("import-decode"))?;
+    let convert = gst::ElementFactory::make("audioconvert", Some("import-convert"))?;
+    let resample = gst::ElementFactory::make("audioresample", Some("import-resample"))?;
+    let sink = gst::ElementFactory::make("appsink", Some("import-sink"))?;
+
+    let location = path
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid path {}", path.display()))?;
+    src.set_property("location", &location)?;
+    pipeline.add_many(&[&src, &decode, &convert, &resample, &sink])?;
+    src.link(&decode)?;
+    gst::Element::link_many(&[&convert, &resample, &sink])?;
+
+    let sink = sink
+        .dynamic_cast::<gst_app::AppSink>()
+        .map_err(|_| anyhow!("bug: couldn't cast sink to an AppSink"))?;
+    let audio_info = AudioInfo::builder(AudioFormat::S16le, SAMPLE_RATE as u32, 1).build()?;
+    sink.set_caps(Some(&audio_info.to_caps()?));
+    // We want the samples as fast as we can decode them, not in real time.
+    sink.set_property("sync", &false)?;
+
+    // The decoder only creates its output pads once it knows what's in the file. If there's more
+    // than one audio stream, we take the first one.
+    let convert_weak = convert.downgrade();
+    decode.connect_pad_added(move |_, pad| {
+        let is_audio = pad
+            .get_current_caps()
+            .and_then(|caps| {
+                caps.get_structure(0)
+                    .map(|s| s.get_name().starts_with("audio/"))
+            })
+            .unwrap_or(false);
+        let sink_pad = convert_weak
+            .upgrade()
+            .and_then(|convert| convert.get_static_pad("sink"));
+        if let Some(sink_pad) = sink_pad {
+            if is_audio && !sink_pad.is_linked() {
+                if let Err(e) = pad.link(&sink_pad) {
+                    log::error!("failed to link decoder: {:?}", e);
+                }
+            }
+        }
+    });
+
+    // If the file doesn't have any audio, the decoder will never link to `convert`, and so we'll
+    // never get any data (or even an end-of-stream) from the sink.
+    let no_audio = Arc::new(AtomicBool::new(false));
+    let no_audio_clone = Arc::clone(&no_audio);
+    let convert_weak = convert.downgrade();
+    decode.connect_no_more_pads(move |_| {
+        let linked = convert_weak
+            .upgrade()
+            .and_then(|convert| convert.get_static_pad("sink"))
+            .map_or(false, |pad| pad.is_linked());
+        if !linked {
+            no_audio_clone.store(true, Ordering::SeqCst);
+        }
+    });
+
+    let result = pipeline
+        .set_state(gst::State::Playing)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| pull_all(&pipeline, &sink, &no_audio))
+        .map_err(|e| anyhow!("failed to decode {}: {}", path.display(), e));
+    if let Err(e) = pipeline.set_state(gst::State::Null) {
+        log::error!("failed to stop the import pipeline: {}", e);
+    }
+    result
+}
+
+// Reads all the samples from the sink, until the stream ends or something goes wrong.
+fn pull_all(
+    pipeline: &gst::Pipeline,
+    sink: &gst_app::AppSink,
+    no_audio: &AtomicBool,
+) -> Result<Vec<i16>> {
+    let bus = pipeline
+        .get_bus()
+        .ok_or_else(|| anyhow!("bug: pipeline has no bus"))?;
+    let mut buf = Vec::new();
+    loop {
+        // If something goes wrong, we won't get an end-of-stream. So instead of blocking on the
+        // sink, we wait for a little while and then check for errors.
+        if let Some(sample) = sink.try_pull_sample(POLL_INTERVAL) {
+            let buffer = sample
+                .get_buffer()
+                .ok_or_else(|| anyhow!("failed to get sample buffer"))?;
+            let buffer = buffer.map_readable()?;
+            buf.extend(
+                buffer
+                    .as_slice()
+                    .chunks_exact(2)
+                    .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])),
+            );
+        } else if sink.is_eos() {
+            break;
+        } else if let Some(msg) = bus.pop_filtered(&[gst::MessageType::Error]) {
+            if let gst::MessageView::Error(err) = msg.view() {
+                return Err(anyhow!("{}", err.get_error()));
+            }
+        } else if no_audio.load(Ordering::SeqCst) {
+            return Err(anyhow!("no audio found"));
+        }
+    }
+
+    if buf.is_empty() {
+        return Err(anyhow!("no audio found"));
+    }
+    Ok(buf)
+}
@@ -1,17 +1,30 @@
 //! This module is in charge of audio (both recording and playback).
 
-use scribl_curves::Time;
+use scribl_curves::{Cursor, Time};
 
 use crate::config::AudioInput as InputConfig;
 
 mod appsrc;
+mod compress;
+mod denoise;
+mod devices;
+mod ducking;
+mod envelope;
 mod handle;
+mod import;
+mod pauses;
+mod process;
 mod snippets;
 mod thread;
 
 pub use appsrc::create_appsrc;
+pub use devices::{list_devices, AudioDevices};
+pub use ducking::Ducking;
 pub use handle::AudioHandle;
-pub use snippets::{TalkSnippet, TalkSnippetId, TalkSnippets};
+pub use import::decode_file;
+pub use pauses::long_pauses;
+pub use process::{mix_normalizing_gain, process_snippet, AudioProcessing};
+pub use snippets::{v1, TalkSnippet, TalkSnippetId, TalkSnippets};
 
 /// We do all of our internal audio processing at 48kHz.
 pub const SAMPLE_RATE: u32 = 48000;
@@ -21,6 +34,14 @@ pub const SAMPLE_RATE: u32 = 48000;
 pub struct OutputData {
     /// The collection of audio snippets. They will be mixed into the final audio output.
     pub snips: TalkSnippets,
+    /// The background music. It gets mixed in below `snips`, and ducked whenever one of `snips`
+    /// is playing.
+    pub music: TalkSnippets,
+    /// How much to turn down the music while someone is talking.
+    pub ducking: Ducking,
+    /// A volume multiplier for the whole mix, applied just before the limiter. This is 1.0 except
+    /// when exporting, where it is used to normalize the loudness.
+    pub gain: f32,
     /// The time at which to start playing.
     pub start_time: Time,
     /// The velocity at which to play back the audio. (1.0 is normal, forwards, playback)
@@ -31,6 +52,9 @@ pub struct OutputData {
 pub struct AudioRecording {
     /// The audio signal.
     pub buf: Vec<i16>,
+    /// The audio signal as it was recorded, before noise removal (if the input config asked us
+    /// to keep it).
+    pub raw: Option<Vec<i16>>,
     /// The perceptual loudness (in dB) of the audio.
     pub loudness: f64,
     /// The peak (as a number in [0.0, 1.0]) of the signal.
@@ -50,6 +74,9 @@ impl OutputData {
     fn new() -> OutputData {
         OutputData {
             snips: TalkSnippets::default(),
+            music: TalkSnippets::default(),
+            ducking: Ducking::default(),
+            gain: 1.0,
             start_time: Time::ZERO,
             velocity: 1.0,
         }
@@ -58,4 +85,19 @@ impl OutputData {
     fn forwards(&self) -> bool {
         self.velocity > 0.0
     }
+
+    /// Mixes the music and the speech in the cursors' current range into `acc` (but doesn't apply
+    /// the gain or the limiter).
+    ///
+    /// The music gets ducked before the speech is mixed on top of it.
+    fn mix_to(
+        &self,
+        cursor: &Cursor<usize, TalkSnippetId>,
+        music_cursor: &Cursor<usize, TalkSnippetId>,
+        acc: &mut [f32],
+    ) {
+        self.music.mix_to_f32(music_cursor, acc);
+        self.ducking.apply(&self.snips, cursor.current().0, acc);
+        self.snips.mix_to_f32(cursor, acc);
+    }
 }
@@ -0,0 +1,146 @@
+//! Finding long pauses in speech.
+
+use scribl_curves::{Time, TimeDiff};
+
+use super::{TalkSnippet, TalkSnippets, SAMPLE_RATE};
+
+/// We decide whether the audio is silent in windows of this many samples (10ms).
+const WINDOW_SIZE: usize = SAMPLE_RATE as usize / 100;
+
+/// A window whose RMS level (after applying the snippet's volume) is below this is considered
+/// silent. This is about -40 dB.
+const SILENCE_THRESHOLD: f32 = 0.01 * i16::MAX as f32;
+
+// The silent and non-silent ranges of a snippet, as (start, end) pairs of sample indices on the
+// timeline.
+struct Ranges {
+    silent: Vec<(usize, usize)>,
+    loud: Vec<(usize, usize)>,
+}
+
+fn ranges(snip: &TalkSnippet) -> Ranges {
+    let offset = snip.start_time().as_audio_idx(SAMPLE_RATE);
+    let mut ret = Ranges {
+        silent: Vec::new(),
+        loud: Vec::new(),
+    };
+    for (i, window) in snip.buf().chunks(WINDOW_SIZE).enumerate() {
+        let sum_sq: f32 = window.iter().map(|&x| (x as f32) * (x as f32)).sum();
+        let rms = (sum_sq / window.len() as f32).sqrt() * snip.multiplier();
+        let start = offset + i * WINDOW_SIZE;
+        let end = start + window.len();
+        let list = if rms < SILENCE_THRESHOLD {
+            &mut ret.silent
+        } else {
+            &mut ret.loud
+        };
+        match list.last_mut() {
+            Some(last) if last.1 == start => last.1 = end,
+            _ => list.push((start, end)),
+        }
+    }
+    ret
+}
+
+/// Finds the pauses in the speech that are longer than `min_len`, returning them as a sorted list
+/// of `(start, end)` times.
+///
+/// A pause is a silent part of some snippet. If another snippet is making noise at the same time,
+/// it doesn't count as a pause.
+pub fn find_pauses(snips: &TalkSnippets, min_len: TimeDiff) -> Vec<(Time, Time)> {
+    let min_len = min_len.as_audio_idx(SAMPLE_RATE).max(0) as usize;
+    let all_ranges: Vec<_> = snips.snippets().map(|(_, snip)| ranges(snip)).collect();
+
+    let mut pauses = Vec::new();
+    for (i, r) in all_ranges.iter().enumerate() {
+        for &(start, end) in &r.silent {
+            let interrupted = all_ranges
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .flat_map(|(_, other)| other.loud.iter())
+                .any(|&(s, e)| s < end && start < e);
+            if end - start >= min_len && !interrupted {
+                pauses.push((start, end));
+            }
+        }
+    }
+
+    // If snippets overlap, they might both have silence in the same place.
+    pauses.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in pauses {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(start, end)| {
+            (
+                Time::from_audio_idx(start, SAMPLE_RATE),
+                Time::from_audio_idx(end, SAMPLE_RATE),
+            )
+        })
+        .collect()
+}
+
+/// Finds the pauses in the speech that are longer than `min_len` and that could be shortened to
+/// `target_len`.
+pub fn long_pauses(
+    snips: &TalkSnippets,
+    min_len: TimeDiff,
+    target_len: TimeDiff,
+) -> Vec<(Time, Time)> {
+    find_pauses(snips, min_len)
+        .into_iter()
+        .filter(|&(start, end)| end - start > target_len)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: i64) -> usize {
+        s as usize * SAMPLE_RATE as usize
+    }
+
+    fn t(s: i64) -> Time {
+        Time::from_micros(s * 1_000_000)
+    }
+
+    fn d(s: i64) -> TimeDiff {
+        TimeDiff::from_micros(s * 1_000_000)
+    }
+
+    // One second of noise, then three seconds of silence, then one second of noise.
+    fn speech_with_pause(start: Time) -> TalkSnippet {
+        let mut buf = vec![1000i16; secs(5)];
+        for x in &mut buf[secs(1)..secs(4)] {
+            *x = 0;
+        }
+        TalkSnippet::new(buf, start, 1.0)
+    }
+
+    #[test]
+    fn pauses() {
+        let snips = TalkSnippets::default();
+        let (snips, _) = snips.with_new_snippet(speech_with_pause(Time::ZERO));
+        assert_eq!(find_pauses(&snips, d(2)), vec![(t(1), t(4))]);
+        assert!(find_pauses(&snips, d(4)).is_empty());
+
+        assert_eq!(long_pauses(&snips, d(2), d(1)), vec![(t(1), t(4))]);
+        assert!(long_pauses(&snips, d(2), d(3)).is_empty());
+    }
+
+    #[test]
+    fn overlapping_speech() {
+        // The second snippet is talking during the first one's pause.
+        let snips = TalkSnippets::default();
+        let (snips, _) = snips.with_new_snippet(speech_with_pause(Time::ZERO));
+        let (snips, _) = snips.with_new_snippet(TalkSnippet::new(vec![1000; secs(1)], t(2), 1.0));
+        assert!(find_pauses(&snips, d(1)).is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+//! Offline processing of audio that is already in the document.
+//!
+//! When recording, the audio thread removes noise and normalizes the loudness of each snippet as
+//! it comes in. The functions here apply the same kinds of processing after the fact, so that
+//! they can also be used on snippets that were imported, or that were recorded with different
+//! settings.
+
+use anyhow::{anyhow, Result};
+use ebur128::EbuR128;
+
+use scribl_curves::{Cursor, Time};
+
+use super::denoise;
+use super::{InputConfig, OutputData, TalkSnippet, SAMPLE_RATE};
+
+/// The number of samples to measure at a time, when reporting progress.
+const CHUNK_SIZE: usize = SAMPLE_RATE as usize;
+
+/// The level (in dB, relative to full scale) above which the compressor starts turning down the
+/// volume.
+const COMPRESSOR_THRESHOLD: f32 = -30.0;
+
+/// Above the threshold, every `COMPRESSOR_RATIO` dB of extra input only gives 1 dB of extra output.
+const COMPRESSOR_RATIO: f32 = 3.0;
+
+/// How quickly (in seconds) the compressor reacts to the audio getting louder.
+const COMPRESSOR_ATTACK: f32 = 0.005;
+
+/// How quickly (in seconds) the compressor recovers after the audio gets quieter.
+const COMPRESSOR_RELEASE: f32 = 0.1;
+
+/// The most that normalization will ever turn up the volume. Audio that would need more than this
+/// is probably just silence (or close to it), and turning it up would only turn up the noise.
+const MAX_NORMALIZING_GAIN: f64 = 500.0;
+
+/// The different kinds of processing that we can apply to a snippet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioProcessing {
+    /// Run the noise removal (but not the voice detection) on the snippet.
+    RemoveNoise,
+    /// Adjust the snippet's volume so that its loudness is the target loudness in the config.
+    Normalize,
+    /// Squash the peaks of the snippet (taking its volume into account) so that they don't clip.
+    Limit,
+    /// Redo the noise removal and voice detection, starting from the raw recorded audio.
+    Reprocess,
+}
+
+impl AudioProcessing {
+    /// A description of this processing, suitable for the undo menu.
+    pub fn description(&self) -> &'static str {
+        match self {
+            AudioProcessing::RemoveNoise => "remove noise",
+            AudioProcessing::Normalize => "normalize volume",
+            AudioProcessing::Limit => "limit peaks",
+            AudioProcessing::Reprocess => "redo noise removal",
+        }
+    }
+}
+
+/// Returns the volume multiplier that takes audio with loudness `loudness` to the loudness
+/// `target` (both in dB).
+fn loudness_multiplier(loudness: f64, target: f64) -> f64 {
+    // Multiplying a signal by x has the effect of adding 20 * log_10(x) to the loudness.
+    10.0f64.powf((target - loudness) / 20.0)
+}
+
+/// Given the loudness (in dB) and peak (as a number in [0.0, 1.0]) of some audio, returns the
+/// volume multiplier that normalizes it to the loudness in `config`.
+pub fn normalizing_multiplier(loudness: f64, peak: f64, config: &InputConfig) -> f64 {
+    // If the audio is completely silent, there's nothing to normalize.
+    if !loudness.is_finite() {
+        return 1.0;
+    }
+    let multiplier = loudness_multiplier(loudness, config.target_loudness);
+    if config.compress {
+        // Compressed audio doesn't have many peaks left, and the limiter takes care of the ones
+        // that remain.
+        multiplier.min(MAX_NORMALIZING_GAIN)
+    } else {
+        // Truncate the multiplier so that we don't clip. (Also make sure the peak isn't really
+        // small, because often the sample is all-zero or close to it.)
+        multiplier.min(1.0 / peak.max(1.0 / MAX_NORMALIZING_GAIN))
+    }
+}
+
+/// Compresses the dynamic range of some audio, by turning down the parts that are louder than
+/// [`COMPRESSOR_THRESHOLD`].
+///
+/// This only ever makes things quieter; the idea is that the result can then be normalized to a
+/// louder level than the original could.
+pub fn compress(buf: &[i16]) -> Vec<i16> {
+    let attack = (-1.0 / (COMPRESSOR_ATTACK * SAMPLE_RATE as f32)).exp();
+    let release = (-1.0 / (COMPRESSOR_RELEASE * SAMPLE_RATE as f32)).exp();
+    let mut envelope = 0.0f32;
+    buf.iter()
+        .map(|&x| {
+            let level = (x as f32 / i16::MAX as f32).abs();
+            let coeff = if level > envelope { attack } else { release };
+            envelope = coeff * envelope + (1.0 - coeff) * level;
+
+            let level_db = 20.0 * envelope.max(1e-6).log10();
+            let excess_db = (level_db - COMPRESSOR_THRESHOLD).max(0.0);
+            let gain_db = -excess_db * (1.0 - 1.0 / COMPRESSOR_RATIO);
+            (x as f32 * 10.0f32.powf(gain_db / 20.0)).round() as i16
+        })
+        .collect()
+}
+
+fn loudness_meter() -> Result<EbuR128> {
+    EbuR128::new(
+        1,
+        SAMPLE_RATE,
+        ebur128::Mode::I | ebur128::Mode::SAMPLE_PEAK,
+    )
+    .map_err(|e| anyhow!("failed to measure loudness: {}", e))
+}
+
+/// Measures the loudness and peak of `buf`, calling `progress` after every chunk.
+fn measure(buf: &[i16], mut progress: impl FnMut(f64)) -> Result<(f64, f64)> {
+    let mut loudness = loudness_meter()?;
+    let chunks = buf.chunks(CHUNK_SIZE).len();
+    for (i, chunk) in buf.chunks(CHUNK_SIZE).enumerate() {
+        progress(i as f64 / chunks as f64);
+        loudness
+            .add_frames_i16(chunk)
+            .map_err(|e| anyhow!("failed to measure loudness: {}", e))?;
+    }
+    Ok((
+        loudness.loudness_global().unwrap_or(-f64::INFINITY),
+        loudness.sample_peak(0).unwrap_or(-f64::INFINITY),
+    ))
+}
+
+/// Measures the loudness of everything in `data` (mixed together, as it will be played) between
+/// `data.start_time` and `end`, and returns the gain that will bring it to `target_loudness`.
+pub fn mix_normalizing_gain(data: &OutputData, end: Time, target_loudness: f64) -> Result<f32> {
+    let mut loudness = loudness_meter()?;
+    let start = data.start_time.as_audio_idx(SAMPLE_RATE);
+    let end = end.as_audio_idx(SAMPLE_RATE);
+    let mut cursor = Cursor::new(data.snips.snippet_spans(), start, start);
+    let mut music_cursor = Cursor::new(data.music.snippet_spans(), start, start);
+    let mut acc = vec![0.0f32; CHUNK_SIZE];
+
+    let mut idx = start;
+    while idx < end {
+        let chunk_end = (idx + CHUNK_SIZE).min(end);
+        cursor.advance_to(idx, chunk_end);
+        music_cursor.advance_to(idx, chunk_end);
+        let acc = &mut acc[..(chunk_end - idx)];
+        for x in acc.iter_mut() {
+            *x = 0.0;
+        }
+        data.mix_to(&cursor, &music_cursor, acc);
+        // The loudness meter wants floating-point samples to be in [-1.0, 1.0].
+        for x in acc.iter_mut() {
+            *x /= i16::MAX as f32;
+        }
+        loudness
+            .add_frames_f32(acc)
+            .map_err(|e| anyhow!("failed to measure loudness: {}", e))?;
+        idx = chunk_end;
+    }
+
+    match loudness.loudness_global() {
+        // If everything is silent, there's nothing to normalize.
+        Ok(x) if x.is_finite() => Ok(loudness_multiplier(x, target_loudness) as f32),
+        _ => Ok(1.0),
+    }
+}
+
+/// Applies some processing to a snippet, returning the processed snippet.
+///
+/// This can take a while, so it should be run in the background. Every now and then, it calls
+/// `progress` with the fraction of the work that's done.
+pub fn process_snippet(
+    snip: &TalkSnippet,
+    op: AudioProcessing,
+    config: &InputConfig,
+    progress: impl FnMut(f64),
+) -> Result<TalkSnippet> {
+    match op {
+        AudioProcessing::RemoveNoise => {
+            let (buf, _vad) = denoise::denoise_with_progress(snip.buf(), progress);
+            Ok(snip.with_buf(buf))
+        }
+        AudioProcessing::Normalize => {
+            let (loudness, peak) = measure(snip.buf(), progress)?;
+            let multiplier = normalizing_multiplier(loudness, peak, config) as f32;
+            Ok(snip.multiplied(multiplier / snip.multiplier()))
+        }
+        AudioProcessing::Limit => Ok(snip.limited()),
+        AudioProcessing::Reprocess => snip
+            .reprocessed(config, progress)
+            .ok_or_else(|| anyhow!("the raw audio wasn't kept")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        let config = InputConfig::default();
+        let buf: Vec<i16> = (0..SAMPLE_RATE as i32 * 2)
+            .map(|i| if i % 2 == 0 { 100 } else { -100 })
+            .collect();
+        let snip = TalkSnippet::new(buf, Time::ZERO, 0.5);
+        let normalized =
+            process_snippet(&snip, AudioProcessing::Normalize, &config, |_| {}).unwrap();
+        let (loudness, peak) = measure(normalized.buf(), |_| {}).unwrap();
+        let expected = normalizing_multiplier(loudness, peak, &config) as f32;
+        assert!((normalized.multiplier() - expected).abs() < 1e-4);
+        assert!(normalized.multiplier() > 1.0);
+        assert_eq!(normalized.buf(), snip.buf());
+
+        // Normalizing twice doesn't change anything.
+        let again =
+            process_snippet(&normalized, AudioProcessing::Normalize, &config, |_| {}).unwrap();
+        assert!((again.multiplier() - normalized.multiplier()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn normalize_silence() {
+        for &compress in &[false, true] {
+            let config = InputConfig {
+                compress,
+                ..InputConfig::default()
+            };
+            let snip = TalkSnippet::new(vec![0; SAMPLE_RATE as usize], Time::ZERO, 1.0);
+            let normalized =
+                process_snippet(&snip, AudioProcessing::Normalize, &config, |_| {}).unwrap();
+            assert_eq!(normalized.multiplier(), 1.0);
+
+            // Very quiet audio doesn't get turned up without bound.
+            assert!(normalizing_multiplier(-200.0, 0.0, &config) <= MAX_NORMALIZING_GAIN);
+        }
+    }
+
+    #[test]
+    fn limit() {
+        let snip = TalkSnippet::new(vec![0, 1000, -1000, i16::MAX, i16::MIN], Time::ZERO, 4.0);
+        let config = InputConfig::default();
+        let limited = process_snippet(&snip, AudioProcessing::Limit, &config, |_| {}).unwrap();
+        assert_eq!(limited.multiplier(), 1.0);
+        assert_eq!(&limited.buf()[..3], &[0, 4000, -4000]);
+        assert_eq!(limited.buf()[3], i16::MAX);
+        assert_eq!(limited.buf()[4], -i16::MAX);
+    }
+
+    #[test]
+    fn compression() {
+        let quiet = vec![100i16; SAMPLE_RATE as usize];
+        assert_eq!(compress(&quiet), quiet);
+
+        // Loud audio gets turned down, but not all the way to the threshold.
+        let loud = vec![i16::MAX / 2; SAMPLE_RATE as usize];
+        let compressed = compress(&loud);
+        let threshold = 10.0f32.powf(COMPRESSOR_THRESHOLD / 20.0) * i16::MAX as f32;
+        assert!(compressed.iter().zip(&loud).all(|(c, l)| c <= l));
+        let last = *compressed.last().unwrap() as f32;
+        assert!(last < i16::MAX as f32 / 4.0);
+        assert!(last > threshold);
+    }
+}
@@ -1,6 +1,6 @@
 use druid::im::OrdMap;
 use druid::Data;
-use serde::de::Deserializer;
+use serde::de::{Deserializer, Visitor};
 use serde::ser::Serializer;
 use serde::{Deserialize, Serialize};
 
@@ -9,7 +9,42 @@ use std::sync::Arc;
 
 use scribl_curves::{Cursor, Span, Time, TimeDiff};
 
-use super::SAMPLE_RATE;
+use super::denoise;
+use super::envelope::Envelope;
+use super::process;
+use super::{InputConfig, SAMPLE_RATE};
+
+/// The length (in samples) of the crossfades that we add automatically when editing audio, so that
+/// the cuts don't click.
+pub const AUTO_FADE_LEN: usize = SAMPLE_RATE as usize / 200;
+
+/// When mixing, samples with absolute value below this are passed through unchanged; above this,
+/// the limiter starts to squash them.
+const LIMITER_THRESHOLD: f32 = 0.75 * i16::MAX as f32;
+
+/// A soft limiter: leaves quiet samples alone, and smoothly compresses loud ones so that they never
+/// go beyond the range of an `i16`.
+///
+/// This doesn't have any state (unlike a "real" limiter, which would have attack and release
+/// times), because we need to get the same output regardless of whether we're playing forwards or
+/// backwards, and regardless of how the output is divided into buffers.
+fn soft_limit(x: f32) -> i16 {
+    let headroom = i16::MAX as f32 - LIMITER_THRESHOLD;
+    let y = if x.abs() <= LIMITER_THRESHOLD {
+        x
+    } else {
+        let excess = (x.abs() - LIMITER_THRESHOLD) / headroom;
+        (LIMITER_THRESHOLD + headroom * excess.tanh()).copysign(x)
+    };
+    y.round() as i16
+}
+
+/// Passes the mixed samples in `acc` through the soft limiter, writing the result to `out`.
+pub fn write_limited(acc: &[f32], out: &mut [i16]) {
+    for (out, &x) in out.iter_mut().zip(acc) {
+        *out = soft_limit(x);
+    }
+}
 
 /// Each audio snippet is uniquely identified by one of these ids.
 // This is serialized as part of saving files, so its serialization format needs to remain
@@ -27,11 +62,28 @@ pub struct TalkSnippetId(u64);
 // stable.
 #[derive(Deserialize, Serialize, Clone, Data, PartialEq)]
 pub struct TalkSnippet {
+    #[serde(with = "compressed_buf")]
     buf: Arc<[i16]>,
     multiplier: f32,
     start_time: Time,
+    /// The number of samples at the beginning of the snippet over which the volume ramps up.
+    #[serde(default)]
+    fade_in: usize,
+    /// The number of samples at the end of the snippet over which the volume ramps down.
+    #[serde(default)]
+    fade_out: usize,
+    #[serde(default, skip_serializing_if = "Envelope::is_empty")]
+    envelope: Envelope,
+    /// The audio as it was recorded, before noise removal. If present, this is always the same
+    /// length as `buf`, and it gets edited (snipped, trimmed, etc.) along with `buf`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw: Option<RawBuf>,
 }
 
+#[derive(Deserialize, Serialize, Clone, Data, PartialEq)]
+#[serde(transparent)]
+struct RawBuf(#[serde(with = "compressed_buf")] Arc<[i16]>);
+
 /// A collection of [`TalkSnippet`](struct.TalkSnippet.html), each one
 /// identified by an [`TalkSnippetId`](struct.TalkSnippetId.html).
 #[derive(Clone, Data, Default, PartialEq)]
@@ -46,9 +98,90 @@ impl TalkSnippet {
             buf: buf.into(),
             multiplier,
             start_time,
+            fade_in: 0,
+            fade_out: 0,
+            envelope: Envelope::default(),
+            raw: None,
         }
     }
 
+    /// Returns a new snippet that remembers `raw` as the audio that was recorded before noise
+    /// removal, so that the noise removal can be redone later.
+    pub fn with_raw(&self, raw: Vec<i16>) -> TalkSnippet {
+        if raw.len() != self.buf.len() {
+            log::error!(
+                "raw audio has length {}, expected {}",
+                raw.len(),
+                self.buf.len()
+            );
+            return self.clone();
+        }
+        TalkSnippet {
+            raw: Some(RawBuf(raw.into())),
+            ..self.clone()
+        }
+    }
+
+    /// Do we have the raw recorded audio for this snippet?
+    pub fn has_raw(&self) -> bool {
+        self.raw.is_some()
+    }
+
+    /// Returns a new snippet in which the noise removal and voice detection (and compression, if
+    /// `config` asks for it) have been redone, starting from the raw recorded audio.
+    ///
+    /// Returns `None` if we don't have the raw audio for this snippet. Every now and then, we call
+    /// `progress` with the fraction of the work that's done.
+    pub fn reprocessed(
+        &self,
+        config: &InputConfig,
+        progress: impl FnMut(f64),
+    ) -> Option<TalkSnippet> {
+        let raw = self.raw.as_ref()?;
+        let mut buf = denoise::process(&raw.0, config, progress);
+        if config.compress {
+            buf = process::compress(&buf);
+        }
+        Some(TalkSnippet {
+            buf: buf.into(),
+            ..self.clone()
+        })
+    }
+
+    /// Returns a new snippet with the audio replaced by `buf`, which must have the same length as
+    /// the current audio. Everything else (volume, fades, raw audio) stays the same.
+    pub fn with_buf(&self, buf: Vec<i16>) -> TalkSnippet {
+        assert_eq!(buf.len(), self.buf.len());
+        TalkSnippet {
+            buf: buf.into(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a new snippet in which the volume multiplier has been applied to the audio, and the
+    /// result passed through the soft limiter. The new snippet has a multiplier of 1.
+    ///
+    /// The raw audio (if there is any) gets the same treatment, so that redoing the noise removal
+    /// doesn't change the volume.
+    pub fn limited(&self) -> TalkSnippet {
+        let limit = |buf: &[i16]| -> Vec<i16> {
+            buf.iter()
+                .map(|&x| soft_limit(x as f32 * self.multiplier))
+                .collect()
+        };
+        TalkSnippet {
+            buf: limit(&self.buf).into(),
+            raw: self.map_raw(limit),
+            multiplier: 1.0,
+            ..self.clone()
+        }
+    }
+
+    // Applies some editing operation to the raw buffer (if there is one).
+    fn map_raw(&self, f: impl FnOnce(&[i16]) -> Vec<i16>) -> Option<RawBuf> {
+        self.raw.as_ref().map(|raw| RawBuf(f(&raw.0).into()))
+    }
+
     pub fn buf(&self) -> &[i16] {
         &self.buf
     }
@@ -64,9 +197,8 @@ impl TalkSnippet {
 
     pub fn shifted(&self, shift: TimeDiff) -> TalkSnippet {
         TalkSnippet {
-            buf: Arc::clone(&self.buf),
-            multiplier: self.multiplier,
             start_time: self.start_time + shift,
+            ..self.clone()
         }
     }
 
@@ -76,10 +208,62 @@ impl TalkSnippet {
 
     pub fn multiplied(&self, factor: f32) -> TalkSnippet {
         TalkSnippet {
-            buf: Arc::clone(&self.buf),
             multiplier: self.multiplier * factor,
-            start_time: self.start_time,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a new snippet, with the volume between `from` and `to` multiplied by `factor`.
+    /// (Unlike [`TalkSnippet::multiplied`], this only affects part of the snippet.)
+    pub fn multiplied_range(&self, from: Time, to: Time, factor: f32) -> TalkSnippet {
+        let from_idx = self.idx(from);
+        let to_idx = self.idx(to);
+        let (from_idx, to_idx) = (from_idx.min(to_idx), from_idx.max(to_idx));
+        TalkSnippet {
+            envelope: self
+                .envelope
+                .with_scaled_range(from_idx, to_idx, factor, AUTO_FADE_LEN),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a new snippet that fades in from silence, reaching full volume at `time`.
+    pub fn with_fade_in_until(&self, time: Time) -> TalkSnippet {
+        TalkSnippet {
+            fade_in: self.idx(time),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a new snippet that starts fading out at `time`, reaching silence at the end.
+    pub fn with_fade_out_from(&self, time: Time) -> TalkSnippet {
+        TalkSnippet {
+            fade_out: self.buf.len() - self.idx(time),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a new snippet whose fade-in and fade-out are at least `fade_in` and `fade_out`
+    /// samples long (but no longer than the snippet).
+    pub fn with_min_fades(&self, fade_in: usize, fade_out: usize) -> TalkSnippet {
+        TalkSnippet {
+            fade_in: self.fade_in.max(fade_in).min(self.buf.len()),
+            fade_out: self.fade_out.max(fade_out).min(self.buf.len()),
+            ..self.clone()
+        }
+    }
+
+    /// The amount by which to multiply the sample at index `idx` when mixing.
+    fn gain(&self, idx: usize) -> f32 {
+        let mut gain = self.multiplier * self.envelope.gain(idx);
+        if idx < self.fade_in {
+            gain *= idx as f32 / self.fade_in as f32;
+        }
+        let remaining = self.buf.len() - idx;
+        if remaining < self.fade_out {
+            gain *= remaining as f32 / self.fade_out as f32;
         }
+        gain
     }
 
     fn idx(&self, time: Time) -> usize {
@@ -90,18 +274,32 @@ impl TalkSnippet {
     }
 
     /// Returns a new snippet, with all audio between `from` and `to` silenced.
+    ///
+    /// The audio fades out at the beginning of the silenced interval and back in at the end, so
+    /// that there are no clicks.
     pub fn silenced(&self, from: Time, to: Time) -> TalkSnippet {
         let from_idx = self.idx(from);
         let to_idx = self.idx(to);
         let (from_idx, to_idx) = (from_idx.min(to_idx), from_idx.max(to_idx));
 
         if from_idx < to_idx {
-            let mut buf = self.buf.deref().to_owned();
-            for i in from_idx..to_idx {
-                buf[i] = 0;
-            }
+            let silence = |buf: &[i16]| {
+                let mut buf = buf.to_owned();
+                let fade_len = AUTO_FADE_LEN.min((to_idx - from_idx) / 2);
+                for i in from_idx..to_idx {
+                    let dist_to_edge = (i - from_idx).min(to_idx - 1 - i);
+                    buf[i] = if dist_to_edge < fade_len {
+                        let gain = 1.0 - (dist_to_edge + 1) as f32 / (fade_len + 1) as f32;
+                        (buf[i] as f32 * gain).round() as i16
+                    } else {
+                        0
+                    };
+                }
+                buf
+            };
             TalkSnippet {
-                buf: buf.into(),
+                buf: silence(&self.buf).into(),
+                raw: self.map_raw(silence),
                 ..self.clone()
             }
         } else {
@@ -110,16 +308,31 @@ impl TalkSnippet {
     }
 
     /// Returns a new (shorter) snippet, with all audio between `from` and `to` deleted.
+    ///
+    /// To avoid a click at the cut, the audio that was deleted is crossfaded (over a short
+    /// interval) with the audio that comes after it.
     pub fn snipped(&self, from: Time, to: Time) -> TalkSnippet {
         let from_idx = self.idx(from);
         let to_idx = self.idx(to);
         let (from_idx, to_idx) = (from_idx.min(to_idx), from_idx.max(to_idx));
 
         if from_idx < to_idx {
-            let mut buf = self.buf.deref().to_owned();
-            buf.drain(from_idx..to_idx);
+            let snip = |buf: &[i16]| {
+                let mut buf = buf.to_owned();
+                let fade_len = AUTO_FADE_LEN.min(to_idx - from_idx).min(buf.len() - to_idx);
+                for i in 0..fade_len {
+                    let t = (i + 1) as f32 / (fade_len + 1) as f32;
+                    let old = buf[from_idx + i] as f32;
+                    let new = buf[to_idx + i] as f32;
+                    buf[to_idx + i] = (old * (1.0 - t) + new * t).round() as i16;
+                }
+                buf.drain(from_idx..to_idx);
+                buf
+            };
             TalkSnippet {
-                buf: buf.into(),
+                buf: snip(&self.buf).into(),
+                raw: self.map_raw(snip),
+                envelope: self.envelope.with_deleted(from_idx, to_idx),
                 ..self.clone()
             }
         } else {
@@ -127,6 +340,55 @@ impl TalkSnippet {
         }
     }
 
+    /// Splits this snippet in two at `time`, returning the part before and the part after. If
+    /// `time` isn't strictly inside this snippet, returns `None`.
+    fn split(&self, time: Time) -> Option<(TalkSnippet, TalkSnippet)> {
+        let idx = self.idx(time);
+        if idx == 0 || idx >= self.buf.len() {
+            return None;
+        }
+        let before = TalkSnippet {
+            buf: self.buf[..idx].to_owned().into(),
+            raw: self.map_raw(|raw| raw[..idx].to_owned()),
+            envelope: self.envelope.with_deleted(idx, self.buf.len()),
+            fade_in: self.fade_in.min(idx),
+            fade_out: 0,
+            ..self.clone()
+        };
+        let after = TalkSnippet {
+            buf: self.buf[idx..].to_owned().into(),
+            raw: self.map_raw(|raw| raw[idx..].to_owned()),
+            start_time: self.start_time + TimeDiff::from_audio_idx(idx as i64, SAMPLE_RATE),
+            envelope: self.envelope.with_deleted(0, idx),
+            fade_in: 0,
+            fade_out: self.fade_out.min(self.buf.len() - idx),
+            ..self.clone()
+        };
+        Some((before, after))
+    }
+
+    /// Returns a new snippet in which the time interval `[start, end)` has been deleted, and
+    /// everything after it moved `end - start` earlier.
+    ///
+    /// If all of the audio was in the deleted interval, returns `None`.
+    pub fn with_deleted_range(&self, start: Time, end: Time) -> Option<TalkSnippet> {
+        if self.end_time() <= start {
+            Some(self.clone())
+        } else if self.start_time() >= end {
+            Some(self.shifted(start - end))
+        } else {
+            let snipped = self.snipped(start, end);
+            if snipped.buf.is_empty() {
+                None
+            } else {
+                Some(TalkSnippet {
+                    start_time: self.start_time.min(start),
+                    ..snipped
+                })
+            }
+        }
+    }
+
     /// Returns a new snippet, with silence at the beginning and end deleted.
     ///
     /// If this snippet has only silence, returns `None`.
@@ -136,12 +398,20 @@ impl TalkSnippet {
         let last_nonzero = buf.iter().rposition(|&x| x != 0);
 
         if let (Some(first), Some(last)) = (first_nonzero, last_nonzero) {
+            let len = buf.len();
             let buf = &buf[first..=last];
             let start_time =
                 self.start_time() + TimeDiff::from_audio_idx(first as i64, SAMPLE_RATE);
             Some(TalkSnippet {
                 buf: buf.to_owned().into(),
+                raw: self.map_raw(|raw| raw[first..=last].to_owned()),
                 start_time,
+                envelope: self
+                    .envelope
+                    .with_deleted(last + 1, len)
+                    .with_deleted(0, first),
+                fade_in: self.fade_in.saturating_sub(first).min(buf.len()),
+                fade_out: self.fade_out.saturating_sub(len - 1 - last).min(buf.len()),
                 ..self.clone()
             })
         } else {
@@ -151,6 +421,14 @@ impl TalkSnippet {
 }
 
 impl TalkSnippets {
+    fn from_map(snippets: OrdMap<TalkSnippetId, TalkSnippet>) -> TalkSnippets {
+        let max_id = snippets.keys().max().unwrap_or(&TalkSnippetId(0)).0;
+        TalkSnippets {
+            snippets,
+            last_id: max_id,
+        }
+    }
+
     pub fn with_new_snippet(&self, snip: TalkSnippet) -> (TalkSnippets, TalkSnippetId) {
         let mut ret = self.clone();
         ret.last_id += 1;
@@ -159,6 +437,15 @@ impl TalkSnippets {
         (ret, id)
     }
 
+    /// Returns a new collection with the snippet `snip` at `id`, replacing any snippet that was
+    /// already there.
+    pub fn with_snippet(&self, id: TalkSnippetId, snip: TalkSnippet) -> TalkSnippets {
+        let mut ret = self.clone();
+        ret.last_id = ret.last_id.max(id.0);
+        ret.snippets.insert(id, snip);
+        ret
+    }
+
     pub fn has_snippet(&self, snip: TalkSnippetId) -> bool {
         self.snippets.contains_key(&snip)
     }
@@ -182,6 +469,26 @@ impl TalkSnippets {
         self.with_modified_snippet(id, |s| s.multiplied(factor as f32))
     }
 
+    /// Returns a new collection in which the volume of the snippet `id` has been multiplied by
+    /// `factor`, but only between `start` and `end`.
+    pub fn with_multiplied_snippet_range(
+        &self,
+        id: TalkSnippetId,
+        start: Time,
+        end: Time,
+        factor: f64,
+    ) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.multiplied_range(start, end, factor as f32))
+    }
+
+    pub fn with_faded_in_snippet(&self, id: TalkSnippetId, until: Time) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.with_fade_in_until(until))
+    }
+
+    pub fn with_faded_out_snippet(&self, id: TalkSnippetId, from: Time) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.with_fade_out_from(from))
+    }
+
     pub fn with_silenced_snippet(&self, id: TalkSnippetId, start: Time, end: Time) -> TalkSnippets {
         self.with_modified_snippet(id, |s| s.silenced(start, end))
     }
@@ -195,6 +502,79 @@ impl TalkSnippets {
         }
     }
 
+    /// Returns a new collection in which the time interval `[start, end)` has been deleted from
+    /// every snippet (see [`TalkSnippet::with_deleted_range`]).
+    pub fn with_deleted_range(&self, start: Time, end: Time) -> TalkSnippets {
+        let mut ret = self.clone();
+        for (id, snip) in self.snippets() {
+            match snip.with_deleted_range(start, end) {
+                Some(snip) => ret.snippets.insert(id, snip),
+                None => ret.snippets.remove(&id),
+            };
+        }
+        ret
+    }
+
+    /// Like [`TalkSnippets::with_deleted_range`], except that snippets lying entirely inside the
+    /// deleted interval get moved to `start` instead of disappearing.
+    pub fn with_squeezed_range(&self, start: Time, end: Time) -> TalkSnippets {
+        let mut ret = self.with_deleted_range(start, end);
+        for (id, snip) in self.snippets() {
+            if !ret.has_snippet(id) {
+                ret.snippets
+                    .insert(id, snip.shifted(start - snip.start_time()));
+            }
+        }
+        ret
+    }
+
+    /// Returns a new collection in which a gap of length `len` has been inserted at time `at`.
+    /// Snippets after `at` move `len` later, and snippets that were playing at `at` get split in
+    /// two.
+    pub fn with_inserted_gap(&self, at: Time, len: TimeDiff) -> TalkSnippets {
+        let mut ret = self.clone();
+        for (id, snip) in self.snippets() {
+            if snip.start_time() >= at {
+                ret.snippets.insert(id, snip.shifted(len));
+            } else if let Some((before, after)) = snip.split(at) {
+                // The two parts are no longer adjacent, so fade them out (and in) to avoid clicks.
+                ret.snippets
+                    .insert(id, before.with_min_fades(0, AUTO_FADE_LEN));
+                let after = after.with_min_fades(AUTO_FADE_LEN, 0).shifted(len);
+                ret = ret.with_new_snippet(after).0;
+            }
+        }
+        ret
+    }
+
+    /// Returns a new collection with all the audio after `end` deleted.
+    pub fn truncated(&self, end: Time) -> TalkSnippets {
+        let mut ret = self.clone();
+        for (id, snip) in self.snippets() {
+            if snip.start_time() >= end {
+                ret.snippets.remove(&id);
+            } else if snip.end_time() > end {
+                ret.snippets.insert(id, snip.snipped(end, snip.end_time()));
+            }
+        }
+        ret
+    }
+
+    /// Splits the snippet `id` in two at `time`. The first part keeps the id `id`, and we return
+    /// the id of the second part. Both parts have the same volume multiplier as the original.
+    ///
+    /// Returns `None` if `time` isn't strictly inside the snippet.
+    pub fn with_split_snippet(
+        &self,
+        id: TalkSnippetId,
+        time: Time,
+    ) -> Option<(TalkSnippets, TalkSnippetId)> {
+        let (before, after) = self.snippet(id).split(time)?;
+        let mut ret = self.clone();
+        ret.snippets.insert(id, before);
+        Some(ret.with_new_snippet(after))
+    }
+
     pub fn without_snippet(&self, id: TalkSnippetId) -> TalkSnippets {
         let mut ret = self.clone();
         ret.snippets.remove(&id);
@@ -217,16 +597,26 @@ impl TalkSnippets {
             .unwrap_or(Time::ZERO)
     }
 
-    /// Fills the provided buffer with samples from the cursor, and advance the cursor.
+    /// Mixes the samples in the cursor's current range into the provided buffer.
+    ///
+    /// The mixing happens in floating point, and then the result gets passed through a soft
+    /// limiter, so that loud overlapping snippets don't overflow.
     pub fn mix_to<B: DerefMut<Target = [i16]>>(
         &self,
         cursor: &Cursor<usize, TalkSnippetId>,
         mut buf: B,
     ) {
+        let buf: &mut [i16] = &mut buf;
+        let mut acc: Vec<f32> = buf.iter().map(|&x| x as f32).collect();
+        self.mix_to_f32(cursor, &mut acc);
+        write_limited(&acc, buf);
+    }
+
+    /// Mixes the samples in the cursor's current range into the provided buffer, without limiting
+    /// them.
+    pub fn mix_to_f32(&self, cursor: &Cursor<usize, TalkSnippetId>, acc: &mut [f32]) {
         for sp in cursor.active_spans() {
-            let buf: &mut [i16] = &mut buf;
             let snip = self.snippet(sp.id);
-            let multiplier = snip.multiplier;
 
             let (curs_start, curs_end) = cursor.current();
             let snip_start = curs_start.saturating_sub(sp.start);
@@ -234,7 +624,8 @@ impl TalkSnippets {
             let buf_offset = sp.start.saturating_sub(curs_start);
 
             for (idx, sample) in snip.buf[snip_start..snip_end].iter().enumerate() {
-                buf[buf_offset + idx] += (*sample as f32 * multiplier) as i16;
+                let gain = snip.gain(snip_start + idx);
+                acc[buf_offset + idx] += *sample as f32 * gain;
             }
         }
     }
@@ -267,25 +658,98 @@ impl Serialize for TalkSnippets {
 impl<'de> Deserialize<'de> for TalkSnippets {
     fn deserialize<D: Deserializer<'de>>(de: D) -> Result<TalkSnippets, D::Error> {
         let snips: OrdMap<TalkSnippetId, TalkSnippet> = Deserialize::deserialize(de)?;
-        let max_id = snips.keys().max().unwrap_or(&TalkSnippetId(0)).0;
-        Ok(TalkSnippets {
-            snippets: snips,
-            last_id: max_id,
-        })
+        Ok(TalkSnippets::from_map(snips))
+    }
+}
+
+// Since version 3 of the save file, the audio buffers are compressed (losslessly). We store the
+// compressed data as a CBOR byte string.
+mod compressed_buf {
+    use super::*;
+    use crate::audio::compress::{compress, decompress};
+
+    pub fn serialize<S: Serializer>(buf: &Arc<[i16]>, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_bytes(&compress(buf))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Arc<[i16]>, D::Error> {
+        de.deserialize_bytes(CompressedVisitor)
+    }
+
+    struct CompressedVisitor;
+
+    impl<'de> Visitor<'de> for CompressedVisitor {
+        type Value = Arc<[i16]>;
+
+        fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+            fmt.write_str("compressed audio data")
+        }
+
+        fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Arc<[i16]>, E> {
+            decompress(v).map(Into::into).map_err(E::custom)
+        }
+    }
+}
+
+/// Before version 2 of the save file format, audio was stored uncompressed.
+pub mod v1 {
+    use super::*;
+
+    #[derive(Clone, Deserialize)]
+    pub struct TalkSnippet {
+        buf: Arc<[i16]>,
+        multiplier: f32,
+        start_time: Time,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    pub struct TalkSnippets {
+        snippets: OrdMap<TalkSnippetId, TalkSnippet>,
+    }
+
+    impl From<TalkSnippet> for super::TalkSnippet {
+        fn from(s: TalkSnippet) -> super::TalkSnippet {
+            super::TalkSnippet {
+                buf: s.buf,
+                multiplier: s.multiplier,
+                start_time: s.start_time,
+                fade_in: 0,
+                fade_out: 0,
+                envelope: Envelope::default(),
+                raw: None,
+            }
+        }
+    }
+
+    impl From<TalkSnippets> for super::TalkSnippets {
+        fn from(s: TalkSnippets) -> super::TalkSnippets {
+            super::TalkSnippets::from_map(
+                s.snippets
+                    .into_iter()
+                    .map(|(id, snip)| (id, snip.into()))
+                    .collect(),
+            )
+        }
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
 
+    /// The number of samples in `n` milliseconds.
+    pub const fn ms(n: usize) -> usize {
+        n * SAMPLE_RATE as usize / 1000
+    }
+
     macro_rules! snips {
         ($($time:expr => $buf:expr),*) => {
             {
                 let mut ret = TalkSnippets::default();
                 $(
                     let buf: &[i16] = $buf;
-                    let time = Time::from_audio_idx($time, SAMPLE_RATE);
+                    let time = Time::from_audio_idx($time as i64, SAMPLE_RATE);
                     ret = ret.with_new_snippet(TalkSnippet::new(buf.to_owned(), time, 1.0)).0;
                 )*
 
@@ -365,4 +829,211 @@ mod tests {
         snips.mix_to(&c, &mut out[..]);
         assert_eq!(out, vec![0, 0, 1, 2, 3, 0, 0, 0, 0, 0]);
     }
+
+    #[test]
+    fn overflow() {
+        let snips = snips!(
+            0 => &[30000, -30000, 20000, 1000],
+            0 => &[30000, -30000, 10000, 1000]
+        );
+        let c = Cursor::new(snips.snippet_spans(), 0, 4);
+        let mut out = vec![0; 4];
+        snips.mix_to(&c, &mut out[..]);
+        assert!(out[0] > LIMITER_THRESHOLD as i16);
+        assert!(out[0] < i16::MAX);
+        // The limiter is symmetric, and it squashes the louder samples more.
+        assert_eq!(out[1], -out[0]);
+        assert!(out[2] > LIMITER_THRESHOLD as i16);
+        assert!(out[2] < out[0]);
+        // Quiet samples are unaffected.
+        assert_eq!(out[3], 2000);
+    }
+
+    #[test]
+    fn overflow_multiplier() {
+        let snips = snips!(0 => &[i16::MAX, i16::MIN, 1000]);
+        let id = snips.snippets().next().unwrap().0;
+        let snips = snips.with_multiplied_snippet(id, 100.0);
+        let c = Cursor::new(snips.snippet_spans(), 0, 3);
+        let mut out = vec![0; 3];
+        snips.mix_to(&c, &mut out[..]);
+        assert_eq!(out[0], i16::MAX);
+        assert_eq!(out[1], -i16::MAX);
+        assert!(out[2] > LIMITER_THRESHOLD as i16);
+    }
+
+    #[test]
+    fn soft_limit_is_monotonic() {
+        let mut prev = i16::MIN;
+        for x in (-200_000..200_000).step_by(7) {
+            let y = soft_limit(x as f32);
+            assert!(y >= prev);
+            prev = y;
+        }
+        assert_eq!(soft_limit(1234.0), 1234);
+        assert_eq!(soft_limit(-1234.0), -1234);
+    }
+
+    #[test]
+    fn fades() {
+        let snip = TalkSnippet::new(vec![100; 8], Time::ZERO, 1.0).with_min_fades(4, 2);
+        let (snips, _) = TalkSnippets::default().with_new_snippet(snip);
+        let c = Cursor::new(snips.snippet_spans(), 0, 8);
+        let mut out = vec![0; 8];
+        snips.mix_to(&c, &mut out[..]);
+        assert_eq!(out, vec![0, 25, 50, 75, 100, 100, 100, 50]);
+    }
+
+    #[test]
+    fn snip_crossfade() {
+        let mut buf = vec![1000; 300];
+        buf.extend_from_slice(&[-1000; 700]);
+        let snip = TalkSnippet::new(buf, Time::ZERO, 1.0);
+        let snipped = snip.snipped(Time::from_micros(2000), Time::from_micros(12000));
+        let buf = snipped.buf();
+        assert_eq!(buf.len(), 1000 - ms(10));
+        assert_eq!(buf[ms(2) - 1], 1000);
+        // Right after the cut, we should still hear mostly the audio that was deleted...
+        assert!(buf[ms(2)] > 900);
+        // ...and it should gradually fade into the audio that came after it.
+        assert!(buf[ms(2) + AUTO_FADE_LEN - 1] < -900);
+        assert_eq!(buf[ms(2) + AUTO_FADE_LEN], -1000);
+    }
+
+    #[test]
+    fn silence_ramps() {
+        let snip = TalkSnippet::new(vec![1000; 1000], Time::ZERO, 1.0);
+        let silenced = snip.silenced(Time::from_micros(2000), Time::from_micros(17000));
+        let buf = silenced.buf();
+        assert_eq!(buf[ms(2) - 1], 1000);
+        assert!(buf[ms(2)] > 900 && buf[ms(2)] < 1000);
+        assert!(buf[ms(2) + AUTO_FADE_LEN / 2] > 0);
+        assert_eq!(buf[ms(9)], 0);
+        assert!(buf[ms(17) - 1] > 900 && buf[ms(17) - 1] < 1000);
+        assert_eq!(buf[ms(17)], 1000);
+    }
+
+    #[test]
+    fn raw_follows_edits() {
+        let raw: Vec<i16> = (0..ms(20) as i16).collect();
+        let mut buf = vec![0; ms(2)];
+        buf.extend_from_slice(&raw[ms(2)..]);
+        let snip = TalkSnippet::new(buf, Time::ZERO, 1.0).with_raw(raw);
+
+        let config = InputConfig {
+            remove_noise: false,
+            ..InputConfig::default()
+        };
+        let snip = snip.trimmed().unwrap();
+        assert_eq!(snip.buf().len(), ms(18));
+        let snip = snip.snipped(Time::from_micros(3000), Time::from_micros(5000));
+        let reprocessed = snip.reprocessed(&config, |_| {}).unwrap();
+        assert!(reprocessed.buf() == snip.buf());
+
+        // Snippets without raw audio can't be reprocessed.
+        assert!(TalkSnippet::new(vec![1; 10], Time::ZERO, 1.0)
+            .reprocessed(&config, |_| {})
+            .is_none());
+    }
+
+    #[test]
+    fn limit_scales_raw() {
+        let raw: Vec<i16> = (0..960).collect();
+        let snip = TalkSnippet::new(raw.clone(), Time::ZERO, 2.0).with_raw(raw);
+        let config = InputConfig {
+            remove_noise: false,
+            ..InputConfig::default()
+        };
+        let limited = snip.limited();
+        assert_eq!(limited.buf()[100], 200);
+        let reprocessed = limited.reprocessed(&config, |_| {}).unwrap();
+        assert!(reprocessed.buf() == limited.buf());
+    }
+
+    #[test]
+    fn truncated() {
+        let snips = snips!(
+            0 => &[1; ms(2)],
+            ms(2) => &[1; 10]
+        );
+        let snips = snips.truncated(Time::from_micros(1000));
+        let remaining: Vec<_> = snips.snippets().map(|(_, s)| s.buf().len()).collect();
+        assert_eq!(remaining, vec![ms(1)]);
+    }
+
+    #[test]
+    fn deleted_range() {
+        let snips = snips!(
+            0 => &[1; ms(2)],
+            ms(1) => &[2; ms(1) / 2],
+            ms(4) => &[3; ms(1)]
+        );
+        let snips = snips.with_deleted_range(Time::from_micros(1000), Time::from_micros(2000));
+        let remaining: Vec<_> = snips
+            .snippets()
+            .map(|(_, s)| (s.start_time().as_micros(), s.buf().len()))
+            .collect();
+        assert_eq!(remaining, vec![(0, ms(1)), (3000, ms(1))]);
+    }
+
+    #[test]
+    fn squeezed_range() {
+        let snips = snips!(
+            0 => &[1; ms(2)],
+            ms(1) => &[2; ms(1) / 2],
+            ms(4) => &[3; ms(1)]
+        );
+        let snips = snips.with_squeezed_range(Time::from_micros(1000), Time::from_micros(2000));
+        let remaining: Vec<_> = snips
+            .snippets()
+            .map(|(_, s)| (s.start_time().as_micros(), s.buf().len()))
+            .collect();
+        assert_eq!(
+            remaining,
+            vec![(0, ms(1)), (1000, ms(1) / 2), (3000, ms(1))]
+        );
+    }
+
+    #[test]
+    fn inserted_gap() {
+        let snips = snips!(
+            0 => &[1; ms(2)],
+            ms(2) => &[2; ms(1)]
+        );
+        let snips = snips.with_inserted_gap(Time::from_micros(1000), TimeDiff::from_micros(1000));
+        let mut remaining: Vec<_> = snips
+            .snippets()
+            .map(|(_, s)| (s.start_time().as_micros(), s.buf().len(), s.buf()[0]))
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![(0, ms(1), 1), (2000, ms(1), 1), (3000, ms(1), 2)]
+        );
+    }
+
+    #[test]
+    fn split() {
+        let snips = snips!(ms(1) => &[1; ms(2)]);
+        let id = snips.snippets().next().unwrap().0;
+        let snips = snips.with_multiplied_snippet(id, 2.0);
+        assert!(snips
+            .with_split_snippet(id, Time::from_micros(1000))
+            .is_none());
+        assert!(snips
+            .with_split_snippet(id, Time::from_micros(3000))
+            .is_none());
+
+        let (snips, new_id) = snips
+            .with_split_snippet(id, Time::from_micros(1500))
+            .unwrap();
+        let first = snips.snippet(id);
+        let second = snips.snippet(new_id);
+        assert_eq!(first.start_time(), Time::from_micros(1000));
+        assert_eq!(first.buf().len(), ms(1) / 2);
+        assert_eq!(second.start_time(), Time::from_micros(1500));
+        assert_eq!(second.buf().len(), ms(3) / 2);
+        assert_eq!(first.multiplier(), 2.0);
+        assert_eq!(second.multiplier(), 2.0);
+    }
 }
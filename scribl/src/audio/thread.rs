@@ -14,17 +14,15 @@ use scribl_curves::Time;
 
 use crate::cmd;
 
+use super::denoise;
+use super::devices;
+use super::process;
+use super::snippets::AUTO_FADE_LEN;
 use super::{
     create_appsrc, AudioRecording, AudioRecordingStatus, InputConfig, OutputData, TalkSnippet,
     SAMPLE_RATE,
 };
 
-// We don't simply drop frames where voice was not detected: doing so tends to cut off consonants
-// like "t". Instead, we do some "smoothing in time": if no voice was detected within
-// `VOICELESS_FRAME_LAG` frames (either forwards or backwards in time) of the current frame, we
-// drop the current frame.
-const VOICELESS_FRAME_LAG: usize = 10;
-
 /// This contains the audio pipelines and the various channels that are used to communicate with
 /// the gstreamer callbacks. Essentially, this is the main state in the audio loop.
 struct AudioState {
@@ -37,12 +35,16 @@ struct AudioState {
     // The current output data (i.e., a copy of the last thing we sent along output_tx).
     output_data: OutputData,
 
+    // Like the output pipeline, this will be `None` if there was an error while creating it.
+    input_pipeline: Option<gst::Pipeline>,
     // The receiver of this lives in the app_sink callback. We send input configs to it when we
     // want to change the input settings. We send `None` when we want to stop storing the input
     // audio.
     input_tx: Sender<Option<InputConfig>>,
-    // The sender of this lives in the app_sink callback. It regularly sends us messages about
-    // things like input levels.
+    // A clone of this lives in the app_sink callback. It regularly sends us messages about
+    // things like input levels. We keep one here, so that the channel stays open even when the
+    // input pipeline is being replaced.
+    input_status_tx: Sender<AudioRecordingStatus>,
     input_status_rx: Receiver<AudioRecordingStatus>,
     // The current input settings (i.e. a copy of the ones that we most recently sent on input_tx).
     input_config: InputConfig,
@@ -56,6 +58,9 @@ struct AudioState {
 
 struct InputData {
     buf: Vec<i16>,
+    /// The audio as it was recorded, before noise removal. This is only kept if the input config
+    /// asks for it.
+    raw: Vec<i16>,
     /// For every frame (of size `DenoiseState::FRAME_SIZE`) in `buf`, we store an estimate of how
     /// likely that frame was to contain speech.
     vad: Vec<f32>,
@@ -69,24 +74,63 @@ pub enum Cmd {
     Record(InputConfig),
     StopRecording(Time),
     Seek(Time, f64),
+    /// Switches to the audio input device with the given name (or the default one, if `None`).
+    SetInputDevice(Option<String>),
+    /// Switches to the audio output device with the given name (or the default one, if `None`).
+    SetOutputDevice(Option<String>),
 }
 
 impl AudioState {
-    /// Initializes the audio input and output pipelines.
+    /// Initializes the audio state. This doesn't create any pipelines: that happens in
+    /// `set_input_device` and `set_output_device`.
     fn init() -> AudioState {
-        let (output_tx, output_rx) = unbounded();
-        let output_pipeline = create_output_pipeline(output_rx);
-        if let Err(e) = &output_pipeline {
-            log::error!(
-                "Error initializing audio output, there will be no sound: {}",
-                e
-            );
+        // These channels don't go anywhere yet; they'll be replaced when the pipelines are
+        // created.
+        let (output_tx, _) = unbounded();
+        let (input_tx, _) = unbounded();
+        let (input_status_tx, input_status_rx) = unbounded();
+
+        AudioState {
+            output_data: OutputData::new(),
+            output_tx,
+            output_pipeline: None,
+            input_pipeline: None,
+            input_tx,
+            input_status_tx,
+            input_status_rx,
+            input_config: InputConfig::default(),
+            input_data: Arc::new(Mutex::new(InputData::new())),
+        }
+    }
+
+    /// (Re)creates the input pipeline, recording from the device named `name` (or the default
+    /// device, if `name` is `None`).
+    ///
+    /// If the requested device couldn't be found, we use the default one instead, and return a
+    /// message saying so.
+    fn set_input_device(&mut self, name: Option<&str>) -> Option<String> {
+        if let Some(pipe) = self.input_pipeline.take() {
+            if let Err(e) = pipe.set_state(gst::State::Null) {
+                log::error!("failed to stop the old audio input: {}", e);
+            }
         }
 
+        let device = name.and_then(devices::find_input);
+        let warning = name.filter(|_| device.is_none()).map(|name| {
+            format!(
+                "couldn't find audio input \"{}\", using the default one",
+                name
+            )
+        });
+
         let (input_tx, input_rx) = unbounded();
-        let (status_tx, status_rx) = unbounded();
-        let input_data = Arc::new(Mutex::new(InputData::new()));
-        let input_pipeline = create_input_pipeline(Arc::clone(&input_data), input_rx, status_tx);
+        self.input_tx = input_tx;
+        let input_pipeline = create_input_pipeline(
+            Arc::clone(&self.input_data),
+            input_rx,
+            self.input_status_tx.clone(),
+            device.as_ref(),
+        );
         // We keep the input pipeline running, even if we aren't recording audio. This is because
         // starting and starting the input pipeline tends to lead to "pops" in the recording.
         match input_pipeline {
@@ -100,18 +144,44 @@ impl AudioState {
                 if let Err(e) = pipe.set_state(gst::State::Playing) {
                     log::error!("failed to start recording audio: {}", e);
                 }
+                self.input_pipeline = Some(pipe);
             }
         };
+        warning
+    }
 
-        AudioState {
-            output_data: OutputData::new(),
-            output_tx,
-            output_pipeline: output_pipeline.ok(),
-            input_tx,
-            input_status_rx: status_rx,
-            input_config: InputConfig::default(),
-            input_data,
+    /// (Re)creates the output pipeline, playing to the device named `name` (or the default
+    /// device, if `name` is `None`).
+    ///
+    /// If the requested device couldn't be found, we use the default one instead, and return a
+    /// message saying so.
+    fn set_output_device(&mut self, name: Option<&str>) -> Option<String> {
+        if let Some(pipe) = self.output_pipeline.take() {
+            if let Err(e) = pipe.set_state(gst::State::Null) {
+                log::error!("failed to stop the old audio output: {}", e);
+            }
         }
+
+        let device = name.and_then(devices::find_output);
+        let warning = name.filter(|_| device.is_none()).map(|name| {
+            format!(
+                "couldn't find audio output \"{}\", using the default one",
+                name
+            )
+        });
+
+        let (output_tx, output_rx) = unbounded();
+        self.output_tx = output_tx;
+        match create_output_pipeline(output_rx, device.as_ref()) {
+            Err(e) => {
+                log::error!(
+                    "Error initializing audio output, there will be no sound: {}",
+                    e
+                );
+            }
+            Ok(pipe) => self.output_pipeline = Some(pipe),
+        }
+        warning
     }
 
     fn seek(&mut self, time: Time, velocity: f64) {
@@ -150,6 +220,7 @@ impl AudioState {
         {
             let mut lock = self.input_data.lock().unwrap();
             lock.buf.clear();
+            lock.raw.clear();
             lock.vad.clear();
         }
         if self.input_tx.send(Some(config)).is_err() {
@@ -166,41 +237,9 @@ impl AudioState {
             log::error!("audio input thread died, no audio will be recorded");
         }
 
-        // Which frames are worth keeping, according to voice detection?
-        let vad_threshold = self.input_config.vad_threshold;
-        let mut keep: Vec<_> = data.vad.iter().map(|&v| v > vad_threshold).collect();
-        keep.push(false);
-        let mut weights = vec![0.0f32; keep.len()];
-        convolve_bools(&keep[..], &mut weights[..], VOICELESS_FRAME_LAG);
-
-        // Windows for fading in and out when voice is detected or not.
-        let constant = vec![1.0; DenoiseState::FRAME_SIZE];
-        let fade_out: Vec<_> = (0..DenoiseState::FRAME_SIZE)
-            .rev()
-            .map(|x| x as f32 / DenoiseState::FRAME_SIZE as f32)
-            .collect();
-        let fade_in: Vec<_> = (0..DenoiseState::FRAME_SIZE)
-            .map(|x| x as f32 / DenoiseState::FRAME_SIZE as f32)
-            .collect();
-
-        for (frame, w) in data
-            .buf
-            .chunks_exact_mut(DenoiseState::FRAME_SIZE)
-            .zip(weights.windows(2))
-        {
-            let window = if w[0] < w[1] {
-                &fade_in
-            } else if w[0] > w[1] {
-                &fade_out
-            } else {
-                &constant
-            };
-            let lo = w[0].min(w[1]);
-            let hi = w[0].max(w[1]);
-            for (x, &y) in frame.iter_mut().zip(window) {
-                let weight = lo + (lo - hi) * y;
-                *x = (*x as f32 * weight).round() as i16;
-            }
+        denoise::gate(&mut data.buf, &data.vad, self.input_config.vad_threshold);
+        if self.input_config.compress {
+            data.buf = process::compress(&data.buf);
         }
 
         // Now that we've changed the data, recalculate the loudness.
@@ -212,6 +251,7 @@ impl AudioState {
         let peak = data.loudness.sample_peak(0).unwrap_or(-f64::INFINITY);
         AudioRecording {
             buf: data.buf,
+            raw: Some(data.raw).filter(|_| self.input_config.keep_raw),
             loudness,
             peak,
         }
@@ -245,6 +285,7 @@ impl InputData {
     fn new() -> InputData {
         InputData {
             buf: Vec::new(),
+            raw: Vec::new(),
             vad: Vec::new(),
             // TODO: what are the failure cases for Ebur128::new?
             loudness: EbuR128::new(
@@ -256,13 +297,14 @@ impl InputData {
         }
     }
 
-    fn append_buffer(&mut self, buf: &[i16], vad: &[f32]) -> AudioRecordingStatus {
+    fn append_buffer(&mut self, buf: &[i16], raw: &[i16], vad: &[f32]) -> AudioRecordingStatus {
         // What are the error cases here?
         if let Err(e) = self.loudness.add_frames_i16(buf) {
             log::error!("failed to calculate loudness: {}", e);
         }
         self.vad.extend_from_slice(vad);
         self.buf.extend_from_slice(buf);
+        self.raw.extend_from_slice(raw);
 
         AudioRecordingStatus {
             vad: *self.vad.last().unwrap_or(&0.0),
@@ -276,44 +318,25 @@ impl InputData {
     }
 }
 
-/// Given a slice of bools, modifies it so that everything within `width` of a `true` is set to
-/// `true`.
-fn convolve_bools(xs: &[bool], out: &mut [f32], width: usize) {
-    let mut count = width;
-    let next_count = |x, count| if x { 0 } else { count + 1 };
-    let step = 1.0 / width as f32;
-
-    let mut weight = 0.0f32;
-    for (&x, y) in xs.iter().zip(&mut out[..]) {
-        count = next_count(x, count);
-        if count <= width {
-            *y = 1.0;
-            weight = 1.0;
-        } else {
-            weight = (weight - step).max(0.0);
-            *y = weight;
-        }
-    }
-
-    count = width;
-    weight = 0.0;
-    for (&x, y) in xs.iter().zip(&mut out[..]).rev() {
-        count = next_count(x, count);
-        if count <= width {
-            *y = 1.0;
-            weight = 1.0;
-        } else {
-            weight = (weight - step).max(0.0);
-            *y = y.max(weight);
-        }
-    }
-}
-
 /// The main function in this module. Spawn it in a new thread, and it will take care of audio
 /// input and output. Send commands to it through the `cmd` channel to make it play, stop, record,
 /// and so on. The audio loop will send things back through `sink`, targeted at `target`.
-pub fn audio_loop(cmd: Receiver<Cmd>, sink: ExtEventSink, target: Target) {
+pub fn audio_loop(
+    cmd: Receiver<Cmd>,
+    sink: ExtEventSink,
+    target: Target,
+    input_device: Option<String>,
+    output_device: Option<String>,
+) {
     let mut state = AudioState::init();
+    let report_missing_device = |warning: Option<String>| {
+        if let Some(warning) = warning {
+            log::warn!("{}", warning);
+            let _ = sink.submit_command(cmd::AUDIO_DEVICE_MISSING, warning, target);
+        }
+    };
+    report_missing_device(state.set_input_device(input_device.as_deref()));
+    report_missing_device(state.set_output_device(output_device.as_deref()));
 
     loop {
         select! {
@@ -326,26 +349,29 @@ pub fn audio_loop(cmd: Receiver<Cmd>, sink: ExtEventSink, target: Target) {
                     Ok(Record(config)) => {
                         state.start_recording(config);
                     }
+                    Ok(SetInputDevice(name)) => {
+                        report_missing_device(state.set_input_device(name.as_deref()));
+                    }
+                    Ok(SetOutputDevice(name)) => {
+                        report_missing_device(state.set_output_device(name.as_deref()));
+                    }
                     Ok(StopRecording(time)) => {
                         let rec = state.stop_recording();
 
-                        // By default, we normalize to loudness -20. This is quieter than many
-                        // sources ask for (e.g. youtube recommends -13 to -15), but going louder
-                        // tends to introduce clipping.  Maybe some sort of dynamic range
-                        // compression would be appropriate?
-                        let target_loudness = -20.0;
-
-                        // Multiplying a signal by x has the effect of adding 20 * log_10(x) to the
-                        // loudness.
-                        let multiplier = 10.0f64
-                            .powf((target_loudness - rec.loudness) / 20.0)
-                            // Truncate the multiplier so that we don't clip. (Also make sure the
-                            // peak isn't really small, because often the sample is all-zero or
-                            // close to it.)
-                            .min(1.0 / rec.peak.max(1.0 / 500.0));
-
-                        let snip = TalkSnippet::new(rec.buf, time, multiplier as f32);
+                        let multiplier = process::normalizing_multiplier(
+                            rec.loudness,
+                            rec.peak,
+                            &state.input_config,
+                        );
+
+                        let mut snip = TalkSnippet::new(rec.buf, time, multiplier as f32);
+                        if let Some(raw) = rec.raw {
+                            snip = snip.with_raw(raw);
+                        }
                         if let Some(trimmed) = snip.trimmed() {
+                            // Fade in and out a little, in case the recording starts or ends
+                            // in the middle of some noise.
+                            let trimmed = trimmed.with_min_fades(AUTO_FADE_LEN, AUTO_FADE_LEN);
                             let cmd = cmd::TalkSnippetCmd { snip: trimmed, orig_start: snip.start_time() };
                             let _ = sink.submit_command(cmd::ADD_TALK_SNIPPET, cmd, target);
                         }
@@ -368,9 +394,13 @@ fn create_input_pipeline(
     data: Arc<Mutex<InputData>>,
     config_rx: Receiver<Option<InputConfig>>,
     status_tx: Sender<AudioRecordingStatus>,
+    device: Option<&gst::Device>,
 ) -> Result<gst::Pipeline> {
     let pipeline = gst::Pipeline::new(None);
-    let src = gst::ElementFactory::make("autoaudiosrc", Some("record-source"))?;
+    let src = match device {
+        Some(d) => d.create_element(Some("record-source"))?,
+        None => gst::ElementFactory::make("autoaudiosrc", Some("record-source"))?,
+    };
     let resample = gst::ElementFactory::make("audioresample", Some("record-resample"))?;
     let convert = gst::ElementFactory::make("audioconvert", Some("record-convert"))?;
     let queue = gst::ElementFactory::make("queue", Some("record-queue"))?;
@@ -388,6 +418,7 @@ fn create_input_pipeline(
     let mut denoise_in_buf = Vec::with_capacity(DenoiseState::FRAME_SIZE);
     let mut denoise_out_buf = vec![0.0; DenoiseState::FRAME_SIZE];
     let mut i16_buf = Vec::with_capacity(DenoiseState::FRAME_SIZE);
+    let mut raw_buf = Vec::with_capacity(DenoiseState::FRAME_SIZE);
     let mut vad_buf = Vec::new();
     let mut config = None;
 
@@ -445,6 +476,17 @@ fn create_input_pipeline(
                 }
                 1.0
             };
+            if config.keep_raw {
+                for sample in &denoise_in_buf {
+                    raw_buf.push(*sample as i16);
+                }
+                // We always output a whole frame (even if the input didn't fill one), and the raw
+                // audio needs to stay the same length as the output.
+                raw_buf.resize(
+                    raw_buf.len() + DenoiseState::FRAME_SIZE - denoise_in_buf.len(),
+                    0,
+                );
+            }
             denoise_in_buf.clear();
 
             vad_buf.push(vad);
@@ -453,9 +495,13 @@ fn create_input_pipeline(
             }
         }
 
-        let status = data.lock().unwrap().append_buffer(&i16_buf, &vad_buf);
+        let status = data
+            .lock()
+            .unwrap()
+            .append_buffer(&i16_buf, &raw_buf, &vad_buf);
         let _ = status_tx.send(status);
         i16_buf.clear();
+        raw_buf.clear();
         vad_buf.clear();
         Ok(gst::FlowSuccess::Ok)
     };
@@ -467,14 +513,20 @@ fn create_input_pipeline(
     Ok(pipeline)
 }
 
-fn create_output_pipeline(rx: Receiver<OutputData>) -> Result<gst::Pipeline> {
+fn create_output_pipeline(
+    rx: Receiver<OutputData>,
+    device: Option<&gst::Device>,
+) -> Result<gst::Pipeline> {
     let pipeline = gst::Pipeline::new(None);
     let src = create_appsrc(rx, "playback-source")?;
     let scale = gst::ElementFactory::make("scaletempo", Some("playback-scale"))?;
     let resample = gst::ElementFactory::make("audioresample", Some("playback-resample"))?;
     let convert = gst::ElementFactory::make("audioconvert", Some("playback-convert"))?;
     let queue = gst::ElementFactory::make("queue", Some("playback-queue"))?;
-    let sink = gst::ElementFactory::make("autoaudiosink", Some("playback-sink"))?;
+    let sink = match device {
+        Some(d) => d.create_element(Some("playback-sink"))?,
+        None => gst::ElementFactory::make("autoaudiosink", Some("playback-sink"))?,
+    };
 
     pipeline.add_many(&[&src, &scale, &resample, &convert, &queue, &sink])?;
     gst::Element::link_many(&[&src, &scale, &resample, &convert, &queue, &sink])?;
@@ -2,60 +2,254 @@ use crossbeam_channel::Sender;
 use directories_next::ProjectDirs;
 use druid::{ExtEventSink, WindowId};
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, TryLockError};
 
 use crate::cmd::{AsyncSaveResult, FINISHED_ASYNC_SAVE};
-use crate::SaveFileData;
+use crate::data::journal::{self, journal_path, Journal};
+use crate::{EditorState, SaveFileData};
 
 pub struct AutosaveData {
     pub path: Option<PathBuf>,
+    /// The contents of the file at `path`, as of the last time it was loaded or saved.
+    pub base: Option<SaveFileData>,
     pub data: SaveFileData,
 }
 
-impl AutosaveData {
-    fn autosave_path(&self) -> Option<PathBuf> {
-        if let Some(proj_dirs) = ProjectDirs::from("ink", "scribl", "scribl") {
-            let autosave_name =
-                if let Some(orig_name) = self.path.as_ref().and_then(|p| p.file_stem()) {
-                    let mut name = orig_name.to_owned();
-                    name.push(".autosave.scb");
-                    name
-                } else {
-                    OsStr::new("untitled-autosave.scb").to_os_string()
-                };
-            let mut ret = proj_dirs.data_local_dir().to_owned();
-            ret.push(autosave_name);
-            Some(ret)
+/// Unsaved changes that were left behind (probably because scribl crashed).
+#[derive(Clone)]
+pub struct Recovery {
+    pub data: SaveFileData,
+    /// The file that we found the changes in.
+    pub path: PathBuf,
+}
+
+// A document with unsaved changes, which we will try to save if we panic.
+struct UnsavedDocument {
+    window: WindowId,
+    path: Option<PathBuf>,
+    data: SaveFileData,
+}
+
+static UNSAVED_DOCUMENTS: Mutex<Vec<UnsavedDocument>> = Mutex::new(Vec::new());
+static SAVING_AFTER_PANIC: AtomicBool = AtomicBool::new(false);
+
+/// Where we put the full autosave of the document at `doc_path` (or of an untitled document, if
+/// `doc_path` is `None`). Documents that have a file usually get autosaved to a journal instead,
+/// but we use this when we can't (e.g. when we're trying to save everything after a panic).
+fn autosave_path(doc_path: Option<&Path>) -> Option<PathBuf> {
+    if let Some(proj_dirs) = ProjectDirs::from("ink", "scribl", "scribl") {
+        let autosave_name = if let Some(orig_name) = doc_path.and_then(|p| p.file_stem()) {
+            let mut name = orig_name.to_owned();
+            name.push(".autosave.scb");
+            name
         } else {
-            None
+            OsStr::new("untitled-autosave.scb").to_os_string()
+        };
+        let mut ret = proj_dirs.data_local_dir().to_owned();
+        ret.push(autosave_name);
+        Some(ret)
+    } else {
+        None
+    }
+}
+
+/// Looks for unsaved changes to the document at `doc_path` (whose contents are `doc`). If
+/// `doc_path` is `None`, looks for unsaved changes to an untitled document.
+///
+/// We look for an autosave that is newer than the document, and then for a journal.
+pub fn find_recovery(doc_path: Option<&Path>, doc: Option<&SaveFileData>) -> Option<Recovery> {
+    find_recovery_at(autosave_path(doc_path), doc_path, doc)
+}
+
+// Like `find_recovery`, but `autosave` is where to look for the autosave.
+fn find_recovery_at(
+    autosave: Option<PathBuf>,
+    doc_path: Option<&Path>,
+    doc: Option<&SaveFileData>,
+) -> Option<Recovery> {
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    if let Some(path) = autosave {
+        let newer = match doc_path {
+            Some(doc_path) => match (modified(&path), modified(doc_path)) {
+                (Some(autosave_time), Some(doc_time)) => autosave_time > doc_time,
+                _ => false,
+            },
+            None => path.exists(),
+        };
+        if newer {
+            match SaveFileData::load_from_path(&path) {
+                Ok(data) => return Some(Recovery { data, path }),
+                Err(e) => log::error!("failed to read autosave {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    if let (Some(doc_path), Some(doc)) = (doc_path, doc) {
+        match journal::replay(doc_path, doc) {
+            Ok(Some(data)) => {
+                return Some(Recovery {
+                    data,
+                    path: journal_path(doc_path),
+                })
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("failed to read journal for {}: {}", doc_path.display(), e),
+        }
+    }
+    None
+}
+
+/// Deletes any autosaves and journals of the document at `doc_path` (or of an untitled document,
+/// if `doc_path` is `None`).
+pub fn discard_autosaves(doc_path: Option<&Path>) {
+    discard_autosaves_at(autosave_path(doc_path), doc_path);
+}
+
+// Like `discard_autosaves`, but `autosave` is where the autosave is.
+fn discard_autosaves_at(autosave: Option<PathBuf>, doc_path: Option<&Path>) {
+    let mut paths: Vec<PathBuf> = autosave.into_iter().collect();
+    paths.extend(doc_path.map(journal_path));
+    for path in paths {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                log::error!("failed to remove {}: {}", path.display(), e)
+            }
+            _ => {}
         }
     }
 }
 
+/// Records the state of the editor in `window`, so that we can save it if we panic.
+pub fn update_unsaved_document(window: WindowId, data: &EditorState) {
+    let mut docs = UNSAVED_DOCUMENTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    docs.retain(|doc| doc.window != window);
+    if data.changed_since_last_save() {
+        docs.push(UnsavedDocument {
+            window,
+            path: data.save_path.clone(),
+            data: SaveFileData::from_editor_state(data),
+        });
+    }
+}
+
+/// Forgets about the editor in `window` (because it was closed).
+pub fn remove_unsaved_document(window: WindowId) {
+    let mut docs = UNSAVED_DOCUMENTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    docs.retain(|doc| doc.window != window);
+}
+
+/// Installs a panic hook that tries to save all the documents with unsaved changes.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        // Saving spawns threads, and if one of them panics we don't want to try again.
+        if SAVING_AFTER_PANIC.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        // If the panic happened while someone was holding the lock, the data might be in a weird
+        // state (and we'd deadlock if the panicking thread was the one holding the lock).
+        let docs = match UNSAVED_DOCUMENTS.try_lock() {
+            Ok(docs) => docs,
+            Err(TryLockError::Poisoned(_)) | Err(TryLockError::WouldBlock) => {
+                eprintln!("couldn't save your unsaved changes, sorry");
+                return;
+            }
+        };
+        for doc in docs.iter() {
+            if let Some(path) = autosave_path(doc.path.as_deref()) {
+                match doc.data.save_to_path(&path) {
+                    Ok(()) => eprintln!("saved your unsaved changes to {}", path.display()),
+                    Err(e) => eprintln!("failed to save to {}: {}", path.display(), e),
+                }
+            }
+        }
+    }));
+}
+
+// Appends the changes in `data` to the journal of the document at `path`, starting a new journal
+// if the current one doesn't belong to this version of the document.
+fn write_journal(
+    journal: &mut Option<Journal>,
+    path: &Path,
+    base: &SaveFileData,
+    data: &SaveFileData,
+) -> anyhow::Result<()> {
+    if !journal.as_ref().map_or(false, |j| j.applies_to(path, base)) {
+        *journal = None;
+        *journal = Some(Journal::create(path, base.clone())?);
+    }
+    journal.as_mut().unwrap().append(data)
+}
+
 pub fn spawn_autosave_thread(ext_cmd: ExtEventSink, id: WindowId) -> Sender<AutosaveData> {
     let (tx, rx) = crossbeam_channel::unbounded::<AutosaveData>();
     std::thread::spawn(move || {
+        let mut journal = None;
         while let Ok(autosave) = rx.recv() {
             // We save only the most recent requested file (so as not to fall behind in case saving
             // is really slow, or the autosave interval is really short).
             let autosave = rx.try_iter().last().unwrap_or(autosave);
-            if let Some(path) = autosave.autosave_path() {
-                let result = autosave.data.save_to_path(&path);
-                let _ = ext_cmd.submit_command(
-                    FINISHED_ASYNC_SAVE,
-                    Box::new(AsyncSaveResult {
-                        path,
-                        data: autosave.data,
-                        error: result.err().map(|e| e.to_string()),
-                        autosave: true,
-                    }),
-                    id,
-                );
-            } else {
-                log::warn!("not autosaving, couldn't determine the path");
-            }
+
+            // If the document has a file, we only need to write down what changed since the
+            // last save. Otherwise, we save the whole thing.
+            let (path, result) = match (&autosave.path, &autosave.base) {
+                (Some(doc_path), Some(base)) => (
+                    journal_path(doc_path),
+                    write_journal(&mut journal, doc_path, base, &autosave.data),
+                ),
+                _ => {
+                    if let Some(path) = autosave_path(autosave.path.as_deref()) {
+                        let result = autosave.data.save_to_path(&path);
+                        (path, result)
+                    } else {
+                        log::warn!("not autosaving, couldn't determine the path");
+                        continue;
+                    }
+                }
+            };
+            let _ = ext_cmd.submit_command(
+                FINISHED_ASYNC_SAVE,
+                Box::new(AsyncSaveResult {
+                    path,
+                    data: autosave.data,
+                    error: result.err().map(|e| e.to_string()),
+                    autosave: true,
+                }),
+                id,
+            );
         }
     });
 
     tx
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn discarded_untitled_recovery() {
+        let dir = std::env::temp_dir().join(format!("scribl-autosave-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let autosave = dir.join("untitled-autosave.scb");
+        let data = &include_bytes!("../sample/intro.scb")[..];
+        SaveFileData::load_from(Cursor::new(data))
+            .unwrap()
+            .save_to_path(&autosave)
+            .unwrap();
+
+        assert!(find_recovery_at(Some(autosave.clone()), None, None).is_some());
+        discard_autosaves_at(Some(autosave.clone()), None);
+        assert!(find_recovery_at(Some(autosave), None, None).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
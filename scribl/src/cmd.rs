@@ -3,9 +3,10 @@ use std::path::PathBuf;
 
 use scribl_curves::Time;
 
-use crate::audio::{AudioRecordingStatus, TalkSnippet};
+use crate::audio::{AudioProcessing, AudioRecordingStatus, TalkSnippet};
+use crate::autosave::Recovery;
 use crate::encode::EncodingStatus;
-use crate::{SaveFileData, ScriblState};
+use crate::{SaveFileData, ScriblState, SnippetId};
 
 /// Selects the snippet below (in the timeline) the currently selected snippet.
 pub const SELECT_SNIPPET_BELOW: Selector = Selector::new("scribl.select-snippet-below");
@@ -17,15 +18,57 @@ pub const SELECT_SNIPPET_ABOVE: Selector = Selector::new("scribl.select-snippet-
 pub const RECORDING_AUDIO_STATUS: Selector<AudioRecordingStatus> =
     Selector::new("scribl.recording-audio-status");
 
+/// This command is sent by the audio thread when the chosen audio device couldn't be found (and
+/// so the default device is being used instead).
+pub const AUDIO_DEVICE_MISSING: Selector<String> = Selector::new("scribl.audio-device-missing");
+
+/// Looks for long pauses in the speech, and shows a dialog offering to shorten them.
+pub const SHOW_PAUSE_SHORTENING: Selector = Selector::new("scribl.show-pause-shortening");
+
+/// Shows the dialog for choosing audio devices.
+pub const SHOW_AUDIO_SETTINGS: Selector = Selector::new("scribl.show-audio-settings");
+
 /// Adds a new audio snippet.
 pub const ADD_TALK_SNIPPET: Selector<TalkSnippetCmd> = Selector::new("scribl.add-talk-snippet");
 
 /// Changes the current animation time, assuming that the UI is in the idle state.
 pub const WARP_TIME_TO: Selector<Time> = Selector::new("scribl.warp-time-to");
 
+/// Shows the dialog for editing the animation's title, chapters, etc.
+pub const SHOW_PROPERTIES: Selector = Selector::new("scribl.show-properties");
+
+/// Imports an audio file as a new talk snippet at the current time.
+pub const IMPORT_AUDIO: Selector<FileInfo> = Selector::new("scribl.import-audio");
+
+/// Imports an audio file as a new background music snippet at the current time.
+pub const IMPORT_MUSIC: Selector<FileInfo> = Selector::new("scribl.import-music");
+
+/// Audio files are decoded asynchronously. When decoding finishes, one of these commands gets
+/// sent.
+pub const FINISHED_ASYNC_IMPORT: Selector<AsyncImportResult> =
+    Selector::new("scribl.finished-async-import");
+
+/// Applies some processing (in the background) to the selected audio snippet.
+pub const PROCESS_AUDIO: Selector<AudioProcessing> = Selector::new("scribl.process-audio");
+
+/// While audio is being processed, this command is periodically sent with the fraction of the
+/// work that's done.
+pub const AUDIO_PROCESSING_PROGRESS: Selector<f64> =
+    Selector::new("scribl.audio-processing-progress");
+
+/// When audio processing finishes, one of these commands gets sent.
+pub const FINISHED_ASYNC_PROCESS: Selector<AsyncProcessResult> =
+    Selector::new("scribl.finished-async-process");
+
 /// Exports the current animation as a video.
 pub const EXPORT: Selector<FileInfo> = Selector::new("scribl.export");
 
+/// Exports the selected range of the current animation as a video.
+pub const EXPORT_RANGE: Selector<FileInfo> = Selector::new("scribl.export-range");
+
+/// Exports the audio of the current animation, without the video.
+pub const EXPORT_AUDIO: Selector<FileInfo> = Selector::new("scribl.export-audio");
+
 /// While the video is encoding asynchronously, it periodically sends these commands.
 pub const ENCODING_STATUS: Selector<EncodingStatus> = Selector::new("scribl.encoding-status");
 
@@ -43,6 +86,30 @@ pub const FINISHED_ASYNC_SAVE: Selector<AsyncSaveResult> =
 pub struct AsyncLoadResult {
     pub path: PathBuf,
     pub save_data: Result<SaveFileData, String>,
+    /// If true, this is just a preview: the audio hasn't been loaded yet, and the rest of the
+    /// file will arrive in another `FINISHED_ASYNC_LOAD`.
+    pub preview: bool,
+    /// Unsaved changes to the file that were left over from last time (if there were any).
+    pub recovery: Option<Recovery>,
+}
+
+#[derive(Clone)]
+pub struct AsyncImportResult {
+    pub path: PathBuf,
+    pub snip: Result<TalkSnippet, String>,
+    /// If true, the snippet goes in the music lane instead of with the speech.
+    pub music: bool,
+}
+
+#[derive(Clone)]
+pub struct AsyncProcessResult {
+    /// The snippet that was processed.
+    pub id: SnippetId,
+    /// The snippet as it was before processing. If it changed in the meantime, we don't replace
+    /// it.
+    pub orig: TalkSnippet,
+    pub op: AudioProcessing,
+    pub snip: Result<TalkSnippet, String>,
 }
 
 #[derive(Clone)]
@@ -58,6 +125,8 @@ pub struct ExportCmd {
     pub scribl: ScriblState,
     pub filename: PathBuf,
     pub config: crate::config::Export,
+    /// If set, we export only this range (given as a start and end time) of the animation.
+    pub range: Option<(Time, Time)>,
 }
 
 pub struct TalkSnippetCmd {
@@ -22,9 +22,22 @@ fn default_vad_threshold() -> f32 {
     0.3
 }
 
+fn default_keep_raw() -> bool {
+    true
+}
+
+// By default, we normalize to loudness -20. This is quieter than many sources ask for (e.g.
+// youtube recommends -13 to -15), but going louder without compression tends to introduce
+// clipping.
+fn default_target_loudness() -> f64 {
+    -20.0
+}
+
 #[derive(Clone, Debug, Default, Deserialize)]
 pub struct Config {
     pub audio_input: AudioInput,
+    #[serde(default)]
+    pub audio_output: AudioOutput,
     pub export: Export,
 }
 
@@ -41,6 +54,11 @@ pub struct Export {
     /// Bitrate of the exported video.
     #[serde(default = "default_video_bitrate")]
     pub bitrate: u32,
+
+    /// If set, the exported audio is normalized (as a whole) to this loudness, in LUFS. For
+    /// example, youtube recommends -14.
+    #[serde(default)]
+    pub target_loudness: Option<f64>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
@@ -53,6 +71,33 @@ pub struct AudioInput {
     /// sounds; 1.0 means we remove everything.)
     #[serde(default = "default_vad_threshold")]
     pub vad_threshold: f32,
+
+    /// Should we keep the audio from before noise removal? This makes the save files bigger, but
+    /// it means that the noise removal can be redone later with different settings.
+    #[serde(default = "default_keep_raw")]
+    pub keep_raw: bool,
+
+    /// The loudness (in LUFS) that we normalize recorded speech to.
+    #[serde(default = "default_target_loudness")]
+    pub target_loudness: f64,
+
+    /// Should we compress the dynamic range of recorded speech (and limit its peaks)? This allows
+    /// normalizing to a louder target without clipping.
+    #[serde(default)]
+    pub compress: bool,
+
+    /// The name of the device to record from. If this isn't set (or if the device isn't found), we
+    /// use the system default.
+    #[serde(default)]
+    pub device: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AudioOutput {
+    /// The name of the device to play audio on. If this isn't set (or if the device isn't found),
+    /// we use the system default.
+    #[serde(default)]
+    pub device: Option<String>,
 }
 
 impl Default for AudioInput {
@@ -60,6 +105,10 @@ impl Default for AudioInput {
         AudioInput {
             remove_noise: default_remove_noise(),
             vad_threshold: default_vad_threshold(),
+            keep_raw: default_keep_raw(),
+            target_loudness: default_target_loudness(),
+            compress: false,
+            device: None,
         }
     }
 }
@@ -70,6 +119,7 @@ impl Default for Export {
             height: default_video_height(),
             fps: default_video_fps(),
             bitrate: default_video_bitrate(),
+            target_loudness: None,
         }
     }
 }
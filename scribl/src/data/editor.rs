@@ -1,14 +1,17 @@
-use druid::{Data, Lens, Point};
+use druid::{Color, Data, Lens, Point};
 use std::path::PathBuf;
 use std::time::Instant;
 
-use scribl_curves::{DrawSnippet, DrawSnippetId, StrokeInProgress, StrokeSeq, Time, TimeDiff};
+use scribl_curves::{
+    CameraTrack, DrawSnippet, DrawSnippetId, PaperTemplate, StrokeInProgress, StrokeSeq, Time,
+    TimeDiff, TimeSpan, Viewport,
+};
 
-use crate::audio::{TalkSnippetId, TalkSnippets};
+use crate::audio::{Ducking, TalkSnippet, TalkSnippetId, TalkSnippets};
 use crate::config::Config;
-use crate::data::{DenoiseSetting, ScriblState, Settings};
+use crate::data::{AspectRatio, DenoiseSetting, ScriblState, Settings};
 use crate::encode::EncodingStatus;
-use crate::undo::{UndoStack, UndoState};
+use crate::undo::{UndoId, UndoStack, UndoState};
 use crate::SaveFileData;
 
 impl From<DrawSnippetId> for SnippetId {
@@ -32,6 +35,10 @@ pub struct InProgressStatus {
     pub autosaving: Option<PathBuf>,
     #[data(same_fn = "PartialEq::eq")]
     pub loading: Option<PathBuf>,
+    #[data(same_fn = "PartialEq::eq")]
+    pub importing: Option<PathBuf>,
+    /// If some audio is being processed, this is the fraction of the work that's done.
+    pub processing: Option<f64>,
 }
 
 #[derive(Clone, Data)]
@@ -54,6 +61,17 @@ pub enum FinishedStatus {
         #[data(same_fn = "PartialEq::eq")]
         time: Instant,
     },
+    Imported {
+        #[data(same_fn = "PartialEq::eq")]
+        path: PathBuf,
+        #[data(same_fn = "PartialEq::eq")]
+        time: Instant,
+    },
+    Processed {
+        description: &'static str,
+        #[data(same_fn = "PartialEq::eq")]
+        time: Instant,
+    },
     Error(String),
 }
 
@@ -71,12 +89,45 @@ pub struct RecordingState {
     pub paused: bool,
     pub new_stroke: StrokeInProgress,
     pub new_stroke_seq: StrokeSeq,
+    /// If we're recording audio at the same time as drawing, this is the time at which the audio
+    /// capture started.
+    pub audio_start: Option<Time>,
 }
 
 #[derive(Copy, Clone, Data, Debug, Eq, Hash, PartialEq)]
 pub enum SnippetId {
     Draw(DrawSnippetId),
     Talk(TalkSnippetId),
+    Music(TalkSnippetId),
+}
+
+/// Audio snippets come in two "lanes": speech and background music. They get edited in the same
+/// ways, but the music is mixed in below the speech.
+#[derive(Copy, Clone, Data, Debug, Eq, Hash, PartialEq)]
+pub enum AudioLane {
+    Talk,
+    Music,
+}
+
+impl AudioLane {
+    pub fn snippet_id(self, id: TalkSnippetId) -> SnippetId {
+        match self {
+            AudioLane::Talk => SnippetId::Talk(id),
+            AudioLane::Music => SnippetId::Music(id),
+        }
+    }
+}
+
+impl SnippetId {
+    /// If this is an audio snippet, returns the lane that it belongs to and its id within that
+    /// lane.
+    pub fn audio(self) -> Option<(AudioLane, TalkSnippetId)> {
+        match self {
+            SnippetId::Draw(_) => None,
+            SnippetId::Talk(id) => Some((AudioLane::Talk, id)),
+            SnippetId::Music(id) => Some((AudioLane::Music, id)),
+        }
+    }
 }
 
 /// This data contains the state of an editor window.
@@ -86,7 +137,12 @@ pub struct EditorState {
     pub selected_snippet: Option<SnippetId>,
     pub settings: Settings,
 
-    pub mark: Option<Time>,
+    /// The start of the selected range. This is always the time of one of the marks in
+    /// `scribl.marks`.
+    pub range_start: Option<Time>,
+    /// The end of the selected range (also the time of one of the marks). If this is `None` but
+    /// `range_start` isn't, the selected range extends from `range_start` to the current time.
+    pub range_end: Option<Time>,
 
     pub action: CurrentAction,
 
@@ -113,6 +169,17 @@ pub struct EditorState {
     /// scale (and 0.0 is very loud).
     pub input_loudness: f64,
 
+    /// The long pauses in the speech (as `(start, end)` times) that we're proposing to shorten.
+    /// These are shown in the timeline while we ask whether to shorten them.
+    #[data(same_fn = "PartialEq::eq")]
+    pub long_pauses: Vec<(Time, Time)>,
+
+    /// When we finish recording a drawing together with audio, the drawing gets added right away
+    /// but the audio only arrives later from the audio thread. In the meantime, this holds the
+    /// drawing's undo action, so that the audio can be added to it if it's still the most recent.
+    #[data(ignore)]
+    awaiting_recorded_talk: Option<UndoId>,
+
     // There are several actions that we do asynchronously. Here, we have the most recent status of
     // these actions.
     pub status: AsyncOpsStatus,
@@ -135,7 +202,8 @@ impl EditorState {
             scribl: ScriblState::default(),
             settings: Settings::new(&config),
             selected_snippet: None,
-            mark: None,
+            range_start: None,
+            range_end: None,
 
             action: CurrentAction::Idle,
             undo: UndoStack::new(),
@@ -143,6 +211,8 @@ impl EditorState {
             time_snapshot: (Instant::now(), Time::ZERO),
             time: Time::ZERO,
             input_loudness: -f64::INFINITY,
+            long_pauses: Vec::new(),
+            awaiting_recorded_talk: None,
 
             status: AsyncOpsStatus::default(),
 
@@ -150,6 +220,7 @@ impl EditorState {
             saved_data: None,
             config,
         };
+        ret.scribl.metadata.created = Some(crate::data::metadata::now());
         ret.saved_data = Some(SaveFileData::from_editor_state(&ret));
         ret
     }
@@ -178,6 +249,26 @@ impl EditorState {
         });
     }
 
+    /// Adds a snippet of audio that was just recorded. `orig_start` is the time at which the
+    /// recording started.
+    pub fn add_recorded_talk_snippet(&mut self, snip: TalkSnippet, orig_start: Time) {
+        let prev_state = self.undo_state();
+        self.selected_snippet = Some(self.scribl.add_talk_snippet(snip).into());
+
+        // If this audio was recorded together with a drawing, it belongs to the drawing's undo
+        // step (unless something else has happened to the undo stack since then).
+        if let Some(id) = self.awaiting_recorded_talk.take() {
+            let redo_state = self.undo_state();
+            if self
+                .undo
+                .amend(id, redo_state, "record drawing and audio".to_owned())
+            {
+                return;
+            }
+        }
+        self.push_undo_state(prev_state.with_time(orig_start), "add audio");
+    }
+
     pub fn delete_selected_snippet(&mut self) {
         match self.selected_snippet {
             Some(SnippetId::Draw(id)) => {
@@ -190,6 +281,10 @@ impl EditorState {
                 state.scribl.delete_talk_snippet(id);
                 state.selected_snippet = None;
             }),
+            Some(SnippetId::Music(id)) => self.with_undo("delete music", |state| {
+                state.scribl.delete_music_snippet(id);
+                state.selected_snippet = None;
+            }),
             None => {
                 log::error!("No snippet id to delete");
             }
@@ -209,18 +304,38 @@ impl EditorState {
         }
     }
 
+    /// Splits the selected audio snippet in two at the current time, and selects the second
+    /// part (so that it can be moved around independently).
+    pub fn split_snippet(&mut self) {
+        if let Some((lane, id)) = self.selected_audio() {
+            let split = self.scribl.audio(lane).with_split_snippet(id, self.time);
+            if let Some((snips, new_id)) = split {
+                self.with_undo("split speech", |data| {
+                    *data.scribl.audio_mut(lane) = snips;
+                    data.selected_snippet = Some(lane.snippet_id(new_id));
+                });
+            } else {
+                log::warn!("cannot split, the selected speech isn't playing at the current time");
+            }
+        } else {
+            log::error!("cannot split, no speech selected");
+        }
+    }
+
     /// "Time-warps" the selected snippet.
     ///
-    /// The image that used to be displayed at the marked time will now be displayed at the current
-    /// time instead.
+    /// The image that used to be displayed at the start of the selected range will now be
+    /// displayed at the end of the selected range instead.
     pub fn warp_snippet(&mut self) {
-        if let (Some(mark_time), Some(SnippetId::Draw(id))) = (self.mark, self.selected_snippet) {
+        if let (Some((start, end)), Some(SnippetId::Draw(id))) =
+            (self.selected_range(), self.selected_snippet)
+        {
             self.with_undo("warp drawing", |data| {
-                data.scribl.draw = data.scribl.draw.with_new_lerp(id, data.time(), mark_time);
-                data.warp_time_to(mark_time);
+                data.scribl.draw = data.scribl.draw.with_new_lerp(id, end, start);
+                data.warp_time_to(start);
             });
-        } else if self.mark.is_none() {
-            log::error!("cannot warp, no marked time");
+        } else if self.range_start.is_none() {
+            log::error!("cannot warp, no range selected");
         } else {
             log::error!("cannot warp, nothing selected");
         }
@@ -239,17 +354,22 @@ impl EditorState {
                     data.scribl.talk = data.scribl.talk.with_shifted_snippet(id, by);
                 });
             }
+            SnippetId::Music(id) => {
+                self.with_undo("time-shift music", |data| {
+                    data.scribl.music = data.scribl.music.with_shifted_snippet(id, by);
+                });
+            }
         }
     }
 
     /// Silences the currently selected range of audio.
     pub fn silence_audio(&mut self) {
-        if let (Some(mark_time), Some(SnippetId::Talk(id))) = (self.mark, self.selected_snippet) {
+        if let (Some((start, end)), Some((lane, id))) =
+            (self.selected_range(), self.selected_audio())
+        {
             self.with_undo("silence speech", |data| {
-                data.scribl.talk =
-                    data.scribl
-                        .talk
-                        .with_silenced_snippet(id, mark_time, data.time());
+                let snips = data.scribl.audio_mut(lane);
+                *snips = snips.with_silenced_snippet(id, start, end);
             });
         }
     }
@@ -258,13 +378,13 @@ impl EditorState {
     ///
     /// If this snippet has more audio after the deleted portion, it will be "moved back."
     pub fn snip_audio(&mut self) {
-        if let (Some(mark_time), Some(SnippetId::Talk(id))) = (self.mark, self.selected_snippet) {
+        if let (Some((start, end)), Some((lane, id))) =
+            (self.selected_range(), self.selected_audio())
+        {
             self.with_undo("snip speech", |data| {
-                data.scribl.talk =
-                    data.scribl
-                        .talk
-                        .with_snipped_snippet(id, mark_time, data.time());
-                if !data.scribl.talk.has_snippet(id) {
+                let snips = data.scribl.audio_mut(lane);
+                *snips = snips.with_snipped_snippet(id, start, end);
+                if !snips.has_snippet(id) {
                     data.selected_snippet = None;
                 }
             });
@@ -273,27 +393,292 @@ impl EditorState {
 
     /// Multiplies the volume of the selected audio snippet by the given factor.
     pub fn multiply_volume(&mut self, factor: f64) {
-        if let Some(SnippetId::Talk(id)) = self.selected_snippet {
+        if let Some((lane, id)) = self.selected_audio() {
             let text = if factor > 1.0 {
                 "increase volume"
             } else {
                 "decrease volume"
             };
             self.with_undo(text, |data| {
-                data.scribl.talk = data.scribl.talk.with_multiplied_snippet(id, factor);
+                let snips = data.scribl.audio_mut(lane);
+                *snips = snips.with_multiplied_snippet(id, factor);
+            });
+        }
+    }
+
+    /// Multiplies the volume of the selected audio snippet by the given factor, but only within
+    /// the selected range.
+    pub fn multiply_range_volume(&mut self, factor: f64) {
+        if let (Some((start, end)), Some((lane, id))) =
+            (self.selected_range(), self.selected_audio())
+        {
+            let text = if factor > 1.0 {
+                "increase range volume"
+            } else {
+                "decrease range volume"
+            };
+            self.with_undo(text, |data| {
+                let snips = data.scribl.audio_mut(lane);
+                *snips = snips.with_multiplied_snippet_range(id, start, end, factor);
             });
         }
     }
 
-    /// Sets the timeline mark to the current time.
+    /// Makes the selected audio snippet fade in from its start until the current time.
+    pub fn fade_in_audio(&mut self) {
+        if let Some((lane, id)) = self.selected_audio() {
+            self.with_undo("fade in speech", |data| {
+                let time = data.time;
+                let snips = data.scribl.audio_mut(lane);
+                *snips = snips.with_faded_in_snippet(id, time);
+            });
+        }
+    }
+
+    /// Makes the selected audio snippet fade out from the current time until its end.
+    pub fn fade_out_audio(&mut self) {
+        if let Some((lane, id)) = self.selected_audio() {
+            self.with_undo("fade out speech", |data| {
+                let time = data.time;
+                let snips = data.scribl.audio_mut(lane);
+                *snips = snips.with_faded_out_snippet(id, time);
+            });
+        }
+    }
+
+    /// Deletes the selected range of time from the whole animation, and moves everything after
+    /// it earlier to fill the hole.
+    pub fn ripple_delete(&mut self) {
+        let (start, end) = match self.selected_range() {
+            Some((a, b)) if a != b => (a.min(b), a.max(b)),
+            _ => return,
+        };
+        self.with_undo("ripple delete", |state| {
+            state.scribl.delete_range(start, end);
+            state.range_start = None;
+            state.range_end = None;
+            state.deselect_if_deleted();
+            state.warp_time_to(start);
+        });
+    }
+
+    // If the selected snippet was deleted, deselects it.
+    fn deselect_if_deleted(&mut self) {
+        let still_exists = match self.selected_snippet {
+            Some(SnippetId::Draw(id)) => self.scribl.draw.has_snippet(id),
+            Some(SnippetId::Talk(id)) => self.scribl.talk.has_snippet(id),
+            Some(SnippetId::Music(id)) => self.scribl.music.has_snippet(id),
+            None => true,
+        };
+        if !still_exists {
+            self.selected_snippet = None;
+        }
+    }
+
+    fn pause_target(&self) -> TimeDiff {
+        TimeDiff::from_micros((self.settings.pause_target * 1e6) as i64)
+    }
+
+    /// Finds the pauses in the speech that are longer than `settings.pause_threshold`. They don't
+    /// get shortened until `shorten_pauses` is called.
+    pub fn find_long_pauses(&mut self) {
+        let threshold = TimeDiff::from_micros((self.settings.pause_threshold * 1e6) as i64);
+        self.long_pauses =
+            crate::audio::long_pauses(&self.scribl.talk, threshold, self.pause_target());
+    }
+
+    /// Shortens the pauses found by `find_long_pauses` to `settings.pause_target`. The audio loses
+    /// the middle of each pause, while the drawings (and everything else) get sped up to match.
+    pub fn shorten_pauses(&mut self) {
+        let pauses = std::mem::take(&mut self.long_pauses);
+        if pauses.is_empty() {
+            return;
+        }
+        let target = self.pause_target();
+        // Moves a time to where it ends up after shortening.
+        let shift = |t: Time| {
+            let mut ret = t;
+            for &(start, end) in &pauses {
+                if t >= end {
+                    ret = ret - (end - start - target);
+                } else if t > start {
+                    let new_span = TimeSpan::new(start, start + target);
+                    ret = ret - (t - TimeSpan::new(start, end).interpolate_to(t, new_span));
+                }
+            }
+            ret
+        };
+        let time = shift(self.time);
+        self.with_undo("shorten pauses", |state| {
+            // Going backwards means that the earlier pauses' times don't change.
+            for &(start, end) in pauses.iter().rev() {
+                state.scribl.shorten_range(start, end, target);
+            }
+            state.range_start = None;
+            state.range_end = None;
+            state.warp_time_to(time);
+        });
+    }
+
+    /// Inserts a gap of length `len` into the whole animation at the current time, moving
+    /// everything after it later.
+    pub fn insert_gap(&mut self, len: TimeDiff) {
+        let at = self.time;
+        self.with_undo("insert gap", |state| {
+            state.scribl.insert_gap(at, len);
+            let shift = |t: Time| if t >= at { t + len } else { t };
+            state.range_start = state.range_start.map(shift);
+            state.range_end = state.range_end.map(shift);
+        });
+    }
+
+    /// If the selected snippet is an audio snippet (speech or music), returns its lane and id.
+    pub fn selected_audio(&self) -> Option<(AudioLane, TalkSnippetId)> {
+        self.selected_snippet.and_then(SnippetId::audio)
+    }
+
+    /// The selected range, as `(start, end)`. Note that `start` might be after `end`.
+    pub fn selected_range(&self) -> Option<(Time, Time)> {
+        self.range_start
+            .map(|start| (start, self.range_end.unwrap_or(self.time)))
+    }
+
+    // Adds a mark at the current time, unless there is one already.
+    fn add_mark_at_current_time(&mut self) {
+        let name = format!("Mark {}", self.scribl.marks.len() + 1);
+        self.scribl.marks = self.scribl.marks.with_mark(self.time, name);
+    }
+
+    /// Adds a mark at the current time, and makes it the start of the selected range.
     pub fn set_mark(&mut self) {
-        self.with_undo("set mark", |state| state.mark = Some(state.time()));
+        self.with_undo("set mark", |state| {
+            state.add_mark_at_current_time();
+            state.range_start = Some(state.time);
+            state.range_end = None;
+        });
+    }
+
+    /// Adds a mark at the current time, and makes it the end of the selected range.
+    pub fn set_range_end(&mut self) {
+        if self.range_start.is_some() {
+            self.with_undo("set range end", |state| {
+                state.add_mark_at_current_time();
+                state.range_end = Some(state.time);
+            });
+        }
+    }
+
+    /// Deselects the selected range (but leaves the marks alone).
+    pub fn clear_range(&mut self) {
+        if self.range_start.is_some() {
+            self.with_undo("clear range", |state| {
+                state.range_start = None;
+                state.range_end = None;
+            });
+        }
+    }
+
+    /// Deletes the mark at the current time (if there is one).
+    pub fn delete_mark(&mut self) {
+        let time = self.time;
+        if self.scribl.marks.mark_at(time).is_some() {
+            self.with_undo("delete mark", |state| {
+                state.scribl.marks = state.scribl.marks.without_mark_at(time);
+                if state.range_start == Some(time) {
+                    state.range_start = None;
+                    state.range_end = None;
+                } else if state.range_end == Some(time) {
+                    state.range_end = None;
+                }
+            });
+        }
     }
 
-    /// Removes the current timeline mark.
-    pub fn clear_mark(&mut self) {
-        if self.mark.is_some() {
-            self.with_undo("clear mark", |state| state.mark = None);
+    /// Moves the current time to the next mark (if there is one).
+    pub fn jump_to_next_mark(&mut self) {
+        if let Some(time) = self.scribl.marks.next_after(self.time).map(|m| m.time) {
+            self.warp_time_to(time);
+        }
+    }
+
+    /// Moves the current time to the previous mark (if there is one).
+    pub fn jump_to_prev_mark(&mut self) {
+        if let Some(time) = self.scribl.marks.prev_before(self.time).map(|m| m.time) {
+            self.warp_time_to(time);
+        }
+    }
+
+    /// Changes the aspect ratio of the drawing.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: AspectRatio) {
+        if self.scribl.aspect_ratio != aspect_ratio {
+            self.with_undo("change aspect ratio", |state| {
+                state.scribl.aspect_ratio = aspect_ratio
+            });
+        }
+    }
+
+    /// Changes the color of the background.
+    pub fn set_background_color(&mut self, color: Color) {
+        if self.scribl.background.color.as_rgba_u32() != color.as_rgba_u32() {
+            self.with_undo("change background", |state| {
+                state.scribl.background.color = color
+            });
+        }
+    }
+
+    /// Changes the template that gets drawn on the background.
+    pub fn set_paper_template(&mut self, template: PaperTemplate) {
+        if self.scribl.background.template != template {
+            self.with_undo("change background", |state| {
+                state.scribl.background.template = template
+            });
+        }
+    }
+
+    /// Changes the settings for ducking the music under the speech.
+    pub fn set_ducking(&mut self, ducking: Ducking) {
+        if self.scribl.ducking != ducking {
+            self.with_undo("change ducking", |state| state.scribl.ducking = ducking);
+        }
+    }
+
+    /// Returns the camera's viewport at the current time.
+    pub fn viewport(&self) -> Viewport {
+        self.scribl.camera.viewport_at(self.time)
+    }
+
+    /// Moves the camera, by adding a camera key-frame at the current time.
+    ///
+    /// This doesn't register an undo action, because camera moves tend to come in a long
+    /// sequence of small changes; the caller is responsible for registering a single undo action
+    /// for the whole sequence.
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.scribl.camera = self.scribl.camera.with_keyframe(self.time, viewport);
+    }
+
+    /// Deletes all the camera key-frames.
+    pub fn clear_camera_track(&mut self) {
+        if !self.scribl.camera.is_empty() {
+            self.with_undo("clear camera", |state| {
+                state.scribl.camera = CameraTrack::default()
+            });
+        }
+    }
+
+    /// Adds a chapter starting at the current time.
+    pub fn add_chapter(&mut self) {
+        let title = format!("Chapter {}", self.scribl.chapters.list.len() + 1);
+        self.with_undo("add chapter", |state| {
+            state.scribl.chapters = state.scribl.chapters.with_chapter(state.time, title)
+        });
+    }
+
+    /// Removes the chapter that the current time belongs to (if there is one).
+    pub fn remove_chapter(&mut self) {
+        if let Some(start) = self.scribl.chapters.chapter_at(self.time).map(|c| c.time) {
+            self.with_undo("remove chapter", |state| {
+                state.scribl.chapters = state.scribl.chapters.without_chapter_at(start)
+            });
         }
     }
 
@@ -447,12 +832,29 @@ impl EditorState {
         ret
     }
 
+    /// Replaces the document with some recovered unsaved changes. We leave `saved_data` alone,
+    /// because the changes haven't made it into the document's file yet.
+    pub fn restore_recovered(&mut self, recovered: &SaveFileData) {
+        let prev_state = self.undo_state();
+        self.scribl = ScriblState::from_save_file(recovered);
+        self.push_undo_state(prev_state, "restore unsaved changes");
+    }
+
     pub fn undo_state(&self) -> UndoState {
         UndoState {
             snippets: self.scribl.draw.clone(),
             audio_snippets: self.scribl.talk.clone(),
+            music_snippets: self.scribl.music.clone(),
+            ducking: self.scribl.ducking.clone(),
+            aspect_ratio: self.scribl.aspect_ratio,
+            background: self.scribl.background.clone(),
+            camera: self.scribl.camera.clone(),
+            metadata: self.scribl.metadata.clone(),
+            chapters: self.scribl.chapters.clone(),
+            marks: self.scribl.marks.clone(),
             selected_snippet: self.selected_snippet.clone(),
-            mark: self.mark,
+            range_start: self.range_start,
+            range_end: self.range_end,
             time: self.time,
             action: self.action.clone(),
         }
@@ -471,7 +873,8 @@ impl EditorState {
     fn restore_undo_state(&mut self, undo: UndoState) {
         self.scribl.restore_undo_state(&undo);
         self.selected_snippet = undo.selected_snippet;
-        self.mark = undo.mark;
+        self.range_start = undo.range_start;
+        self.range_end = undo.range_end;
         self.warp_time_to(undo.time);
         self.action = CurrentAction::Idle;
 
@@ -482,6 +885,8 @@ impl EditorState {
         // the state is recording but paused.
         if let CurrentAction::Recording(mut rec_state) = undo.action {
             rec_state.paused = true;
+            // The audio capture can't be rewound, so after undoing we just keep drawing.
+            rec_state.audio_start = None;
             rec_state.new_stroke = StrokeInProgress::new();
 
             if !rec_state.new_stroke_seq.is_empty() {
@@ -544,6 +949,68 @@ impl EditorState {
         };
     }
 
+    /// Adds a newly imported audio snippet (if the import succeeded), and updates the status.
+    pub fn finish_import(&mut self, import: &crate::cmd::AsyncImportResult) {
+        self.status.in_progress.importing = None;
+        self.status.last_finished = match &import.snip {
+            Ok(snip) => {
+                let snip = snip.clone();
+                if import.music {
+                    self.with_undo("import music", |data| {
+                        let id = data.scribl.add_music_snippet(snip);
+                        data.selected_snippet = Some(SnippetId::Music(id));
+                    });
+                } else {
+                    self.with_undo("import audio", |data| {
+                        data.selected_snippet = Some(data.scribl.add_talk_snippet(snip).into());
+                    });
+                }
+                Some(FinishedStatus::Imported {
+                    path: import.path.clone(),
+                    time: Instant::now(),
+                })
+            }
+            Err(e) => {
+                log::error!("error importing: '{}'", e);
+                Some(FinishedStatus::Error(e.clone()))
+            }
+        };
+    }
+
+    /// Replaces a snippet with its processed version (if processing succeeded, and if the snippet
+    /// wasn't changed while it was being processed), and updates the status.
+    pub fn finish_processing(&mut self, result: &crate::cmd::AsyncProcessResult) {
+        self.status.in_progress.processing = None;
+        let (lane, id) = match result.id.audio() {
+            Some(x) => x,
+            None => return,
+        };
+        self.status.last_finished = match &result.snip {
+            Ok(snip) => {
+                let audio = self.scribl.audio(lane);
+                if audio.has_snippet(id) && audio.snippet(id).same(&result.orig) {
+                    let snips = audio.with_snippet(id, snip.clone());
+                    self.with_undo(result.op.description(), |data| {
+                        *data.scribl.audio_mut(lane) = snips;
+                    });
+                    Some(FinishedStatus::Processed {
+                        description: result.op.description(),
+                        time: Instant::now(),
+                    })
+                } else {
+                    log::warn!("not applying processing, the snippet changed in the meantime");
+                    Some(FinishedStatus::Error(
+                        "the audio changed while it was being processed".to_owned(),
+                    ))
+                }
+            }
+            Err(e) => {
+                log::error!("error processing audio: '{}'", e);
+                Some(FinishedStatus::Error(e.clone()))
+            }
+        };
+    }
+
     pub fn update_save_status(&mut self, save: &crate::cmd::AsyncSaveResult) {
         if save.autosave {
             self.status.in_progress.autosaving = None;
@@ -573,19 +1040,11 @@ impl EditorState {
         !self.saved_data.same(&Some(new_save))
     }
 
-    pub fn audio_state(&self) -> AudioState {
-        use CurrentAction::*;
-
-        let snips = self.scribl.talk.clone();
-        let play = |velocity: f64| AudioState::Playing {
-            start_time: self.time_snapshot.1,
-            snips,
-            velocity,
-        };
-
+    /// The settings for processing recorded audio. These come from the config file, but the UI
+    /// can override some of them.
+    pub fn input_config(&self) -> crate::config::AudioInput {
         let mut config = self.config.audio_input.clone();
 
-        // We allow the UI to override what's in the config file.
         match self.settings.denoise_setting {
             DenoiseSetting::DenoiseOn => {
                 config.remove_noise = true;
@@ -598,10 +1057,34 @@ impl EditorState {
                 config.remove_noise = true;
             }
         }
+        config
+    }
 
+    pub fn audio_state(&self) -> AudioState {
+        use CurrentAction::*;
+
+        let snips = self.scribl.talk.clone();
+        let music = self.scribl.music.clone();
+        let ducking = self.scribl.ducking.clone();
+        let play = |velocity: f64| AudioState::Playing {
+            start_time: self.time_snapshot.1,
+            snips,
+            music,
+            ducking,
+            velocity,
+        };
+
+        let config = self.input_config();
         match &self.action {
             Playing => play(1.0),
             Scanning(x) => play(*x),
+            Recording(RecordingState {
+                audio_start: Some(t),
+                ..
+            }) => AudioState::Recording {
+                start_time: *t,
+                config,
+            },
             Recording(state) if !state.paused => play(state.time_factor),
             RecordingAudio(t) => AudioState::Recording {
                 start_time: *t,
@@ -619,6 +1102,23 @@ impl EditorState {
                 paused: true,
                 new_stroke: StrokeInProgress::new(),
                 new_stroke_seq: StrokeSeq::default(),
+                audio_start: None,
+            });
+            state.take_time_snapshot();
+        });
+    }
+
+    /// Starts recording a drawing and audio at the same time. Unlike `draw`, the time starts
+    /// moving right away (and in real time), since the audio doesn't wait for the first stroke.
+    pub fn draw_and_talk(&mut self) {
+        self.finish_action();
+        self.with_transient_undo("start recording", |state| {
+            state.action = CurrentAction::Recording(RecordingState {
+                time_factor: 1.0,
+                paused: false,
+                new_stroke: StrokeInProgress::new(),
+                new_stroke_seq: StrokeSeq::default(),
+                audio_start: Some(state.time),
             });
             state.take_time_snapshot();
         });
@@ -631,6 +1131,9 @@ impl EditorState {
     }
 
     pub fn talk(&mut self) {
+        // If the audio from a previous combined recording never showed up (e.g. because it was
+        // silent), don't let this recording get mixed up with it.
+        self.awaiting_recorded_talk = None;
         self.finish_action();
         self.action = CurrentAction::RecordingAudio(self.time);
         self.take_time_snapshot();
@@ -638,9 +1141,16 @@ impl EditorState {
 
     pub fn finish_action(&mut self) {
         match self.action {
-            CurrentAction::Recording(_) => {
+            CurrentAction::Recording(ref state) => {
+                let with_audio = state.audio_start.is_some();
                 if let Some(new_snippet) = self.stop_recording() {
                     self.add_draw_snippet(new_snippet);
+                    if with_audio {
+                        self.awaiting_recorded_talk = self.undo.last_id();
+                    }
+                }
+                if with_audio {
+                    self.input_loudness = -f64::INFINITY;
                 }
             }
             CurrentAction::RecordingAudio(_) => {
@@ -690,8 +1200,26 @@ impl CurrentAction {
         matches!(*self, CurrentAction::Playing)
     }
 
+    /// Returns `true` if we're recording audio (possibly while also drawing).
     pub fn is_recording_audio(&self) -> bool {
-        matches!(self, &CurrentAction::RecordingAudio(_))
+        matches!(
+            self,
+            &CurrentAction::RecordingAudio(_)
+                | &CurrentAction::Recording(RecordingState {
+                    audio_start: Some(_),
+                    ..
+                })
+        )
+    }
+
+    pub fn is_recording_with_audio(&self) -> bool {
+        matches!(
+            self,
+            &CurrentAction::Recording(RecordingState {
+                audio_start: Some(_),
+                ..
+            })
+        )
     }
 
     pub fn is_idle(&self) -> bool {
@@ -730,6 +1258,8 @@ pub enum AudioState {
     Idle,
     Playing {
         snips: TalkSnippets,
+        music: TalkSnippets,
+        ducking: Ducking,
         start_time: Time,
         velocity: f64,
     },
@@ -0,0 +1,376 @@
+//! An append-only journal of changes to a document.
+//!
+//! Writing out the whole document on every autosave gets slow for big projects (mostly because of
+//! the audio). So once a document has a file, we autosave it by appending whatever changed since
+//! the previous autosave to a journal that lives next to the document. When the document gets
+//! saved explicitly, everything in the journal makes it into the document and so the journal is
+//! deleted. When a document is opened, we offer to replay its journal (if it has one).
+//!
+//! The journal file consists of `MAGIC` followed by a sequence of records, each of which is a
+//! CBOR-encoded value preceded by its length (as a little-endian `u32`). The first record is a
+//! `Fingerprint` of the document that the journal applies to, and every other record is a
+//! `JournalEntry`. If we crash in the middle of writing a record, the incomplete record is
+//! ignored when replaying.
+
+use anyhow::anyhow;
+use druid::Data;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use scribl_curves::{DrawSnippet, DrawSnippetId};
+
+use crate::audio::{TalkSnippet, TalkSnippetId};
+use crate::data::save::Meta;
+use crate::SaveFileData;
+
+const MAGIC: &[u8; 8] = b"\x89SCRJNL\n";
+
+/// Identifies a particular version of the document on disk, so that we don't apply a journal to
+/// the wrong version of the document (for example, if the document was modified by someone else
+/// in the meantime).
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Fingerprint {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> std::io::Result<Fingerprint> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Fingerprint {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+// This is part of the journal file format, so it needs to remain stable.
+#[derive(Deserialize, Serialize)]
+enum JournalEntry {
+    PutDraw(DrawSnippetId, DrawSnippet),
+    RemoveDraw(DrawSnippetId),
+    PutTalk(TalkSnippetId, TalkSnippet),
+    RemoveTalk(TalkSnippetId),
+    Properties(Meta),
+    PutMusic(TalkSnippetId, TalkSnippet),
+    RemoveMusic(TalkSnippetId),
+}
+
+/// An open journal, which we append changes to.
+pub struct Journal {
+    doc_path: PathBuf,
+    // The contents of the document on disk.
+    base: SaveFileData,
+    // The data, as of the last thing that we wrote to the journal.
+    last: SaveFileData,
+    file: File,
+}
+
+/// Returns the path of the journal for the document at `doc_path`.
+pub fn journal_path(doc_path: &Path) -> PathBuf {
+    let mut name = doc_path
+        .file_name()
+        .map(|n| n.to_owned())
+        .unwrap_or_default();
+    name.push(".journal");
+    doc_path.with_file_name(name)
+}
+
+// Compares two collections of snippets, both of which must be sorted by id. Returns the snippets
+// that were added or changed (with `Some`) and the ids of the ones that were removed (with `None`).
+fn diff<'a, Id: Copy + Ord, S: Clone + Data + 'a>(
+    old: impl Iterator<Item = (Id, &'a S)>,
+    new: impl Iterator<Item = (Id, &'a S)>,
+) -> Vec<(Id, Option<S>)> {
+    let mut ret = Vec::new();
+    let mut old = old.peekable();
+    for (id, snip) in new {
+        while let Some((old_id, _)) = old.peek() {
+            if *old_id < id {
+                ret.push((*old_id, None));
+                old.next();
+            } else {
+                break;
+            }
+        }
+        match old.peek() {
+            Some((old_id, old_snip)) if *old_id == id => {
+                if !S::same(old_snip, snip) {
+                    ret.push((id, Some(snip.clone())));
+                }
+                old.next();
+            }
+            _ => ret.push((id, Some(snip.clone()))),
+        }
+    }
+    ret.extend(old.map(|(id, _)| (id, None)));
+    ret
+}
+
+fn changes(old: &SaveFileData, new: &SaveFileData) -> Vec<JournalEntry> {
+    let mut ret = Vec::new();
+    if !old.same_meta(new) {
+        ret.push(JournalEntry::Properties(new.meta()));
+    }
+    if !old.snippets.same(&new.snippets) {
+        ret.extend(
+            diff(old.snippets.snippets(), new.snippets.snippets())
+                .into_iter()
+                .map(|(id, snip)| match snip {
+                    Some(snip) => JournalEntry::PutDraw(id, snip),
+                    None => JournalEntry::RemoveDraw(id),
+                }),
+        );
+    }
+    if !old.audio_snippets.same(&new.audio_snippets) {
+        ret.extend(
+            diff(old.audio_snippets.snippets(), new.audio_snippets.snippets())
+                .into_iter()
+                .map(|(id, snip)| match snip {
+                    Some(snip) => JournalEntry::PutTalk(id, snip),
+                    None => JournalEntry::RemoveTalk(id),
+                }),
+        );
+    }
+    if !old.music_snippets.same(&new.music_snippets) {
+        ret.extend(
+            diff(old.music_snippets.snippets(), new.music_snippets.snippets())
+                .into_iter()
+                .map(|(id, snip)| match snip {
+                    Some(snip) => JournalEntry::PutMusic(id, snip),
+                    None => JournalEntry::RemoveMusic(id),
+                }),
+        );
+    }
+    ret
+}
+
+fn apply(data: &mut SaveFileData, entry: JournalEntry) {
+    match entry {
+        JournalEntry::PutDraw(id, snip) => data.snippets = data.snippets.with_snippet(id, snip),
+        JournalEntry::RemoveDraw(id) => data.snippets = data.snippets.without_snippet(id),
+        JournalEntry::PutTalk(id, snip) => {
+            data.audio_snippets = data.audio_snippets.with_snippet(id, snip)
+        }
+        JournalEntry::RemoveTalk(id) => {
+            data.audio_snippets = data.audio_snippets.without_snippet(id)
+        }
+        JournalEntry::Properties(meta) => data.set_meta(meta),
+        JournalEntry::PutMusic(id, snip) => {
+            data.music_snippets = data.music_snippets.with_snippet(id, snip)
+        }
+        JournalEntry::RemoveMusic(id) => {
+            data.music_snippets = data.music_snippets.without_snippet(id)
+        }
+    }
+}
+
+fn write_record<T: Serialize>(buf: &mut Vec<u8>, record: &T) -> anyhow::Result<()> {
+    let bytes = serde_cbor::to_vec(record)?;
+    let len = bytes.len() as u32;
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(&bytes);
+    Ok(())
+}
+
+// Returns `None` if we're at the end of the file.
+fn read_record<T: DeserializeOwned, R: Read>(read: &mut R) -> anyhow::Result<Option<T>> {
+    let mut len = [0; 4];
+    match read.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_le_bytes(len) as u64;
+    let mut buf = Vec::new();
+    read.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(anyhow!("incomplete record"));
+    }
+    Ok(Some(serde_cbor::from_slice(&buf)?))
+}
+
+impl Journal {
+    /// Starts a new (empty) journal for the document at `doc_path`, replacing any journal that
+    /// was already there. `base` is the content of the document on disk.
+    pub fn create(doc_path: &Path, base: SaveFileData) -> anyhow::Result<Journal> {
+        let path = journal_path(doc_path);
+        let mut buf = MAGIC.to_vec();
+        write_record(&mut buf, &Fingerprint::of(doc_path)?)?;
+
+        // Write the header to a temporary file first, so that we don't clobber the old journal
+        // unless we succeed in replacing it.
+        let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        std::fs::write(&tmp_path, &buf)?;
+        std::fs::rename(&tmp_path, &path)?;
+
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(Journal {
+            doc_path: doc_path.to_owned(),
+            last: base.clone(),
+            base,
+            file,
+        })
+    }
+
+    /// Does this journal belong to the document at `doc_path`, whose on-disk contents are `base`?
+    pub fn applies_to(&self, doc_path: &Path, base: &SaveFileData) -> bool {
+        self.doc_path == doc_path && self.base.same(base)
+    }
+
+    /// Appends to the journal everything that changed since the last call to `append`.
+    pub fn append(&mut self, data: &SaveFileData) -> anyhow::Result<()> {
+        let mut buf = Vec::new();
+        for entry in changes(&self.last, data) {
+            write_record(&mut buf, &entry)?;
+        }
+        if !buf.is_empty() {
+            self.file.write_all(&buf)?;
+            self.file.sync_data()?;
+        }
+        self.last = data.clone();
+        Ok(())
+    }
+}
+
+/// Deletes the journal of the document at `doc_path`, if there is one.
+pub fn remove_journal(doc_path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(journal_path(doc_path)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Replays the journal of the document at `doc_path`, whose contents are `data`.
+///
+/// Returns `None` if there is no journal that applies to this document.
+pub fn replay(doc_path: &Path, data: &SaveFileData) -> anyhow::Result<Option<SaveFileData>> {
+    let path = journal_path(doc_path);
+    let mut read = match File::open(&path) {
+        Ok(f) => BufReader::new(f),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut magic = [0; 8];
+    read.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(anyhow!("{} is not a journal file", path.display()));
+    }
+    let fingerprint: Fingerprint =
+        read_record(&mut read)?.ok_or_else(|| anyhow!("journal is missing its header"))?;
+    if fingerprint != Fingerprint::of(doc_path)? {
+        log::info!(
+            "ignoring {}, because the document was modified after it was written",
+            path.display()
+        );
+        return Ok(None);
+    }
+
+    let mut ret = data.clone();
+    let mut count = 0;
+    loop {
+        match read_record(&mut read) {
+            Ok(Some(entry)) => {
+                apply(&mut ret, entry);
+                count += 1;
+            }
+            Ok(None) => break,
+            Err(e) => {
+                log::warn!("ignoring the rest of {}: {}", path.display(), e);
+                break;
+            }
+        }
+    }
+    log::info!("replayed {} journal entries from {}", count, path.display());
+    Ok(if count > 0 { Some(ret) } else { None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scribl_curves::Time;
+    use std::io::Cursor;
+
+    fn sample() -> SaveFileData {
+        let data = &include_bytes!("../../sample/intro.scb")[..];
+        SaveFileData::load_from(Cursor::new(data)).unwrap()
+    }
+
+    fn check_same(a: &SaveFileData, b: &SaveFileData) {
+        let ids = |d: &SaveFileData| d.snippets.snippets().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids(a), ids(b));
+        assert!(a.audio_snippets == b.audio_snippets);
+        assert!(a.music_snippets == b.music_snippets);
+        assert!(a.same_meta(b));
+    }
+
+    fn edit(data: &SaveFileData) -> SaveFileData {
+        let mut ret = data.clone();
+        let (first_draw, _) = ret.snippets.snippets().next().unwrap();
+        let (first_talk, _) = ret.audio_snippets.snippets().next().unwrap();
+        ret.snippets = ret.snippets.without_snippet(first_draw);
+        ret.audio_snippets = ret
+            .audio_snippets
+            .with_new_snippet(TalkSnippet::new(vec![1, 2, 3], Time::ZERO, 1.0))
+            .0
+            .without_snippet(first_talk);
+        ret.music_snippets = ret
+            .music_snippets
+            .with_new_snippet(TalkSnippet::new(vec![4, 5, 6], Time::ZERO, 1.0))
+            .0;
+        ret.aspect_ratio = (16, 9);
+        ret
+    }
+
+    #[test]
+    fn changes_then_apply() {
+        let base = sample();
+        let edited = edit(&base);
+        let mut replayed = base.clone();
+        for entry in changes(&base, &edited) {
+            apply(&mut replayed, entry);
+        }
+        check_same(&replayed, &edited);
+        assert!(changes(&edited, &edited).is_empty());
+    }
+
+    #[test]
+    fn write_and_replay() {
+        let dir = std::env::temp_dir().join(format!("scribl-journal-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let doc_path = dir.join("doc.scb");
+        let base = sample();
+        base.save_to_path(&doc_path).unwrap();
+
+        let edited = edit(&base);
+        let mut journal = Journal::create(&doc_path, base.clone()).unwrap();
+        assert!(replay(&doc_path, &base).unwrap().is_none());
+        journal.append(&edited).unwrap();
+        check_same(&replay(&doc_path, &base).unwrap().unwrap(), &edited);
+
+        // A truncated record at the end doesn't prevent us from reading the rest.
+        let mut edited_again = edited.clone();
+        edited_again.aspect_ratio = (1, 1);
+        journal.append(&edited_again).unwrap();
+        let journal_file = journal_path(&doc_path);
+        let len = std::fs::metadata(&journal_file).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&journal_file)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+        check_same(&replay(&doc_path, &base).unwrap().unwrap(), &edited);
+
+        remove_journal(&doc_path).unwrap();
+        assert!(replay(&doc_path, &base).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use druid::im::Vector;
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+
+use scribl_curves::{Time, TimeDiff, TimeSpan};
+
+/// A named point on the timeline, for navigating around and for selecting ranges.
+#[derive(Clone, Data, Debug, Deserialize, Lens, PartialEq, Serialize)]
+pub struct Mark {
+    pub time: Time,
+    pub name: String,
+}
+
+/// A collection of marks, sorted by time. There is at most one mark at any given time.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Data, Debug, Default, Deserialize, Lens, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Marks {
+    /// The marks, sorted by time. It's ok to modify the names of the marks through this lens, but
+    /// the times should be modified only through the methods on `Marks`.
+    pub list: Vector<Mark>,
+}
+
+impl Marks {
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Mark> {
+        self.list.iter()
+    }
+
+    /// Returns the mark at exactly `time`, if there is one.
+    pub fn mark_at(&self, time: Time) -> Option<&Mark> {
+        self.list.iter().find(|m| m.time == time)
+    }
+
+    /// Returns the first mark strictly after `time`.
+    pub fn next_after(&self, time: Time) -> Option<&Mark> {
+        self.list.iter().find(|m| m.time > time)
+    }
+
+    /// Returns the last mark strictly before `time`.
+    pub fn prev_before(&self, time: Time) -> Option<&Mark> {
+        self.list.iter().take_while(|m| m.time < time).last()
+    }
+
+    /// Returns a new collection with an extra mark. If there is already a mark at `time`, it is
+    /// left alone.
+    pub fn with_mark(&self, time: Time, name: String) -> Marks {
+        let mut list = self.list.clone();
+        let idx = list
+            .iter()
+            .position(|m| m.time >= time)
+            .unwrap_or(list.len());
+        if list.get(idx).map_or(true, |m| m.time != time) {
+            list.insert(idx, Mark { time, name });
+        }
+        Marks { list }
+    }
+
+    /// Returns a new collection in which the time interval `[start, end)` has been deleted. Marks
+    /// after the interval move `end - start` earlier, and marks inside it move to `start` (but if
+    /// that would put several marks at the same time, we keep only the first).
+    pub fn with_deleted_range(&self, start: Time, end: Time) -> Marks {
+        let mut list = Vector::new();
+        for m in self.list.iter() {
+            let time = if m.time >= end {
+                m.time - (end - start)
+            } else {
+                m.time.min(start)
+            };
+            if list.last().map_or(true, |prev: &Mark| prev.time != time) {
+                list.push_back(Mark {
+                    time,
+                    name: m.name.clone(),
+                });
+            }
+        }
+        Marks { list }
+    }
+
+    /// Returns a new collection in which the time interval `[start, end]` has been rescaled to
+    /// `[start, new_end]`. Marks inside the interval move along with it, and marks after it move
+    /// by `new_end - end`.
+    pub fn with_scaled_range(&self, start: Time, end: Time, new_end: Time) -> Marks {
+        let span = TimeSpan::new(start, end);
+        let new_span = TimeSpan::new(start, new_end);
+        let mut list = Vector::new();
+        for m in self.list.iter() {
+            let time = if m.time >= end {
+                m.time + (new_end - end)
+            } else if m.time > start {
+                span.interpolate_to(m.time, new_span)
+            } else {
+                m.time
+            };
+            if list.last().map_or(true, |prev: &Mark| prev.time != time) {
+                list.push_back(Mark {
+                    time,
+                    name: m.name.clone(),
+                });
+            }
+        }
+        Marks { list }
+    }
+
+    /// Returns a new collection in which all the marks at or after `at` move `len` later.
+    pub fn with_inserted_gap(&self, at: Time, len: TimeDiff) -> Marks {
+        let mut ret = self.clone();
+        for m in ret.list.iter_mut().filter(|m| m.time >= at) {
+            m.time += len;
+        }
+        ret
+    }
+
+    /// Returns a new collection without the mark at exactly `time`.
+    pub fn without_mark_at(&self, time: Time) -> Marks {
+        Marks {
+            list: self
+                .list
+                .iter()
+                .filter(|m| m.time != time)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks() {
+        let t = Time::from_micros;
+        let marks = Marks::default()
+            .with_mark(t(200), "b".to_owned())
+            .with_mark(t(100), "a".to_owned())
+            .with_mark(t(200), "ignored".to_owned());
+        let names: Vec<_> = marks.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+
+        assert_eq!(marks.next_after(t(100)).unwrap().name, "b");
+        assert_eq!(marks.next_after(t(50)).unwrap().name, "a");
+        assert!(marks.next_after(t(200)).is_none());
+        assert_eq!(marks.prev_before(t(200)).unwrap().name, "a");
+        assert_eq!(marks.prev_before(t(300)).unwrap().name, "b");
+        assert!(marks.prev_before(t(100)).is_none());
+
+        let deleted = marks.with_deleted_range(t(50), t(150));
+        let times: Vec<_> = deleted.iter().map(|m| m.time).collect();
+        assert_eq!(times, vec![t(50), t(100)]);
+        let deleted = marks.with_deleted_range(t(100), t(250));
+        let names: Vec<_> = deleted.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["a"]);
+
+        let scaled = marks.with_scaled_range(t(50), t(250), t(150));
+        let times: Vec<_> = scaled.iter().map(|m| m.time).collect();
+        assert_eq!(times, vec![t(75), t(125)]);
+
+        let marks = marks.without_mark_at(t(100));
+        assert!(marks.mark_at(t(100)).is_none());
+        assert_eq!(marks.len(), 1);
+    }
+}
@@ -0,0 +1,234 @@
+use druid::im::Vector;
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use scribl_curves::{Time, TimeDiff, TimeSpan};
+
+/// Descriptive information about an animation. This doesn't affect how the animation looks, but it
+/// gets written into exported videos.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Data, Debug, Default, Deserialize, Lens, PartialEq, Serialize)]
+pub struct Metadata {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub description: String,
+    /// When the animation was created, in seconds since the Unix epoch.
+    #[serde(default)]
+    pub created: Option<i64>,
+    /// When the animation was last saved, in seconds since the Unix epoch. This is filled in by
+    /// `SaveFileData::from_editor_state`, and it doesn't count as a change to the animation.
+    #[serde(default)]
+    #[data(ignore)]
+    pub modified: Option<i64>,
+}
+
+/// A named point in the animation.
+#[derive(Clone, Data, Debug, Deserialize, Lens, PartialEq, Serialize)]
+pub struct Chapter {
+    pub time: Time,
+    pub title: String,
+}
+
+/// A collection of chapters, sorted by time. There is at most one chapter at any given time.
+#[derive(Clone, Data, Debug, Default, Deserialize, Lens, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Chapters {
+    /// The chapters, sorted by time. It's ok to modify the titles of the chapters through this
+    /// lens, but the times should be modified only through the methods on `Chapters`.
+    pub list: Vector<Chapter>,
+}
+
+/// Returns the current time, in seconds since the Unix epoch.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Formats a time (in seconds since the Unix epoch) as a UTC date and time, like
+/// "2021-03-04 05:06 UTC".
+pub fn format_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(86400);
+    let secs_of_day = secs.rem_euclid(86400);
+
+    // This is Howard Hinnant's algorithm for converting days since the epoch into a civil date.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        (secs_of_day % 3600) / 60
+    )
+}
+
+impl Chapters {
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Chapter> {
+        self.list.iter()
+    }
+
+    /// Returns the chapter that `time` belongs to (i.e. the last one starting at or before `time`).
+    pub fn chapter_at(&self, time: Time) -> Option<&Chapter> {
+        self.list.iter().take_while(|c| c.time <= time).last()
+    }
+
+    /// Returns a new collection with an extra chapter, replacing any chapter that starts at
+    /// exactly the same time.
+    pub fn with_chapter(&self, time: Time, title: String) -> Chapters {
+        let mut list = self.list.clone();
+        let idx = list
+            .iter()
+            .position(|c| c.time >= time)
+            .unwrap_or(list.len());
+        if list.get(idx).map_or(false, |c| c.time == time) {
+            list[idx].title = title;
+        } else {
+            list.insert(idx, Chapter { time, title });
+        }
+        Chapters { list }
+    }
+
+    /// Returns a new collection without the chapter that starts at exactly `time`.
+    pub fn without_chapter_at(&self, time: Time) -> Chapters {
+        Chapters {
+            list: self
+                .list
+                .iter()
+                .filter(|c| c.time != time)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Returns a new collection in which the time interval `[start, end)` has been deleted.
+    /// Chapters after the interval move `end - start` earlier, and chapters inside it move to
+    /// `start`. If that would put several chapters at the same time, we keep only the last one,
+    /// because the others no longer have anything in them.
+    pub fn with_deleted_range(&self, start: Time, end: Time) -> Chapters {
+        let mut list: Vector<Chapter> = Vector::new();
+        for c in self.list.iter() {
+            let time = if c.time >= end {
+                c.time - (end - start)
+            } else {
+                c.time.min(start)
+            };
+            if list.last().map_or(false, |prev| prev.time == time) {
+                list.pop_back();
+            }
+            list.push_back(Chapter {
+                time,
+                title: c.title.clone(),
+            });
+        }
+        Chapters { list }
+    }
+
+    /// Returns a new collection in which the time interval `[start, end]` has been rescaled to
+    /// `[start, new_end]`. Chapters starting inside the interval move along with it, and chapters
+    /// after it move by `new_end - end`.
+    pub fn with_scaled_range(&self, start: Time, end: Time, new_end: Time) -> Chapters {
+        let span = TimeSpan::new(start, end);
+        let new_span = TimeSpan::new(start, new_end);
+        let mut list: Vector<Chapter> = Vector::new();
+        for c in self.list.iter() {
+            let time = if c.time >= end {
+                c.time + (new_end - end)
+            } else if c.time > start {
+                span.interpolate_to(c.time, new_span)
+            } else {
+                c.time
+            };
+            if list.last().map_or(false, |prev| prev.time == time) {
+                list.pop_back();
+            }
+            list.push_back(Chapter {
+                time,
+                title: c.title.clone(),
+            });
+        }
+        Chapters { list }
+    }
+
+    /// Returns a new collection in which all the chapters starting at or after `at` move `len`
+    /// later.
+    pub fn with_inserted_gap(&self, at: Time, len: TimeDiff) -> Chapters {
+        let mut ret = self.clone();
+        for c in ret.list.iter_mut().filter(|c| c.time >= at) {
+            c.time += len;
+        }
+        ret
+    }
+
+    /// Iterates over the chapters, along with the time that each of them ends. Each chapter ends
+    /// when the next one starts, and the last one ends at `end`.
+    pub fn spans(&self, end: Time) -> impl Iterator<Item = (&Chapter, Time)> {
+        let ends = self
+            .list
+            .iter()
+            .skip(1)
+            .map(|c| c.time)
+            .chain(std::iter::once(end));
+        self.list.iter().zip(ends)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00 UTC");
+        assert_eq!(format_timestamp(1_614_834_360), "2021-03-04 05:06 UTC");
+    }
+
+    #[test]
+    fn chapters() {
+        let t = Time::from_micros;
+        let chapters = Chapters::default()
+            .with_chapter(t(200), "b".to_owned())
+            .with_chapter(t(100), "a".to_owned())
+            .with_chapter(t(300), "c".to_owned())
+            .with_chapter(t(200), "B".to_owned());
+        let titles: Vec<_> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, vec!["a", "B", "c"]);
+
+        let ends: Vec<_> = chapters.spans(t(1000)).map(|(_, end)| end).collect();
+        assert_eq!(ends, vec![t(200), t(300), t(1000)]);
+
+        assert!(chapters.chapter_at(t(50)).is_none());
+        assert_eq!(chapters.chapter_at(t(250)).unwrap().title, "B");
+        let deleted = chapters.with_deleted_range(t(150), t(250));
+        let titles: Vec<_> = deleted.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, vec!["a", "B", "c"]);
+        let deleted = chapters.with_deleted_range(t(200), t(350));
+        let titles: Vec<_> = deleted.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, vec!["a", "c"]);
+        assert_eq!(deleted.list[1].time, t(200));
+
+        let chapters = chapters.without_chapter_at(t(200));
+        assert_eq!(chapters.chapter_at(t(250)).unwrap().title, "a");
+        assert_eq!(chapters.list.len(), 2);
+    }
+}
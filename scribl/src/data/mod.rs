@@ -1,4 +1,7 @@
 pub mod editor;
+pub mod journal;
+pub mod marks;
+pub mod metadata;
 pub mod save;
 pub mod scribl;
 pub mod settings;
@@ -6,6 +9,8 @@ pub mod settings;
 pub use editor::{
     AsyncOpsStatus, AudioState, CurrentAction, EditorState, FinishedStatus, SnippetId,
 };
+pub use marks::{Mark, Marks};
+pub use metadata::{Chapter, Chapters, Metadata};
 pub use save::SaveFileData;
-pub use scribl::ScriblState;
+pub use scribl::{AspectRatio, ScriblState};
 pub use settings::{DenoiseSetting, PenSize, RecordingSpeed, Settings, MAX_ZOOM};
@@ -1,35 +1,258 @@
+//! Reading and writing save files.
+//!
+//! Starting from version 3, a save file is a container with a small binary header followed by
+//! several independent sections:
+//!
+//! - 8 bytes of magic (`MAGIC`);
+//! - the format version, as a little-endian `u32`;
+//! - the number of sections, as a little-endian `u32`;
+//! - for each section, a 4-byte tag followed by the section's offset and length (both
+//!   little-endian `u64`s, with the offset measured from the start of the file);
+//! - the contents of the sections.
+//!
+//! Each section is CBOR-encoded. Because the header tells us where each section lives, we can
+//! read the drawing without touching the (much larger) audio, and we can serialize the sections
+//! independently of one another. Readers ignore sections that they don't recognize, and some
+//! sections (like the background music) are optional.
+//!
+//! Versions 0 through 2 were just a single CBOR-encoded struct. We can still read them, and we
+//! tell them apart from the new format by the magic bytes (which are not a valid start of the old
+//! format).
+
 use anyhow::anyhow;
 use druid::Data;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
-use scribl_curves::DrawSnippets;
+use scribl_curves::{Background, CameraTrack, DrawSnippets};
 
-use crate::audio::TalkSnippets;
+use crate::audio::{Ducking, TalkSnippets};
+use crate::data::{Chapters, Marks, Metadata};
 use crate::EditorState;
 
+const CURRENT_VERSION: u8 = 3;
+
+/// The first bytes of every save file in the sectioned format. The first byte isn't valid ASCII
+/// (so that nobody mistakes us for a text file) and it also doesn't look like the beginning of
+/// the old, CBOR-only format.
+const MAGIC: &[u8; 8] = b"\x89SCRIBL\n";
+
+const META_SECTION: [u8; 4] = *b"META";
+const DRAW_SECTION: [u8; 4] = *b"DRAW";
+const TALK_SECTION: [u8; 4] = *b"TALK";
+const MUSIC_SECTION: [u8; 4] = *b"MUSC";
+
+/// The size of a single entry in the section table.
+const SECTION_ENTRY_LEN: u64 = 4 + 8 + 8;
+
 /// This is the data that we put into the saved files.
-// TODO: how disruptive would it be to move to bincode? It's apparently much faster than CBOR.
 #[derive(Clone, Data, Deserialize, Serialize)]
 pub struct SaveFileData {
     /// This is the version of the save file format. Every time we change the format, this gets
     /// incremented. We retain support for reading (but not writing) old versions.
     ///
-    /// The current version is 1.
+    /// The current version is 3. The difference between versions 1 and 3 is that version 3
+    /// compresses the audio and splits the file into sections (see the module documentation).
+    /// Version 2 (compressed audio, but no sections) never made it into a release.
     pub version: u8,
 
     pub snippets: DrawSnippets,
     pub audio_snippets: TalkSnippets,
 
-    /// The aspect ratio of the animation. Currently this is fixed at 4:3, but eventually we'll
-    /// want to support other values, so let's put it in the save file format.
+    /// The aspect ratio of the animation.
     pub aspect_ratio: (u32, u32),
     /// The "width" of the animation, in some arbitrary and meaningless unit. Currently this is
     /// fixed at 1.0 but eventually we may want to allow them to resize the canvas, so let's put it
     /// in the save file format.
     pub width: f64,
+
+    /// The background color and paper template. This was added in the middle of version 1, so
+    /// it's allowed to be missing.
+    #[serde(default)]
+    pub background: Background,
+
+    /// The camera track. This was also added after version 1 was released.
+    #[serde(default)]
+    pub camera: CameraTrack,
+
+    /// The title, author, etc. These were added in version 3.
+    #[serde(default)]
+    pub metadata: Metadata,
+
+    #[serde(default)]
+    pub chapters: Chapters,
+
+    /// The named marks on the timeline. These were added in version 3 (but a bit later than the
+    /// metadata).
+    #[serde(default)]
+    pub marks: Marks,
+
+    /// The background music, and the settings for ducking it under the speech. These were added in
+    /// version 3 (and live in their own section, which is allowed to be missing).
+    #[serde(default)]
+    pub music_snippets: TalkSnippets,
+    #[serde(default)]
+    pub ducking: Ducking,
+}
+
+/// The contents of the "META" section: everything except the drawing and the audio. This is
+/// small, so new document-wide settings should go here (with a `#[serde(default)]`, so that we
+/// can still read files that were written without them).
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct Meta {
+    aspect_ratio: (u32, u32),
+    width: f64,
+    #[serde(default)]
+    background: Background,
+    #[serde(default)]
+    camera: CameraTrack,
+    #[serde(default)]
+    metadata: Metadata,
+    #[serde(default)]
+    chapters: Chapters,
+    #[serde(default)]
+    marks: Marks,
+    #[serde(default)]
+    ducking: Ducking,
+}
+
+struct SectionEntry {
+    tag: [u8; 4],
+    offset: u64,
+    len: u64,
+}
+
+/// Reads a save file in the sectioned format, one section at a time.
+///
+/// Unlike [`SaveFileData::load_from`], this doesn't need to read the whole file into memory: it
+/// just reads the section table and then seeks to whatever sections are asked for.
+pub struct SaveFileReader<R> {
+    read: R,
+    sections: Vec<SectionEntry>,
+}
+
+fn read_u32<R: Read>(read: &mut R) -> std::io::Result<u32> {
+    let mut buf = [0; 4];
+    read.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(read: &mut R) -> std::io::Result<u64> {
+    let mut buf = [0; 8];
+    read.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+impl<R: Read + Seek> SaveFileReader<R> {
+    /// Reads the header of a save file. The reader should be positioned at the start of the file.
+    pub fn new(mut read: R) -> anyhow::Result<SaveFileReader<R>> {
+        let mut magic = [0; 8];
+        read.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(anyhow!("not a sectioned save file"));
+        }
+        SaveFileReader::after_magic(read)
+    }
+
+    // Reads the rest of the header, assuming that the magic bytes were already consumed.
+    fn after_magic(mut read: R) -> anyhow::Result<SaveFileReader<R>> {
+        let version = read_u32(&mut read)?;
+        log::info!("Found file format version {}", version);
+        if version != CURRENT_VERSION as u32 {
+            return Err(anyhow!("unsupported file format version: {}", version));
+        }
+
+        let file_len = read.seek(SeekFrom::End(0))?;
+        read.seek(SeekFrom::Start(MAGIC.len() as u64 + 4))?;
+        let count = read_u32(&mut read)? as u64;
+        // Check the count before allocating anything, in case the file is corrupted.
+        if count.saturating_mul(SECTION_ENTRY_LEN) > file_len {
+            return Err(anyhow!("file too short!"));
+        }
+
+        let mut sections = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let mut tag = [0; 4];
+            read.read_exact(&mut tag)?;
+            let offset = read_u64(&mut read)?;
+            let len = read_u64(&mut read)?;
+            if offset.checked_add(len).map_or(true, |end| end > file_len) {
+                return Err(anyhow!("section extends past the end of the file"));
+            }
+            sections.push(SectionEntry { tag, offset, len });
+        }
+        Ok(SaveFileReader { read, sections })
+    }
+
+    fn section<T: DeserializeOwned>(&mut self, tag: [u8; 4]) -> anyhow::Result<T> {
+        let entry = self
+            .sections
+            .iter()
+            .find(|s| s.tag == tag)
+            .ok_or_else(|| anyhow!("missing section {}", String::from_utf8_lossy(&tag)))?;
+        let (offset, len) = (entry.offset, entry.len);
+        self.read.seek(SeekFrom::Start(offset))?;
+        Ok(serde_cbor::from_reader((&mut self.read).take(len))?)
+    }
+
+    // Like `section`, but returns the default value if the section isn't there.
+    fn optional_section<T: DeserializeOwned + Default>(
+        &mut self,
+        tag: [u8; 4],
+    ) -> anyhow::Result<T> {
+        if self.sections.iter().any(|s| s.tag == tag) {
+            self.section(tag)
+        } else {
+            Ok(T::default())
+        }
+    }
+
+    /// Reads everything except for the audio (including the music), which is left empty. Use
+    /// [`read_audio`] to read the audio later.
+    ///
+    /// [`read_audio`]: SaveFileReader::read_audio
+    pub fn read_without_audio(&mut self) -> anyhow::Result<SaveFileData> {
+        let meta: Meta = self.section(META_SECTION)?;
+        let mut ret = SaveFileData {
+            version: CURRENT_VERSION,
+            snippets: self.section(DRAW_SECTION)?,
+            audio_snippets: TalkSnippets::default(),
+            aspect_ratio: (4, 3),
+            width: 1.0,
+            background: Background::default(),
+            camera: CameraTrack::default(),
+            metadata: Metadata::default(),
+            chapters: Chapters::default(),
+            marks: Marks::default(),
+            music_snippets: TalkSnippets::default(),
+            ducking: Ducking::default(),
+        };
+        ret.set_meta(meta);
+        Ok(ret)
+    }
+
+    /// Reads the audio.
+    pub fn read_audio(&mut self) -> anyhow::Result<TalkSnippets> {
+        self.section(TALK_SECTION)
+    }
+
+    /// Reads the whole file.
+    pub fn read_all(&mut self) -> anyhow::Result<SaveFileData> {
+        let mut ret = self.read_without_audio()?;
+        self.read_all_audio(&mut ret)?;
+        Ok(ret)
+    }
+
+    // Fills in the audio and the music of something that was returned from `read_without_audio`.
+    fn read_all_audio(&mut self, data: &mut SaveFileData) -> anyhow::Result<()> {
+        data.audio_snippets = self.read_audio()?;
+        data.music_snippets = self.optional_section(MUSIC_SECTION)?;
+        Ok(())
+    }
 }
 
 pub mod v0 {
@@ -37,17 +260,60 @@ pub mod v0 {
     pub struct SaveFileData {
         pub version: u8,
         pub snippets: scribl_curves::save::v0::DrawSnippets,
-        pub audio_snippets: crate::audio::TalkSnippets,
+        pub audio_snippets: crate::audio::v1::TalkSnippets,
     }
 
     impl From<SaveFileData> for super::SaveFileData {
         fn from(d: SaveFileData) -> super::SaveFileData {
             super::SaveFileData {
-                version: 1,
+                version: super::CURRENT_VERSION,
                 snippets: d.snippets.into(),
-                audio_snippets: d.audio_snippets,
+                audio_snippets: d.audio_snippets.into(),
                 aspect_ratio: (4, 3),
                 width: 1.0,
+                background: scribl_curves::Background::default(),
+                camera: scribl_curves::CameraTrack::default(),
+                metadata: crate::data::Metadata::default(),
+                chapters: crate::data::Chapters::default(),
+                marks: crate::data::Marks::default(),
+                music_snippets: crate::audio::TalkSnippets::default(),
+                ducking: crate::audio::Ducking::default(),
+            }
+        }
+    }
+}
+
+pub mod v1 {
+    use super::*;
+
+    #[derive(Deserialize)]
+    pub struct SaveFileData {
+        pub version: u8,
+        pub snippets: DrawSnippets,
+        pub audio_snippets: crate::audio::v1::TalkSnippets,
+        pub aspect_ratio: (u32, u32),
+        pub width: f64,
+        #[serde(default)]
+        pub background: Background,
+        #[serde(default)]
+        pub camera: CameraTrack,
+    }
+
+    impl From<SaveFileData> for super::SaveFileData {
+        fn from(d: SaveFileData) -> super::SaveFileData {
+            super::SaveFileData {
+                version: super::CURRENT_VERSION,
+                snippets: d.snippets,
+                audio_snippets: d.audio_snippets.into(),
+                aspect_ratio: d.aspect_ratio,
+                width: d.width,
+                background: d.background,
+                camera: d.camera,
+                metadata: Metadata::default(),
+                chapters: Chapters::default(),
+                marks: Marks::default(),
+                music_snippets: TalkSnippets::default(),
+                ducking: Ducking::default(),
             }
         }
     }
@@ -55,21 +321,114 @@ pub mod v0 {
 
 impl SaveFileData {
     pub fn from_editor_state(data: &EditorState) -> SaveFileData {
+        let mut metadata = data.scribl.metadata.clone();
+        metadata.modified = Some(crate::data::metadata::now());
         SaveFileData {
-            version: 1,
+            version: CURRENT_VERSION,
             snippets: data.scribl.draw.clone(),
             audio_snippets: data.scribl.talk.clone(),
-            aspect_ratio: (4, 3),
+            aspect_ratio: data.scribl.aspect_ratio.into(),
             width: 1.0,
+            background: data.scribl.background.clone(),
+            camera: data.scribl.camera.clone(),
+            metadata,
+            chapters: data.scribl.chapters.clone(),
+            marks: data.scribl.marks.clone(),
+            music_snippets: data.scribl.music.clone(),
+            ducking: data.scribl.ducking.clone(),
         }
     }
 
+    /// Everything except for the drawing and the audio.
+    pub(crate) fn meta(&self) -> Meta {
+        Meta {
+            aspect_ratio: self.aspect_ratio,
+            width: self.width,
+            background: self.background.clone(),
+            camera: self.camera.clone(),
+            metadata: self.metadata.clone(),
+            chapters: self.chapters.clone(),
+            marks: self.marks.clone(),
+            ducking: self.ducking.clone(),
+        }
+    }
+
+    pub(crate) fn set_meta(&mut self, meta: Meta) {
+        self.aspect_ratio = meta.aspect_ratio;
+        self.width = meta.width;
+        self.background = meta.background;
+        self.camera = meta.camera;
+        self.metadata = meta.metadata;
+        self.chapters = meta.chapters;
+        self.marks = meta.marks;
+        self.ducking = meta.ducking;
+    }
+
+    /// Returns true if everything except for the drawing and the audio is the same.
+    pub(crate) fn same_meta(&self, other: &SaveFileData) -> bool {
+        self.aspect_ratio == other.aspect_ratio
+            && self.width == other.width
+            && self.background.same(&other.background)
+            && self.camera.same(&other.camera)
+            && self.metadata.same(&other.metadata)
+            && self.chapters.same(&other.chapters)
+            && self.marks.same(&other.marks)
+            && self.ducking.same(&other.ducking)
+    }
+
     pub fn load_from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<SaveFileData> {
         let file = BufReader::new(File::open(path.as_ref())?);
         SaveFileData::load_from(file)
     }
 
-    pub fn load_from<R: std::io::Read>(mut read: R) -> anyhow::Result<SaveFileData> {
+    /// Like [`load_from_path`], but for files in the sectioned format, `preview` gets called with
+    /// everything except for the audio before we start reading the audio. Since the audio is
+    /// by far the biggest part of the file, this allows the drawing to be shown quickly.
+    ///
+    /// [`load_from_path`]: SaveFileData::load_from_path
+    pub fn load_from_path_with_preview<P: AsRef<Path>>(
+        path: P,
+        preview: impl FnOnce(SaveFileData),
+    ) -> anyhow::Result<SaveFileData> {
+        let file = BufReader::new(File::open(path.as_ref())?);
+        SaveFileData::load_from_with_preview(file, preview)
+    }
+
+    /// Loads everything but the audio from a save file. For files in the sectioned format, this
+    /// doesn't even read the audio from disk.
+    pub fn load_without_audio_from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<SaveFileData> {
+        let mut file = BufReader::new(File::open(path.as_ref())?);
+        let mut magic = [0; 8];
+        if file.read_exact(&mut magic).is_ok() && &magic == MAGIC {
+            SaveFileReader::after_magic(file)?.read_without_audio()
+        } else {
+            file.seek(SeekFrom::Start(0))?;
+            let mut ret = SaveFileData::load_from(file)?;
+            ret.audio_snippets = TalkSnippets::default();
+            ret.music_snippets = TalkSnippets::default();
+            Ok(ret)
+        }
+    }
+
+    pub fn load_from<R: Read + Seek>(read: R) -> anyhow::Result<SaveFileData> {
+        SaveFileData::load_from_with_preview(read, |_| {})
+    }
+
+    fn load_from_with_preview<R: Read + Seek>(
+        mut read: R,
+        preview: impl FnOnce(SaveFileData),
+    ) -> anyhow::Result<SaveFileData> {
+        let mut magic = [0; 8];
+        if read.read_exact(&mut magic).is_ok() && &magic == MAGIC {
+            let mut reader = SaveFileReader::after_magic(read)?;
+            let mut ret = reader.read_without_audio()?;
+            preview(ret.clone());
+            reader.read_all_audio(&mut ret)?;
+            return Ok(ret);
+        }
+
+        // This is one of the old formats, which have to be read all at once.
+        read.seek(SeekFrom::Start(0))?;
         let mut buf = Vec::new();
         read.read_to_end(&mut buf)?;
         // The version number is at byte 9 (the first two bytes are some CBOR tags, followed by the
@@ -85,7 +444,12 @@ impl SaveFileData {
                 let data: v0::SaveFileData = serde_cbor::from_slice(&buf[..])?;
                 Ok(data.into())
             }
-            1 => Ok(serde_cbor::from_slice(&buf[..])?),
+            1 => {
+                let data: v1::SaveFileData = serde_cbor::from_slice(&buf[..])?;
+                Ok(data.into())
+            }
+            // Version 2 was replaced by version 3 before it was ever released, so there are no
+            // version 2 files to read.
             n => Err(anyhow!("unsupported file format version: {}", n)),
         }
     }
@@ -111,8 +475,44 @@ impl SaveFileData {
         Ok(())
     }
 
-    pub fn save_to<W: std::io::Write>(&self, write: W) -> anyhow::Result<()> {
-        serde_cbor::to_writer(write, self)?;
+    pub fn save_to<W: Write>(&self, mut write: W) -> anyhow::Result<()> {
+        // Compressing the audio is the slowest part of saving, so we do it on a separate thread
+        // while the drawing gets serialized. (Cloning the audio is cheap, because the buffers are
+        // reference-counted.)
+        let audio = self.audio_snippets.clone();
+        let music = self.music_snippets.clone();
+        let talk_thread = std::thread::spawn(move || -> serde_cbor::Result<_> {
+            Ok((serde_cbor::to_vec(&audio)?, serde_cbor::to_vec(&music)?))
+        });
+
+        let meta = serde_cbor::to_vec(&self.meta())?;
+        let draw = serde_cbor::to_vec(&self.snippets)?;
+        let (talk, music) = talk_thread
+            .join()
+            .map_err(|_| anyhow!("audio serialization panicked"))??;
+
+        let sections = [
+            (META_SECTION, &meta[..]),
+            (DRAW_SECTION, &draw[..]),
+            (TALK_SECTION, &talk[..]),
+            (MUSIC_SECTION, &music[..]),
+        ];
+        let mut offset = MAGIC.len() as u64 + 4 + 4 + SECTION_ENTRY_LEN * sections.len() as u64;
+
+        write.write_all(MAGIC)?;
+        write.write_all(&(CURRENT_VERSION as u32).to_le_bytes())?;
+        let count: u32 = sections.len().try_into()?;
+        write.write_all(&count.to_le_bytes())?;
+        for (tag, data) in &sections {
+            write.write_all(tag)?;
+            write.write_all(&offset.to_le_bytes())?;
+            write.write_all(&(data.len() as u64).to_le_bytes())?;
+            offset += data.len() as u64;
+        }
+        for (_, data) in &sections {
+            write.write_all(data)?;
+        }
+        write.flush()?;
         Ok(())
     }
 }
@@ -120,10 +520,11 @@ impl SaveFileData {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
 
     fn check_round_trip(data: &[u8]) {
         // Check that we can read our sample file.
-        let save_data = SaveFileData::load_from(&data[..]).unwrap();
+        let save_data = SaveFileData::load_from(Cursor::new(data)).unwrap();
 
         let mut written = Vec::new();
         save_data.save_to(&mut written).unwrap();
@@ -132,13 +533,18 @@ mod tests {
         // fragile (e.g., compression settings could change). We also don't check
         // that load -> save is the identity (for now), because implementing
         // PartialEq is a pain.
-        let read_again = SaveFileData::load_from(&written[..]).unwrap();
+        let read_again = SaveFileData::load_from(Cursor::new(&written)).unwrap();
 
         // We do check that if something was written using the current version
         // of scribl, then save -> load is the identity.
         let mut written_again = Vec::new();
         read_again.save_to(&mut written_again).unwrap();
         assert_eq!(written, written_again);
+
+        // The audio compression is lossless.
+        assert_eq!(read_again.version, CURRENT_VERSION);
+        assert!(save_data.audio_snippets == read_again.audio_snippets);
+        assert!(save_data.music_snippets == read_again.music_snippets);
     }
 
     #[test]
@@ -146,8 +552,87 @@ mod tests {
         check_round_trip(&include_bytes!("../../sample/intro.scb")[..]);
     }
 
+    #[test]
+    fn save_compresses_audio() {
+        let orig = &include_bytes!("../../sample/intro.scb")[..];
+        let save_data = SaveFileData::load_from(Cursor::new(orig)).unwrap();
+        let mut written = Vec::new();
+        save_data.save_to(&mut written).unwrap();
+        assert_eq!(&written[..8], MAGIC);
+        assert_eq!(written[8], CURRENT_VERSION);
+        assert!(written.len() < orig.len());
+    }
+
     #[test]
     fn save_load_v0() {
         check_round_trip(&include_bytes!("../../sample/intro_v0.scb")[..]);
     }
+
+    #[test]
+    fn lazy_audio() {
+        let orig = &include_bytes!("../../sample/intro.scb")[..];
+        let save_data = SaveFileData::load_from(Cursor::new(orig)).unwrap();
+        let mut written = Vec::new();
+        save_data.save_to(&mut written).unwrap();
+
+        let mut reader = SaveFileReader::new(Cursor::new(&written)).unwrap();
+        let no_audio = reader.read_without_audio().unwrap();
+        assert!(no_audio.audio_snippets.snippets().next().is_none());
+        assert_eq!(no_audio.aspect_ratio, save_data.aspect_ratio);
+        assert!(reader.read_audio().unwrap() == save_data.audio_snippets);
+    }
+
+    #[test]
+    fn preview() {
+        let orig = &include_bytes!("../../sample/intro.scb")[..];
+        let save_data = SaveFileData::load_from(Cursor::new(orig)).unwrap();
+        let mut written = Vec::new();
+        save_data.save_to(&mut written).unwrap();
+
+        let mut previewed = None;
+        let loaded =
+            SaveFileData::load_from_with_preview(Cursor::new(&written), |d| previewed = Some(d))
+                .unwrap();
+        let previewed = previewed.unwrap();
+        assert!(previewed.audio_snippets.snippets().next().is_none());
+        assert_eq!(previewed.aspect_ratio, save_data.aspect_ratio);
+        assert!(loaded.audio_snippets == save_data.audio_snippets);
+
+        // The old formats don't get previewed.
+        let mut previewed = false;
+        SaveFileData::load_from_with_preview(Cursor::new(orig), |_| previewed = true).unwrap();
+        assert!(!previewed);
+    }
+
+    #[test]
+    fn music() {
+        let orig = &include_bytes!("../../sample/intro.scb")[..];
+        let mut save_data = SaveFileData::load_from(Cursor::new(orig)).unwrap();
+        // The sample file doesn't have a music section, so there should be no music.
+        assert!(save_data.music_snippets.snippets().next().is_none());
+
+        save_data.music_snippets = save_data.audio_snippets.clone();
+        save_data.ducking.depth = -6.0;
+        let mut written = Vec::new();
+        save_data.save_to(&mut written).unwrap();
+        let read_again = SaveFileData::load_from(Cursor::new(&written)).unwrap();
+        assert!(read_again.music_snippets == save_data.music_snippets);
+        assert_eq!(read_again.ducking, save_data.ducking);
+
+        let mut reader = SaveFileReader::new(Cursor::new(&written)).unwrap();
+        let no_audio = reader.read_without_audio().unwrap();
+        assert!(no_audio.music_snippets.snippets().next().is_none());
+    }
+
+    #[test]
+    fn truncated() {
+        let orig = &include_bytes!("../../sample/intro.scb")[..];
+        let save_data = SaveFileData::load_from(Cursor::new(orig)).unwrap();
+        let mut written = Vec::new();
+        save_data.save_to(&mut written).unwrap();
+
+        for &len in &[4, 12, 30, written.len() - 1] {
+            assert!(SaveFileData::load_from(Cursor::new(&written[..len])).is_err());
+        }
+    }
 }
@@ -1,27 +1,122 @@
 use druid::{Data, Lens};
-use scribl_curves::{DrawSnippet, DrawSnippetId, DrawSnippets};
+use scribl_curves::{
+    Background, CameraTrack, DrawSnippet, DrawSnippetId, DrawSnippets, Time, TimeDiff,
+};
 
-use crate::audio::{TalkSnippet, TalkSnippetId, TalkSnippets};
+use crate::audio::{Ducking, TalkSnippet, TalkSnippetId, TalkSnippets};
+use crate::data::editor::AudioLane;
+use crate::data::{Chapters, Marks, Metadata};
 use crate::undo::UndoState;
 use crate::SaveFileData;
 
+/// The aspect ratio (width to height) of the drawing area.
+///
+/// The drawing coordinates are chosen so that the width of the image is always 1.0, so this
+/// determines the height of the image.
+#[derive(Clone, Copy, Data, Debug, Eq, PartialEq)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
 /// This data contains the state of the current scribl. That means, just the parts that get saved
 /// if we save the file.
 #[derive(Clone, Data, Default, Lens)]
 pub struct ScriblState {
     pub draw: DrawSnippets,
     pub talk: TalkSnippets,
+    /// The background music, which gets mixed in below `talk`.
+    pub music: TalkSnippets,
+    pub ducking: Ducking,
+    pub aspect_ratio: AspectRatio,
+    pub background: Background,
+    pub camera: CameraTrack,
+    pub metadata: Metadata,
+    pub chapters: Chapters,
+    pub marks: Marks,
+}
+
+impl AspectRatio {
+    pub const FOUR_BY_THREE: AspectRatio = AspectRatio::new(4, 3);
+    pub const SIXTEEN_BY_NINE: AspectRatio = AspectRatio::new(16, 9);
+    pub const SIXTEEN_BY_TEN: AspectRatio = AspectRatio::new(16, 10);
+    pub const SQUARE: AspectRatio = AspectRatio::new(1, 1);
+
+    /// The aspect ratios that we offer in the menus.
+    pub const STANDARD: [AspectRatio; 4] = [
+        AspectRatio::FOUR_BY_THREE,
+        AspectRatio::SIXTEEN_BY_NINE,
+        AspectRatio::SIXTEEN_BY_TEN,
+        AspectRatio::SQUARE,
+    ];
+
+    pub const fn new(width: u32, height: u32) -> AspectRatio {
+        AspectRatio { width, height }
+    }
+
+    /// The ratio of the width to the height.
+    pub fn ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+}
+
+impl Default for AspectRatio {
+    fn default() -> AspectRatio {
+        AspectRatio::FOUR_BY_THREE
+    }
+}
+
+impl std::fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.width, self.height)
+    }
+}
+
+impl From<(u32, u32)> for AspectRatio {
+    fn from((width, height): (u32, u32)) -> AspectRatio {
+        if width == 0 || height == 0 {
+            log::warn!("invalid aspect ratio {}:{}, using 4:3", width, height);
+            AspectRatio::default()
+        } else {
+            AspectRatio { width, height }
+        }
+    }
+}
+
+impl From<AspectRatio> for (u32, u32) {
+    fn from(ar: AspectRatio) -> (u32, u32) {
+        (ar.width, ar.height)
+    }
 }
 
 impl ScriblState {
     pub fn new(draw: DrawSnippets, talk: TalkSnippets) -> ScriblState {
-        ScriblState { draw, talk }
+        ScriblState {
+            draw,
+            talk,
+            music: TalkSnippets::default(),
+            ducking: Ducking::default(),
+            aspect_ratio: AspectRatio::default(),
+            background: Background::default(),
+            camera: CameraTrack::default(),
+            metadata: Metadata::default(),
+            chapters: Chapters::default(),
+            marks: Marks::default(),
+        }
     }
 
     pub fn from_save_file(data: &SaveFileData) -> ScriblState {
         ScriblState {
             draw: data.snippets.clone(),
             talk: data.audio_snippets.clone(),
+            music: data.music_snippets.clone(),
+            ducking: data.ducking.clone(),
+            aspect_ratio: data.aspect_ratio.into(),
+            background: data.background.clone(),
+            camera: data.camera.clone(),
+            metadata: data.metadata.clone(),
+            chapters: data.chapters.clone(),
+            marks: data.marks.clone(),
         }
     }
 
@@ -37,6 +132,12 @@ impl ScriblState {
         new_id
     }
 
+    pub fn add_music_snippet(&mut self, snip: TalkSnippet) -> TalkSnippetId {
+        let (new_snippets, new_id) = self.music.with_new_snippet(snip);
+        self.music = new_snippets;
+        new_id
+    }
+
     pub fn delete_draw_snippet(&mut self, id: DrawSnippetId) {
         self.draw = self.draw.without_snippet(id);
     }
@@ -45,8 +146,96 @@ impl ScriblState {
         self.talk = self.talk.without_snippet(id);
     }
 
+    pub fn delete_music_snippet(&mut self, id: TalkSnippetId) {
+        self.music = self.music.without_snippet(id);
+    }
+
+    /// The audio snippets in the given lane.
+    pub fn audio(&self, lane: AudioLane) -> &TalkSnippets {
+        match lane {
+            AudioLane::Talk => &self.talk,
+            AudioLane::Music => &self.music,
+        }
+    }
+
+    pub fn audio_mut(&mut self, lane: AudioLane) -> &mut TalkSnippets {
+        match lane {
+            AudioLane::Talk => &mut self.talk,
+            AudioLane::Music => &mut self.music,
+        }
+    }
+
+    /// The time at which the animation ends: the last time that anything gets drawn, spoken or
+    /// played.
+    pub fn end_time(&self) -> Time {
+        self.draw
+            .last_draw_time()
+            .max(self.talk.end_time())
+            .max(self.music.end_time())
+    }
+
+    /// Deletes the time interval `[start, end)` from everything (drawings, speech, camera, etc.),
+    /// moving everything after it earlier to fill the hole.
+    pub fn delete_range(&mut self, start: Time, end: Time) {
+        self.draw = self.draw.with_deleted_range(start, end);
+        self.talk = self.talk.with_deleted_range(start, end);
+        self.music = self.music.with_deleted_range(start, end);
+        self.camera = self
+            .camera
+            .without_keyframes_in(start, end)
+            .shifted_after(end, start - end);
+        self.marks = self.marks.with_deleted_range(start, end);
+        self.chapters = self.chapters.with_deleted_range(start, end);
+    }
+
+    /// Shortens the time interval `[start, end]` to length `new_len`, moving everything after it
+    /// earlier.
+    ///
+    /// This is meant for squeezing out pauses in the speech, so the audio just loses the middle
+    /// of the interval. Everything else (drawings, camera, etc.) gets sped up instead, so that
+    /// nothing drawn during the interval gets lost. No snippets get deleted.
+    pub fn shorten_range(&mut self, start: Time, end: Time, new_len: TimeDiff) {
+        let new_end = start + new_len;
+        let half_len = TimeDiff::from_micros(new_len.as_micros() / 2);
+        let cut_start = start + half_len;
+        let cut_end = end - (new_len - half_len);
+        self.draw = self.draw.with_scaled_range(start, end, new_end);
+        self.talk = self.talk.with_squeezed_range(cut_start, cut_end);
+        self.music = self.music.with_squeezed_range(cut_start, cut_end);
+        self.camera = self.camera.with_scaled_range(start, end, new_end);
+        self.marks = self.marks.with_scaled_range(start, end, new_end);
+        self.chapters = self.chapters.with_scaled_range(start, end, new_end);
+    }
+
+    /// Inserts a gap of length `len` at time `at` into everything (drawings, speech, camera,
+    /// etc.), moving everything after it later.
+    pub fn insert_gap(&mut self, at: Time, len: TimeDiff) {
+        self.draw = self.draw.with_inserted_gap(at, len);
+        self.talk = self.talk.with_inserted_gap(at, len);
+        self.music = self.music.with_inserted_gap(at, len);
+        if !self.camera.is_empty() {
+            // The camera stays still during the gap.
+            let viewport = self.camera.viewport_at(at);
+            self.camera = self
+                .camera
+                .shifted_after(at, len)
+                .with_keyframe(at, viewport)
+                .with_keyframe(at + len, viewport);
+        }
+        self.marks = self.marks.with_inserted_gap(at, len);
+        self.chapters = self.chapters.with_inserted_gap(at, len);
+    }
+
     pub fn restore_undo_state(&mut self, undo: &UndoState) {
         self.draw = undo.snippets.clone();
         self.talk = undo.audio_snippets.clone();
+        self.music = undo.music_snippets.clone();
+        self.ducking = undo.ducking.clone();
+        self.aspect_ratio = undo.aspect_ratio;
+        self.background = undo.background.clone();
+        self.camera = undo.camera.clone();
+        self.metadata = undo.metadata.clone();
+        self.chapters = undo.chapters.clone();
+        self.marks = undo.marks.clone();
     }
 }
@@ -32,7 +32,23 @@ pub struct Settings {
     /// The current denoise setting, as selected in the UI.
     pub denoise_setting: DenoiseSetting,
 
+    /// When true, panning and zooming the drawing pane moves the camera (and records a camera
+    /// key-frame at the current time) instead of just changing the view in the editor.
+    pub camera_recording: bool,
+
     pub palette: crate::widgets::PaletteData,
+
+    /// The name of the audio device to record from, or `None` for the default one.
+    pub input_device: Option<String>,
+
+    /// The name of the audio device to play to, or `None` for the default one.
+    pub output_device: Option<String>,
+
+    /// "Shorten pauses" looks for pauses in the speech that are longer than this many seconds...
+    pub pause_threshold: f64,
+
+    /// ...and shortens them to this many seconds.
+    pub pause_target: f64,
 }
 
 impl Settings {
@@ -52,7 +68,12 @@ impl Settings {
             fade_enabled: false,
             shape_detect: true,
             pen_size: PenSize::Medium,
+            camera_recording: false,
             palette: crate::widgets::PaletteData::default(),
+            input_device: config.audio_input.device.clone(),
+            output_device: config.audio_output.device.clone(),
+            pause_threshold: 1.5,
+            pause_target: 0.5,
         }
     }
 
@@ -2,7 +2,7 @@ use anyhow::{anyhow, Error};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use druid::kurbo::TranslateScale;
 use druid::piet::{Device, ImageFormat};
-use druid::{Color, Data, Rect, RenderContext};
+use druid::{Data, Rect, RenderContext};
 use gst::prelude::*;
 use gst_video::{VideoFormat, VideoInfo};
 use gstreamer as gst;
@@ -11,13 +11,10 @@ use gstreamer_video as gst_video;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use scribl_curves::{DrawSnippets, Time, TimeDiff};
+use scribl_curves::{Time, TimeDiff, Viewport};
 
-use crate::audio::TalkSnippets;
-
-// Note that the aspect ratio here needs to match the aspect ratio
-// of the drawing, which is currently fixed at 4:3 in widgets/drawing_pane.rs.
-const ASPECT_RATIO: f64 = 4.0 / 3.0;
+use crate::data::{Chapters, Metadata};
+use crate::{AspectRatio, ScriblState};
 
 // We make a custom error here because the default display for gst::message::Error isn't very
 // helpful in narrowing down the problem.
@@ -56,37 +53,172 @@ fn make_elt(ty: &str, name: &str) -> Result<gst::Element, ElementCreationError>
     })
 }
 
+// Most encoders want the dimensions to be even.
+fn frame_width(height: u32, aspect_ratio: AspectRatio) -> u32 {
+    (height as f64 * aspect_ratio.ratio() / 2.0).round() as u32 * 2
+}
+
+// The kind of file that we export, which we choose based on the file extension.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Container {
+    Mp4,
+    WebM,
+    // The rest of these are audio-only.
+    Wav,
+    Flac,
+    Opus,
+}
+
+impl Container {
+    fn from_path(path: &Path) -> Container {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("webm") => Container::WebM,
+            Some("wav") => Container::Wav,
+            Some("flac") => Container::Flac,
+            Some("opus") | Some("ogg") => Container::Opus,
+            _ => Container::Mp4,
+        }
+    }
+
+    fn is_audio_only(self) -> bool {
+        match self {
+            Container::Mp4 | Container::WebM => false,
+            Container::Wav | Container::Flac | Container::Opus => true,
+        }
+    }
+}
+
+// Writes the animation's title, author, etc. into the muxer's tags.
+fn set_metadata(mux: &gst::Element, metadata: &Metadata) {
+    let setter = if let Some(setter) = mux.dynamic_cast_ref::<gst::TagSetter>() {
+        setter
+    } else {
+        log::warn!("this file format doesn't support metadata");
+        return;
+    };
+
+    let mut tags = gst::TagList::new();
+    {
+        let tags = tags.get_mut().unwrap();
+        let mode = gst::TagMergeMode::Replace;
+        if !metadata.title.is_empty() {
+            tags.add::<gst::tags::Title>(&metadata.title.as_str(), mode);
+        }
+        if !metadata.author.is_empty() {
+            tags.add::<gst::tags::Artist>(&metadata.author.as_str(), mode);
+        }
+        if !metadata.description.is_empty() {
+            tags.add::<gst::tags::Description>(&metadata.description.as_str(), mode);
+        }
+        if let Some(created) = metadata.created {
+            tags.add::<gst::tags::DateTime>(&gst::DateTime::new_from_unix_epoch_utc(created), mode);
+        }
+    }
+    setter.merge_tags(&tags, gst::TagMergeMode::Replace);
+}
+
+// Writes the animation's chapters into the muxer's table of contents. The video covers the part
+// of the animation between `start` and `end`; chapters outside of that are skipped, and the rest
+// are shifted so that `start` is at the beginning of the video.
+fn set_chapters(mux: &gst::Element, chapters: &Chapters, start: Time, end: Time) {
+    if chapters.is_empty() {
+        return;
+    }
+    let setter = if let Some(setter) = mux.dynamic_cast_ref::<gst::TocSetter>() {
+        setter
+    } else {
+        log::warn!("this file format doesn't support chapters; try exporting to webm");
+        return;
+    };
+
+    let mut edition = gst::TocEntry::new(gst::TocEntryType::Edition, "edition");
+    for (idx, (chapter, chapter_end)) in chapters.spans(end).enumerate() {
+        if chapter.time >= end {
+            break;
+        }
+        if chapter_end <= start {
+            continue;
+        }
+        let mut entry = gst::TocEntry::new(gst::TocEntryType::Chapter, &format!("chapter{}", idx));
+        {
+            let entry = entry.get_mut().unwrap();
+            entry.set_start_stop_times(
+                (chapter.time.max(start) - start).as_micros() * 1000,
+                (chapter_end.min(end) - start).as_micros() * 1000,
+            );
+            let mut tags = gst::TagList::new();
+            tags.get_mut()
+                .unwrap()
+                .add::<gst::tags::Title>(&chapter.title.as_str(), gst::TagMergeMode::Replace);
+            entry.set_tags(tags);
+        }
+        edition.get_mut().unwrap().append_sub_entry(entry);
+    }
+
+    let mut toc = gst::Toc::new(gst::TocScope::Global);
+    toc.get_mut().unwrap().append_entry(edition);
+    setter.set_toc(Some(&toc));
+}
+
 fn create_pipeline(
-    anim: DrawSnippets,
-    audio: TalkSnippets,
+    scribl: ScriblState,
+    start: Time,
     frame_count: u32,
     path: &Path,
     config: crate::config::Export,
     progress: Sender<EncodingStatus>,
 ) -> Result<gst::Pipeline, anyhow::Error> {
+    let container = Container::from_path(path);
     let pipeline = gst::Pipeline::new(None);
     let v_src = make_elt("appsrc", "encode-vsource")?;
     let v_convert = make_elt("videoconvert", "encode-vconvert")?;
-    let v_encode = make_elt("x264enc", "encode-vencode")?;
+    let v_encode = match container {
+        Container::WebM => make_elt("vp8enc", "encode-vencode")?,
+        _ => make_elt("x264enc", "encode-vencode")?,
+    };
     let v_queue1 = make_elt("queue", "encode-vqueue1")?;
     let v_queue2 = make_elt("queue", "encode-vqueue2")?;
-    let audio_output_data = crate::audio::OutputData {
-        start_time: Time::ZERO,
-        snips: audio,
-        velocity: 1.0,
+    let (fps_frac, fps) = if let Some(f) = gst::Fraction::approximate_f64(config.fps) {
+        (f, config.fps)
+    } else {
+        log::warn!("invalid fps value {}, defaulting to 30.0", config.fps);
+        (gst::Fraction::new(30, 1), 30.0)
     };
+    let end = start + (Time::from_video_frame(frame_count, fps) - Time::ZERO);
+    let audio_output_data = export_audio_data(&scribl, start, end, &config)?;
     let (output_tx, output_rx) = unbounded();
     // The unwrap is ok because we know that the receiver is still alive.
     output_tx.send(audio_output_data).unwrap();
     let a_src = crate::audio::create_appsrc(output_rx, "encode-asrc")?;
+    // The audio timestamps are relative to the beginning of the animation, but the video's are
+    // relative to `start`.
+    if let Some(pad) = a_src.get_static_pad("src") {
+        pad.set_offset(-(start - Time::ZERO).as_micros() * 1000);
+    }
     let a_convert = make_elt("audioconvert", "encode-aconvert")?;
-    let a_encode = make_elt("lamemp3enc", "encode-aencode")?;
+    let a_encode = match container {
+        Container::WebM => make_elt("opusenc", "encode-aencode")?,
+        _ => make_elt("lamemp3enc", "encode-aencode")?,
+    };
     let a_queue1 = make_elt("queue", "encode-aqueue1")?;
     let a_queue2 = make_elt("queue", "encode-aqueue2")?;
-    let mux = make_elt("mp4mux", "encode-mux")?;
+    let mux = match container {
+        Container::WebM => make_elt("webmmux", "encode-mux")?,
+        _ => make_elt("mp4mux", "encode-mux")?,
+    };
     let sink = make_elt("filesink", "encode-sink")?;
 
-    v_encode.set_property("bitrate", &config.bitrate)?;
+    match container {
+        // x264enc wants kbit/s, but vp8enc wants bit/s.
+        Container::WebM => {
+            v_encode.set_property("target-bitrate", &(config.bitrate as i32 * 1000))?
+        }
+        _ => v_encode.set_property("bitrate", &config.bitrate)?,
+    }
 
     pipeline.add_many(&[&v_src, &v_convert, &v_encode, &v_queue1, &v_queue2])?;
     pipeline.add_many(&[&a_src, &a_convert, &a_encode, &a_queue1, &a_queue2])?;
@@ -105,17 +237,14 @@ fn create_pipeline(
     )?;
 
     let height = config.height;
-    let width = (height as f64 * ASPECT_RATIO).round() as u32;
-    let (fps_frac, fps) = if let Some(f) = gst::Fraction::approximate_f64(config.fps) {
-        (f, config.fps)
-    } else {
-        log::warn!("invalid fps value {}, defaulting to 30.0", config.fps);
-        (gst::Fraction::new(30, 1), 30.0)
-    };
+    let width = frame_width(height, scribl.aspect_ratio);
     let video_info = VideoInfo::builder(VideoFormat::Rgba, width, height)
         .fps(fps_frac)
         .build()?;
 
+    set_metadata(&mux, &scribl.metadata);
+    set_chapters(&mux, &scribl.chapters, start, end);
+
     let v_src = v_src
         .dynamic_cast::<gst_app::AppSrc>()
         .map_err(|_| anyhow!("bug: couldn't cast v_src to an AppSrc"))?;
@@ -137,10 +266,11 @@ fn create_pipeline(
             rx,
             progress,
             v_src,
-            anim,
+            scribl,
             width,
             height,
             fps,
+            start,
             frame_count,
             video_info,
         )
@@ -149,6 +279,122 @@ fn create_pipeline(
     Ok(pipeline)
 }
 
+// The audio to export between `start` and `end`. If the config asks for it, the whole mix gets
+// normalized to the target loudness.
+fn export_audio_data(
+    scribl: &ScriblState,
+    start: Time,
+    end: Time,
+    config: &crate::config::Export,
+) -> Result<crate::audio::OutputData, anyhow::Error> {
+    let mut data = crate::audio::OutputData {
+        start_time: start,
+        snips: scribl.talk.truncated(end),
+        music: scribl.music.truncated(end),
+        ducking: scribl.ducking.clone(),
+        gain: 1.0,
+        velocity: 1.0,
+    };
+    if let Some(target) = config.target_loudness {
+        data.gain = crate::audio::mix_normalizing_gain(&data, end, target)?;
+    }
+    Ok(data)
+}
+
+// Creates a pipeline for exporting just the audio between `start` and `end`.
+fn create_audio_pipeline(
+    scribl: ScriblState,
+    start: Time,
+    end: Time,
+    path: &Path,
+    config: &crate::config::Export,
+) -> Result<gst::Pipeline, anyhow::Error> {
+    let container = Container::from_path(path);
+    let pipeline = gst::Pipeline::new(None);
+    let audio_output_data = export_audio_data(&scribl, start, end, config)?;
+    let (output_tx, output_rx) = unbounded();
+    // The unwrap is ok because we know that the receiver is still alive.
+    output_tx.send(audio_output_data).unwrap();
+    let src = crate::audio::create_appsrc(output_rx, "encode-asrc")?;
+    // The audio timestamps are relative to the beginning of the animation, but the file's should
+    // be relative to `start`.
+    if let Some(pad) = src.get_static_pad("src") {
+        pad.set_offset(-(start - Time::ZERO).as_micros() * 1000);
+    }
+    let convert = make_elt("audioconvert", "encode-aconvert")?;
+    let queue = make_elt("queue", "encode-aqueue")?;
+    let sink = make_elt("filesink", "encode-sink")?;
+    pipeline.add_many(&[&src, &queue, &convert, &sink])?;
+
+    // The element that gets the metadata (and, if the format supports it, the chapters).
+    let tag_elt = match container {
+        Container::Wav => make_elt("wavenc", "encode-aencode")?,
+        Container::Flac => make_elt("flacenc", "encode-aencode")?,
+        _ => make_elt("opusenc", "encode-aencode")?,
+    };
+    pipeline.add(&tag_elt)?;
+    gst::Element::link_many(&[&src, &queue, &convert, &tag_elt])?;
+    if container == Container::Opus {
+        let mux = make_elt("oggmux", "encode-mux")?;
+        pipeline.add(&mux)?;
+        gst::Element::link_many(&[&tag_elt, &mux, &sink])?;
+    } else {
+        gst::Element::link(&tag_elt, &sink)?;
+    }
+
+    sink.set_property(
+        "location",
+        &path
+            .to_str()
+            .ok_or(anyhow!("this filename is too weird"))?
+            .to_value(),
+    )?;
+
+    set_metadata(&tag_elt, &scribl.metadata);
+    set_chapters(&tag_elt, &scribl.chapters, start, end);
+    Ok(pipeline)
+}
+
+// Like `main_loop`, but periodically sends progress reports, based on how far the pipeline has
+// gotten through the `duration` that it's supposed to produce. The progress is measured in
+// milliseconds.
+fn main_loop_with_progress(
+    pipeline: gst::Pipeline,
+    duration: TimeDiff,
+    progress: Sender<EncodingStatus>,
+) -> Result<(), anyhow::Error> {
+    pipeline.set_state(gst::State::Playing)?;
+    let bus = pipeline
+        .get_bus()
+        .ok_or_else(|| anyhow!("couldn't get pipeline bus"))?;
+    let out_of = (duration.as_micros() / 1000).max(1) as u64;
+
+    loop {
+        if let Some(msg) = bus.timed_pop(gst::ClockTime::from_mseconds(100)) {
+            use gst::MessageView::*;
+
+            match msg.view() {
+                Eos(..) => break,
+                Error(err) => {
+                    pipeline.set_state(gst::State::Null)?;
+
+                    return Err(PipelineError::from(err).into());
+                }
+                _ => {}
+            }
+        }
+        if let Some(pos) = pipeline.query_position::<gst::ClockTime>() {
+            let _ = progress.send(EncodingStatus::Encoding {
+                frame: pos.mseconds().unwrap_or(0).min(out_of),
+                out_of,
+            });
+        }
+    }
+
+    pipeline.set_state(gst::State::Null)?;
+    Ok(())
+}
+
 // Runs the pipeline (blocking) until it exits or errors.
 fn main_loop(pipeline: gst::Pipeline) -> Result<(), anyhow::Error> {
     pipeline.set_state(gst::State::Playing)?;
@@ -183,10 +429,11 @@ fn render_loop(
     cmd: Receiver<RenderLoopCmd>,
     progress: Sender<EncodingStatus>,
     app_src: gst_app::AppSrc,
-    snippets: DrawSnippets,
+    scribl: ScriblState,
     width: u32,
     height: u32,
     fps: f64,
+    start: Time,
     frame_count: u32,
     video_info: VideoInfo,
 ) -> Result<(), Error> {
@@ -194,15 +441,11 @@ fn render_loop(
     let mut bitmap = device
         .bitmap_target(width as usize, height as usize, 1.0)
         .map_err(|e| anyhow!("failed to get bitmap: {}", e))?;
-    let mut cursor = snippets.create_cursor(Time::ZERO);
-    let transform = TranslateScale::scale(width as f64);
-
-    {
-        let mut ctx = bitmap.render_context();
-        ctx.clear(None, Color::WHITE);
-        ctx.finish()
-            .map_err(|e| anyhow!("failed to finish context: {}", e))?;
-    }
+    let snippets = &scribl.draw;
+    let mut cursor = snippets.create_cursor(start);
+    let frame = Rect::new(0.0, 0.0, width as f64, height as f64);
+    let mut prev_buffer: Option<gst::Buffer> = None;
+    let mut prev_viewport: Option<Viewport> = None;
 
     for frame_counter in 0..frame_count {
         while let Ok(msg) = cmd.try_recv() {
@@ -219,15 +462,22 @@ fn render_loop(
             out_of: frame_count as u64,
         });
 
-        let time = Time::from_video_frame(frame_counter, fps);
+        // The timestamp of this frame in the video, and the time in the animation that it shows.
+        let pts = Time::from_video_frame(frame_counter, fps);
+        let time = start + (pts - Time::ZERO);
         let last_time = cursor.current().0;
+        let viewport = scribl.camera.viewport_at(time);
+        let transform = TranslateScale::scale(width as f64) * viewport.to_page();
+        // If the camera moved (or if this is the first frame), we need to redraw everything.
+        let camera_moved = prev_viewport != Some(viewport);
+        prev_viewport = Some(viewport);
 
         // TODO: we have a cursor for visible snippets, but we could also have a cursor for
         // snippets that might potentially cause a change in the visibility. There should be less
         // of these.
         cursor.advance_to(time.min(last_time), time.max(last_time));
         let mut bbox = Rect::ZERO;
-        for b in cursor.bboxes(&snippets) {
+        for b in cursor.bboxes(snippets) {
             if bbox.area() == 0.0 {
                 bbox = b;
             } else {
@@ -236,15 +486,36 @@ fn render_loop(
             }
         }
 
-        bbox = (transform * bbox).expand();
+        // If nothing changed since the last frame, there's no need to render anything or to copy
+        // the pixels out again: we just push a (shallow) copy of the previous buffer with an
+        // updated timestamp. This makes a big difference for long stretches of static drawing.
+        if bbox.area() == 0.0 && !camera_moved {
+            if let Some(prev) = &prev_buffer {
+                cursor.advance_to(time, time);
+                let mut gst_buffer = prev.copy();
+                gst_buffer
+                    .get_mut()
+                    .ok_or(anyhow!("failed to get mutable buffer"))?
+                    .set_pts(pts.as_gst_clock_time());
+                let _ = app_src.push_buffer(gst_buffer);
+                continue;
+            }
+        }
+
+        bbox = if camera_moved {
+            frame
+        } else {
+            (transform * bbox).expand()
+        };
 
         cursor.advance_to(time, time);
         {
             let mut ctx = bitmap.render_context();
             ctx.with_save(|ctx| {
                 ctx.clip(bbox);
-                ctx.fill(bbox, &Color::WHITE);
                 ctx.transform(transform.into());
+                // The background is rendered in drawing coordinates, exactly as in the editor.
+                scribl.background.render(ctx, transform.inverse() * bbox);
                 for id in cursor.active_ids() {
                     snippets.snippet(id).render(ctx, time);
                 }
@@ -263,13 +534,15 @@ fn render_loop(
                 .get_mut()
                 .ok_or(anyhow!("failed to get mutable buffer"))?;
             // Presentation time stamp (i.e. when should this frame be displayed).
-            gst_buffer_ref.set_pts(time.as_gst_clock_time());
+            gst_buffer_ref.set_pts(pts.as_gst_clock_time());
 
             let mut data = gst_buffer_ref.map_writable()?;
             bitmap
                 .copy_raw_pixels(ImageFormat::RgbaPremul, &mut data)
                 .map_err(|e| anyhow!("failed to get raw pixels: {}", e))?;
         }
+        // Cloning a gst buffer just bumps a reference count.
+        prev_buffer = Some(gst_buffer.clone());
 
         // Ignore the error, since appsrc is supposed to handle it.
         let _ = app_src.push_buffer(gst_buffer);
@@ -283,8 +556,8 @@ fn render_loop(
 
 #[derive(Clone, Data, Debug)]
 pub enum EncodingStatus {
-    /// We are still encoding, and the parameter is the progress (0.0 at the beginning, 1.0 at the
-    /// end).
+    /// We are still encoding, and have gotten through `frame` out of `out_of` steps. For videos,
+    /// the steps are video frames; for audio-only exports, they are milliseconds.
     Encoding { frame: u64, out_of: u64 },
 
     /// We finished encoding successfully.
@@ -298,16 +571,22 @@ pub fn do_encode_blocking(
     cmd: crate::cmd::ExportCmd,
     progress: Sender<EncodingStatus>,
 ) -> Result<(), anyhow::Error> {
-    let end_time = cmd
-        .scribl
-        .draw
-        .last_draw_time()
-        .max(cmd.scribl.talk.end_time())
-        + TimeDiff::from_micros(200000);
-    let num_frames = end_time.as_video_frame(cmd.config.fps);
+    if Container::from_path(&cmd.filename).is_audio_only() {
+        let (start, end) = cmd
+            .range
+            .unwrap_or_else(|| (Time::ZERO, cmd.scribl.end_time()));
+        let pipeline = create_audio_pipeline(cmd.scribl, start, end, &cmd.filename, &cmd.config)?;
+        return main_loop_with_progress(pipeline, end - start, progress);
+    }
+
+    let (start, end) = cmd.range.unwrap_or_else(|| {
+        let end = cmd.scribl.end_time() + TimeDiff::from_micros(200000);
+        (Time::ZERO, end)
+    });
+    let num_frames = (Time::ZERO + (end - start)).as_video_frame(cmd.config.fps);
     main_loop(create_pipeline(
-        cmd.scribl.draw,
-        cmd.scribl.talk,
+        cmd.scribl,
+        start,
         num_frames as u32,
         &cmd.filename,
         cmd.config,
@@ -315,6 +594,65 @@ pub fn do_encode_blocking(
     )?)
 }
 
+/// Renders a single frame of the animation and saves it as a png file.
+///
+/// The width of the image is determined by `height` and the animation's aspect ratio.
+pub fn save_frame_blocking(
+    scribl: &ScriblState,
+    time: Time,
+    height: u32,
+    path: &Path,
+) -> Result<(), anyhow::Error> {
+    let width = frame_width(height, scribl.aspect_ratio);
+    let pixels = scribl_curves::render_frame(
+        &scribl.draw,
+        &scribl.background,
+        &scribl.camera,
+        time,
+        width as usize,
+        height as usize,
+    )
+    .map_err(|e| anyhow!("failed to render: {}", e))?;
+
+    let pipeline = gst::Pipeline::new(None);
+    let src = make_elt("appsrc", "frame-source")?;
+    let convert = make_elt("videoconvert", "frame-convert")?;
+    let encode = make_elt("pngenc", "frame-encode")?;
+    let sink = make_elt("filesink", "frame-sink")?;
+    pipeline.add_many(&[&src, &convert, &encode, &sink])?;
+    gst::Element::link_many(&[&src, &convert, &encode, &sink])?;
+
+    encode.set_property("snapshot", &true)?;
+    sink.set_property(
+        "location",
+        &path
+            .to_str()
+            .ok_or(anyhow!("this filename is too weird"))?
+            .to_value(),
+    )?;
+
+    let video_info = VideoInfo::builder(VideoFormat::Rgba, width, height)
+        .fps(gst::Fraction::new(1, 1))
+        .build()?;
+    let src = src
+        .dynamic_cast::<gst_app::AppSrc>()
+        .map_err(|_| anyhow!("bug: couldn't cast src to an AppSrc"))?;
+    src.set_caps(Some(&video_info.to_caps()?));
+    src.set_property_format(gst::Format::Time);
+
+    let mut buffer = gst::Buffer::from_mut_slice(pixels);
+    buffer
+        .get_mut()
+        .ok_or(anyhow!("failed to get mutable buffer"))?
+        .set_pts(Time::ZERO.as_gst_clock_time());
+    src.push_buffer(buffer)
+        .map_err(|e| anyhow!("failed to push frame: {:?}", e))?;
+    src.end_of_stream()
+        .map_err(|e| anyhow!("failed to end stream: {:?}", e))?;
+
+    main_loop(pipeline)
+}
+
 pub fn encode_blocking(cmd: crate::cmd::ExportCmd, progress: Sender<EncodingStatus>) {
     let path = cmd.filename.clone();
     if let Err(e) = do_encode_blocking(cmd, progress.clone()) {
@@ -19,8 +19,8 @@ mod undo;
 mod widgets;
 
 pub use data::{
-    CurrentAction, DenoiseSetting, EditorState, PenSize, RecordingSpeed, SaveFileData, ScriblState,
-    SnippetId, MAX_ZOOM,
+    AspectRatio, CurrentAction, DenoiseSetting, EditorState, PenSize, RecordingSpeed, SaveFileData,
+    ScriblState, SnippetId, MAX_ZOOM,
 };
 
 const BUTTON_BACKGROUND_DISABLED: Key<Color> = Key::new("button_background_disabled");
@@ -67,22 +67,50 @@ fn main() {
         )
         .arg(
             Arg::with_name("export-to")
-                .help("Export the animation as a video instead of opening it")
+                .help(
+                    "Export the animation instead of opening it (as a video, or as audio if the \
+                     file ends in .wav, .flac or .opus)",
+                )
                 .long("export-to")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("render-frame")
+                .help("Render a single frame as a png image instead of opening the file")
+                .long("render-frame")
+                .takes_value(true)
+                .requires("FILE"),
+        )
+        .arg(
+            Arg::with_name("time")
+                .help("The time (in seconds) of the frame to render (defaults to 0)")
+                .long("time")
+                .takes_value(true)
+                .requires("render-frame"),
+        )
         .get_matches();
 
     let config = crate::config::load_config();
 
     let initial_editor = if let Some(path) = matches.value_of("FILE") {
-        match crate::SaveFileData::load_from_path(path) {
+        // Rendering a frame doesn't need the audio, so don't bother loading it.
+        let save_file = if matches.is_present("render-frame") {
+            crate::SaveFileData::load_without_audio_from_path(path)
+        } else {
+            crate::SaveFileData::load_from_path(path)
+        };
+        match save_file {
             Ok(save_file) => {
                 let mut e = EditorState::from_save_file(save_file, config);
                 e.save_path = Some(path.into());
                 e
             }
             Err(e) => {
+                // Rendering frames is meant for scripts, which need to know that it failed.
+                if matches.is_present("render-frame") {
+                    eprintln!("Error opening save file: {}", e);
+                    std::process::exit(1);
+                }
                 log::error!("Error opening save file: {}", e);
                 return;
             }
@@ -91,13 +119,39 @@ fn main() {
         EditorState::new(config)
     };
 
+    if let Some(output_path) = matches.value_of("render-frame") {
+        let time = match matches.value_of("time").unwrap_or("0").parse::<f64>() {
+            Ok(t) if t >= 0.0 => scribl_curves::Time::from_micros((t * 1e6) as i64),
+            _ => {
+                eprintln!("Invalid time: expected a non-negative number of seconds");
+                std::process::exit(1);
+            }
+        };
+        let height = initial_editor.config.export.height;
+        if let Err(e) = crate::encode::save_frame_blocking(
+            &initial_editor.scribl,
+            time,
+            height,
+            std::path::Path::new(output_path),
+        ) {
+            eprintln!("Error rendering frame: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     if let Some(output_path) = matches.value_of("export-to") {
         encode(initial_editor, output_path);
         return;
     }
 
+    crate::autosave::install_panic_hook();
+    let recovery = crate::autosave::find_recovery(
+        initial_editor.save_path.as_deref(),
+        initial_editor.saved_data.as_ref(),
+    );
     let mut initial_state = AppState::default();
-    let editor_window_desc = initial_state.add_editor(initial_editor);
+    let editor_window_desc = initial_state.add_editor(initial_editor, recovery);
 
     let launcher = AppLauncher::with_window(editor_window_desc).configure_env(|e, _| {
         e.set(theme::BUTTON_LIGHT, Color::rgb8(0x70, 0x70, 0x70));
@@ -126,6 +180,7 @@ fn encode(data: EditorState, path: &str) {
         scribl: data.scribl,
         filename: path.into(),
         config: config.export,
+        range: None,
     };
     let (tx, rx) = unbounded();
     std::thread::spawn(move || crate::encode::encode_blocking(export, tx));
@@ -136,7 +191,7 @@ fn encode(data: EditorState, path: &str) {
         match msg {
             EncodingStatus::Encoding { frame, out_of } => {
                 let _ = term.clear_line();
-                let _ = write!(term, "Encoding frame {} of {}", frame, out_of);
+                let _ = write!(term, "Encoding: {}%", frame * 100 / out_of.max(1));
             }
             EncodingStatus::Error(s) => eprintln!("\nEncoding error: {}", s),
             EncodingStatus::Finished(_) => eprintln!("\nFinished!"),
@@ -2,15 +2,48 @@ use druid::commands;
 use druid::menu::MenuEventCtx;
 use druid::platform_menus;
 use druid::{
-    Env, FileDialogOptions, FileSpec, HotKey, KbKey, LocalizedString, Menu, MenuItem, SysMods,
-    WindowId,
+    Color, Env, FileDialogOptions, FileInfo, FileSpec, HotKey, KbKey, LocalizedString, Menu,
+    MenuItem, Selector, SysMods, WindowId,
 };
 
+use scribl_curves::{PaperTemplate, TimeDiff};
+
 use crate::app_state::AppState;
-use crate::{cmd, CurrentAction, EditorState, SnippetId};
+use crate::audio::{AudioProcessing, Ducking};
+use crate::{cmd, AspectRatio, CurrentAction, EditorState, SnippetId};
 
 const SCRIBL_FILE_TYPE: FileSpec = FileSpec::new("Scribl animation (.scb)", &["scb"]);
-const EXPORT_FILE_TYPE: FileSpec = FileSpec::new("mp4 video (.mp4)", &["mp4"]);
+const VIDEO_EXPORT_FILE_TYPES: [FileSpec; 2] = [
+    // mp4mux doesn't support tables of contents, so only WebM gets the chapters.
+    FileSpec::new("mp4 video, without chapters (.mp4)", &["mp4"]),
+    FileSpec::new("WebM video, with chapters (.webm)", &["webm"]),
+];
+const AUDIO_EXPORT_FILE_TYPES: [FileSpec; 3] = [
+    FileSpec::new("WAV audio (.wav)", &["wav"]),
+    FileSpec::new("FLAC audio (.flac)", &["flac"]),
+    FileSpec::new("Opus audio (.opus)", &["opus"]),
+];
+const AUDIO_IMPORT_FILE_TYPE: FileSpec = FileSpec::new(
+    "Audio (.wav, .flac, .mp3, .ogg, .opus)",
+    &["wav", "flac", "mp3", "ogg", "opus"],
+);
+
+/// The amounts (in dB) by which we offer to duck the music while someone is talking.
+const DUCKING_DEPTHS: [f32; 5] = [0.0, -6.0, -12.0, -18.0, -24.0];
+
+/// The attack and release times (in milliseconds) that we offer for ducking the music.
+const DUCKING_TIMES: [i64; 4] = [100, 200, 500, 1000];
+
+/// The lengths (in seconds) of the gaps that we offer to insert.
+const GAP_LENGTHS: [i64; 5] = [1, 2, 5, 10, 30];
+
+// TODO: internationalization
+const BACKGROUND_COLORS: [(&str, Color); 4] = [
+    ("White", Color::WHITE),
+    ("Cream", Color::rgb8(0xfb, 0xf6, 0xe4)),
+    ("Chalkboard", Color::rgb8(0x2c, 0x4a, 0x3b)),
+    ("Blackboard", Color::rgb8(0x24, 0x24, 0x24)),
+];
 
 trait EditorMenu {
     fn action<F: FnMut(&mut MenuEventCtx, &mut EditorState) + 'static>(
@@ -53,6 +86,27 @@ pub fn save_dialog_options() -> FileDialogOptions {
     FileDialogOptions::new().allowed_types(vec![SCRIBL_FILE_TYPE])
 }
 
+fn export_dialog_options(
+    data: &EditorState,
+    accept: Selector<FileInfo>,
+    types: &[FileSpec],
+    title: &str,
+) -> FileDialogOptions {
+    let mut export_options = FileDialogOptions::new()
+        .allowed_types(types.to_vec())
+        .title(title)
+        .button_text("Export")
+        .accept_command(accept);
+    if let Some(save_path) = &data.save_path {
+        if let Some(save_name) = save_path.file_stem() {
+            if let Some(save_name) = save_name.to_str() {
+                export_options = export_options.default_name(save_name);
+            }
+        }
+    }
+    export_options
+}
+
 fn file_menu(window_id: WindowId, _data: &AppState) -> Menu<AppState> {
     let new = platform_menus::win::file::new();
 
@@ -80,22 +134,189 @@ fn file_menu(window_id: WindowId, _data: &AppState) -> Menu<AppState> {
     let export =
         MenuItem::new(LocalizedString::new("scribl-menu-file-export").with_placeholder("Export"))
             .action(window_id, move |ctx, data| {
-                let mut export_options = FileDialogOptions::new()
-                    .allowed_types(vec![EXPORT_FILE_TYPE])
-                    .title("Export to video")
-                    .button_text("Export")
-                    .accept_command(cmd::EXPORT);
-                if let Some(save_path) = &data.save_path {
-                    if let Some(save_name) = save_path.file_stem() {
-                        if let Some(save_name) = save_name.to_str() {
-                            export_options = export_options.default_name(save_name);
-                        }
-                    }
-                }
+                let export_options = export_dialog_options(
+                    data,
+                    cmd::EXPORT,
+                    &VIDEO_EXPORT_FILE_TYPES,
+                    "Export to video",
+                );
                 ctx.submit_command(commands::SHOW_SAVE_PANEL.with(export_options))
             })
             .hotkey(SysMods::Cmd, "e");
 
+    let export_range = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-export-range").with_placeholder("Export range"),
+    )
+    .action(window_id, move |ctx, data| {
+        let export_options = export_dialog_options(
+            data,
+            cmd::EXPORT_RANGE,
+            &VIDEO_EXPORT_FILE_TYPES,
+            "Export to video",
+        );
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(export_options))
+    })
+    .active_if(window_id, |data| data.range_start.is_some());
+
+    let export_audio = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-export-audio").with_placeholder("Export audio"),
+    )
+    .action(window_id, move |ctx, data| {
+        let export_options = export_dialog_options(
+            data,
+            cmd::EXPORT_AUDIO,
+            &AUDIO_EXPORT_FILE_TYPES,
+            "Export audio",
+        );
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(export_options))
+    });
+
+    let import_audio = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-import-audio").with_placeholder("Import audio…"),
+    )
+    .action(window_id, |ctx, _| {
+        let import_options = FileDialogOptions::new()
+            .allowed_types(vec![AUDIO_IMPORT_FILE_TYPE])
+            .title("Import audio")
+            .button_text("Import")
+            .accept_command(cmd::IMPORT_AUDIO);
+        ctx.submit_command(commands::SHOW_OPEN_PANEL.with(import_options))
+    })
+    .active_if(window_id, |data| {
+        data.action.is_idle() && data.status.in_progress.importing.is_none()
+    });
+
+    let import_music = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-import-music").with_placeholder("Import music…"),
+    )
+    .action(window_id, |ctx, _| {
+        let import_options = FileDialogOptions::new()
+            .allowed_types(vec![AUDIO_IMPORT_FILE_TYPE])
+            .title("Import music")
+            .button_text("Import")
+            .accept_command(cmd::IMPORT_MUSIC);
+        ctx.submit_command(commands::SHOW_OPEN_PANEL.with(import_options))
+    })
+    .active_if(window_id, |data| {
+        data.action.is_idle() && data.status.in_progress.importing.is_none()
+    });
+
+    let properties = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-properties").with_placeholder("Properties…"),
+    )
+    .action(window_id, |ctx, _| ctx.submit_command(cmd::SHOW_PROPERTIES));
+
+    let audio_devices = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-audio-devices").with_placeholder("Audio devices…"),
+    )
+    .action(window_id, |ctx, _| {
+        ctx.submit_command(cmd::SHOW_AUDIO_SETTINGS)
+    })
+    .active_if(window_id, |data| data.action.is_idle());
+
+    let mut aspect_ratio = Menu::new(
+        LocalizedString::new("scribl-menu-file-aspect-ratio").with_placeholder("Aspect ratio"),
+    );
+    for &ar in &AspectRatio::STANDARD {
+        aspect_ratio = aspect_ratio.entry(
+            MenuItem::new(ar.to_string())
+                .action(window_id, move |_, data| data.set_aspect_ratio(ar))
+                .selected_if(move |data: &AppState, _env| {
+                    data.editor(window_id)
+                        .map(|e| e.scribl.aspect_ratio == ar)
+                        .unwrap_or(false)
+                }),
+        );
+    }
+
+    let mut background = Menu::new(
+        LocalizedString::new("scribl-menu-file-background").with_placeholder("Background"),
+    );
+    for &(name, color) in &BACKGROUND_COLORS {
+        background = background.entry(
+            MenuItem::new(name)
+                .action(window_id, move |_, data| data.set_background_color(color))
+                .selected_if(move |data: &AppState, _env| {
+                    data.editor(window_id)
+                        .map(|e| e.scribl.background.color.as_rgba_u32() == color.as_rgba_u32())
+                        .unwrap_or(false)
+                }),
+        );
+    }
+    background = background.separator();
+    for &template in &PaperTemplate::ALL {
+        background = background.entry(
+            MenuItem::new(template.name())
+                .action(window_id, move |_, data| data.set_paper_template(template))
+                .selected_if(move |data: &AppState, _env| {
+                    data.editor(window_id)
+                        .map(|e| e.scribl.background.template == template)
+                        .unwrap_or(false)
+                }),
+        );
+    }
+
+    let mut ducking = Menu::new(
+        LocalizedString::new("scribl-menu-file-ducking").with_placeholder("Music ducking"),
+    );
+    for &depth in &DUCKING_DEPTHS {
+        let name = if depth == 0.0 {
+            "Off".to_owned()
+        } else {
+            format!("{} dB", depth)
+        };
+        ducking = ducking.entry(
+            MenuItem::new(name)
+                .action(window_id, move |_, data| {
+                    data.set_ducking(Ducking {
+                        depth,
+                        ..data.scribl.ducking.clone()
+                    })
+                })
+                .selected_if(move |data: &AppState, _env| {
+                    data.editor(window_id)
+                        .map(|e| e.scribl.ducking.depth == depth)
+                        .unwrap_or(false)
+                }),
+        );
+    }
+    ducking = ducking.separator();
+    for &ms in &DUCKING_TIMES {
+        let attack = TimeDiff::from_micros(ms * 1000);
+        ducking = ducking.entry(
+            MenuItem::new(format!("Attack: {} ms", ms))
+                .action(window_id, move |_, data| {
+                    data.set_ducking(Ducking {
+                        attack,
+                        ..data.scribl.ducking.clone()
+                    })
+                })
+                .selected_if(move |data: &AppState, _env| {
+                    data.editor(window_id)
+                        .map(|e| e.scribl.ducking.attack == attack)
+                        .unwrap_or(false)
+                }),
+        );
+    }
+    ducking = ducking.separator();
+    for &ms in &DUCKING_TIMES {
+        let release = TimeDiff::from_micros(ms * 1000);
+        ducking = ducking.entry(
+            MenuItem::new(format!("Release: {} ms", ms))
+                .action(window_id, move |_, data| {
+                    data.set_ducking(Ducking {
+                        release,
+                        ..data.scribl.ducking.clone()
+                    })
+                })
+                .selected_if(move |data: &AppState, _env| {
+                    data.editor(window_id)
+                        .map(|e| e.scribl.ducking.release == release)
+                        .unwrap_or(false)
+                }),
+        );
+    }
+
     let close = MenuItem::new(LocalizedString::new("common-menu-file-close"))
         .command(druid::commands::CLOSE_WINDOW)
         .hotkey(SysMods::Cmd, "q");
@@ -106,6 +327,16 @@ fn file_menu(window_id: WindowId, _data: &AppState) -> Menu<AppState> {
         .entry(save)
         .entry(save_as)
         .entry(export)
+        .entry(export_range)
+        .entry(export_audio)
+        .entry(import_audio)
+        .entry(import_music)
+        .separator()
+        .entry(properties)
+        .entry(audio_devices)
+        .entry(aspect_ratio)
+        .entry(background)
+        .entry(ducking)
         .separator()
         .entry(close)
 }
@@ -149,6 +380,15 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
             .active_if(id, move |data| !data.action.is_recording_audio())
             .hotkey(SysMods::Shift, " ");
 
+    let draw_and_talk = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-draw-and-talk").with_placeholder("Draw and talk"),
+    )
+    .action(id, |_, data| data.draw_and_talk())
+    .active_if(id, move |data| {
+        !data.action.is_recording() && !data.action.is_recording_audio()
+    })
+    .hotkey(SysMods::None, "r");
+
     let play =
         MenuItem::new(LocalizedString::new("scribl-menu-edit-play").with_placeholder("Play"))
             .action(id, |_, data| data.play())
@@ -172,6 +412,7 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
                 match action {
                     // The stop hotkey matches the hotkey that was used to start the current action.
                     CurrentAction::Playing => Some(HotKey::new(SysMods::None, KbKey::Enter)),
+                    a if a.is_recording_with_audio() => Some(HotKey::new(SysMods::None, "r")),
                     CurrentAction::Recording(_) => Some(HotKey::new(SysMods::None, " ")),
                     CurrentAction::RecordingAudio(_) => Some(HotKey::new(SysMods::Shift, " ")),
                     _ => None,
@@ -183,12 +424,59 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
             .action(id, move |_, data| data.set_mark())
             .hotkey(SysMods::None, "m");
 
-    let clear_mark = MenuItem::new(
-        LocalizedString::new("scribl-menu-edit-clear-mark").with_placeholder("Clear mark"),
+    let range_end = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-range-end").with_placeholder("Set range end"),
+    )
+    .action(id, move |_, data| data.set_range_end())
+    .hotkey(SysMods::Shift, "M")
+    .active_if(id, move |data| data.range_start.is_some());
+
+    let clear_range = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-clear-range").with_placeholder("Clear range"),
     )
-    .action(id, move |_, data| data.clear_mark())
+    .action(id, move |_, data| data.clear_range())
     .hotkey(SysMods::None, KbKey::Escape)
-    .active_if(id, move |data| data.mark.is_some());
+    .active_if(id, move |data| data.range_start.is_some());
+
+    let delete_mark = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-delete-mark").with_placeholder("Delete mark"),
+    )
+    .action(id, move |_, data| data.delete_mark())
+    .active_if(id, move |data| {
+        data.scribl.marks.mark_at(data.time()).is_some()
+    });
+
+    let next_mark = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-next-mark").with_placeholder("Jump to next mark"),
+    )
+    .action(id, move |_, data| data.jump_to_next_mark())
+    .hotkey(SysMods::None, "]")
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.scribl.marks.next_after(data.time()).is_some()
+    });
+
+    let prev_mark = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-prev-mark")
+            .with_placeholder("Jump to previous mark"),
+    )
+    .action(id, move |_, data| data.jump_to_prev_mark())
+    .hotkey(SysMods::None, "[")
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.scribl.marks.prev_before(data.time()).is_some()
+    });
+
+    let add_chapter = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-add-chapter").with_placeholder("Add chapter"),
+    )
+    .action(id, |_, data| data.add_chapter());
+
+    let remove_chapter = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-remove-chapter").with_placeholder("Remove chapter"),
+    )
+    .action(id, |_, data| data.remove_chapter())
+    .active_if(id, |data| {
+        data.scribl.chapters.chapter_at(data.time()).is_some()
+    });
 
     let warp = MenuItem::new(
         LocalizedString::new("scribl-menu-edit-warp").with_placeholder("Warp snippet"),
@@ -196,7 +484,7 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
     .action(id, |_, data| data.warp_snippet())
     .hotkey(SysMods::None, "w")
     .active_if(id, move |data| {
-        data.mark.is_some() && matches!(data.selected_snippet, Some(SnippetId::Draw(_)))
+        data.range_start.is_some() && matches!(data.selected_snippet, Some(SnippetId::Draw(_)))
     });
 
     let trunc = MenuItem::new(
@@ -215,8 +503,37 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
     .hotkey(SysMods::None, KbKey::Delete)
     .active_if(id, move |data| data.selected_snippet.is_some());
 
-    let talk_selected =
-        move |data: &EditorState| matches!(data.selected_snippet, Some(SnippetId::Talk(_)));
+    let ripple_delete = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-ripple-delete").with_placeholder("Delete range"),
+    )
+    .action(id, |_, data| data.ripple_delete())
+    .hotkey(SysMods::Shift, KbKey::Delete)
+    .active_if(id, |data| {
+        data.action.is_idle() && data.selected_range().map_or(false, |(a, b)| a != b)
+    });
+
+    let mut insert_gap = Menu::new(
+        LocalizedString::new("scribl-menu-edit-insert-gap").with_placeholder("Insert gap"),
+    );
+    for &secs in &GAP_LENGTHS {
+        insert_gap = insert_gap.entry(
+            MenuItem::new(format!("{} s", secs))
+                .action(id, move |_, data| {
+                    data.insert_gap(TimeDiff::from_micros(secs * 1_000_000))
+                })
+                .active_if(id, |data| data.action.is_idle()),
+        );
+    }
+
+    let shorten_pauses = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-shorten-pauses").with_placeholder("Shorten pauses…"),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::SHOW_PAUSE_SHORTENING))
+    .active_if(id, |data| {
+        data.action.is_idle() && data.scribl.talk.snippets().next().is_some()
+    });
+
+    let audio_selected = move |data: &EditorState| data.selected_audio().is_some();
 
     let increase_volume = MenuItem::new(
         LocalizedString::new("scribl-menu-edit-increase-volume")
@@ -224,7 +541,7 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
     )
     .action(id, |_, data| data.multiply_volume(1.1))
     .hotkey(SysMods::None, "+")
-    .active_if(id, talk_selected);
+    .active_if(id, audio_selected);
 
     let decrease_volume = MenuItem::new(
         LocalizedString::new("scribl-menu-edit-decrease-volume")
@@ -232,20 +549,99 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
     )
     .action(id, |_, data| data.multiply_volume(1.0 / 1.1))
     .hotkey(SysMods::None, "-")
-    .active_if(id, talk_selected);
+    .active_if(id, audio_selected);
+
+    let range_audio_selected = move |data: &EditorState| {
+        audio_selected(data) && data.selected_range().map_or(false, |(a, b)| a != b)
+    };
+
+    let increase_range_volume = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-increase-range-volume")
+            .with_placeholder("Increase volume in range"),
+    )
+    .action(id, |_, data| data.multiply_range_volume(1.1))
+    .active_if(id, range_audio_selected);
+
+    let decrease_range_volume = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-decrease-range-volume")
+            .with_placeholder("Decrease volume in range"),
+    )
+    .action(id, |_, data| data.multiply_range_volume(1.0 / 1.1))
+    .active_if(id, range_audio_selected);
+
+    let fade_in = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-fade-in").with_placeholder("Fade in to here"),
+    )
+    .action(id, |_, data| data.fade_in_audio())
+    .active_if(id, audio_selected);
+
+    let fade_out = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-fade-out").with_placeholder("Fade out from here"),
+    )
+    .action(id, |_, data| data.fade_out_audio())
+    .active_if(id, audio_selected);
 
     let silence = MenuItem::new(
         LocalizedString::new("scribl-menu-edit-silence").with_placeholder("Silence range"),
     )
     .action(id, |_, data| data.silence_audio())
     .hotkey(SysMods::None, KbKey::Backspace)
-    .active_if(id, talk_selected);
+    .active_if(id, audio_selected);
+
+    let split = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-split").with_placeholder("Split speech"),
+    )
+    .action(id, |_, data| data.split_snippet())
+    .hotkey(SysMods::None, "s")
+    .active_if(id, audio_selected);
+
+    let reprocess = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-reprocess").with_placeholder("Redo noise removal"),
+    )
+    .action(id, |ctx, _| {
+        ctx.submit_command(cmd::PROCESS_AUDIO.with(AudioProcessing::Reprocess))
+    })
+    .active_if(id, |data| {
+        data.status.in_progress.processing.is_none()
+            && data.selected_audio().map_or(false, |(lane, id)| {
+                data.scribl.audio(lane).snippet(id).has_raw()
+            })
+    });
+
+    let can_process = move |data: &EditorState| {
+        audio_selected(data) && data.status.in_progress.processing.is_none()
+    };
+    let process_item = |key: &'static str, placeholder: &'static str, op: AudioProcessing| {
+        MenuItem::new(LocalizedString::new(key).with_placeholder(placeholder))
+            .action(id, move |ctx, _| {
+                ctx.submit_command(cmd::PROCESS_AUDIO.with(op))
+            })
+            .active_if(id, can_process)
+    };
+    let process = Menu::new(
+        LocalizedString::new("scribl-menu-edit-process").with_placeholder("Process audio"),
+    )
+    .entry(process_item(
+        "scribl-menu-edit-process-remove-noise",
+        "Remove noise",
+        AudioProcessing::RemoveNoise,
+    ))
+    .entry(process_item(
+        "scribl-menu-edit-process-normalize",
+        "Normalize volume",
+        AudioProcessing::Normalize,
+    ))
+    .entry(process_item(
+        "scribl-menu-edit-process-limit",
+        "Limit peaks",
+        AudioProcessing::Limit,
+    ));
 
     let snip =
         MenuItem::new(LocalizedString::new("scribl-menu-edit-snip").with_placeholder("Snip range"))
             .action(id, |_, data| data.snip_audio())
             .hotkey(SysMods::Shift, KbKey::Backspace)
-            .active_if(id, talk_selected);
+            .active_if(id, audio_selected);
 
     Menu::new(LocalizedString::new("common-menu-edit-menu"))
         .entry(undo)
@@ -253,19 +649,36 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
         .separator()
         .entry(draw)
         .entry(talk)
+        .entry(draw_and_talk)
         .entry(play)
         .entry(stop)
         .separator()
         .entry(mark)
-        .entry(clear_mark)
+        .entry(range_end)
+        .entry(clear_range)
+        .entry(delete_mark)
+        .entry(next_mark)
+        .entry(prev_mark)
+        .entry(add_chapter)
+        .entry(remove_chapter)
         .entry(warp)
         .entry(trunc)
         .entry(delete)
+        .entry(ripple_delete)
+        .entry(insert_gap)
+        .entry(shorten_pauses)
         .separator()
         .entry(increase_volume)
         .entry(decrease_volume)
+        .entry(increase_range_volume)
+        .entry(decrease_range_volume)
+        .entry(fade_in)
+        .entry(fade_out)
         .entry(silence)
         .entry(snip)
+        .entry(split)
+        .entry(reprocess)
+        .entry(process)
 }
 
 fn view_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
@@ -285,10 +698,32 @@ fn view_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
     )
     .action(id, |_, data| data.settings.zoom_reset());
 
+    let record_camera = MenuItem::new(
+        LocalizedString::new("scribl-menu-view-record-camera").with_placeholder("Record camera"),
+    )
+    .action(id, |_, data| {
+        data.settings.camera_recording = !data.settings.camera_recording
+    })
+    .selected_if(move |data: &AppState, _env| {
+        data.editor(id)
+            .map(|e| e.settings.camera_recording)
+            .unwrap_or(false)
+    })
+    .hotkey(SysMods::None, "c");
+
+    let clear_camera = MenuItem::new(
+        LocalizedString::new("scribl-menu-view-clear-camera").with_placeholder("Clear camera"),
+    )
+    .action(id, |_, data| data.clear_camera_track())
+    .active_if(id, |data| !data.scribl.camera.is_empty());
+
     Menu::new(LocalizedString::new("scribl-menu-view-menu").with_placeholder("View"))
         .entry(zoom_in)
         .entry(zoom_out)
         .entry(zoom_reset)
+        .separator()
+        .entry(record_camera)
+        .entry(clear_camera)
 }
 
 pub fn make_menu(window_id: Option<WindowId>, data: &AppState) -> Menu<AppState> {
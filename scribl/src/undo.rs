@@ -1,10 +1,11 @@
 use druid::im::Vector;
 use druid::Data;
 
-use scribl_curves::{DrawSnippets, Time};
+use scribl_curves::{Background, CameraTrack, DrawSnippets, Time};
 
-use crate::audio::TalkSnippets;
-use crate::{CurrentAction, SnippetId};
+use crate::audio::{Ducking, TalkSnippets};
+use crate::data::{Chapters, Marks, Metadata};
+use crate::{AspectRatio, CurrentAction, SnippetId};
 
 const MAX_UNDO_STACK: usize = 128;
 
@@ -15,8 +16,17 @@ const MAX_UNDO_STACK: usize = 128;
 pub struct UndoState {
     pub snippets: DrawSnippets,
     pub audio_snippets: TalkSnippets,
+    pub music_snippets: TalkSnippets,
+    pub ducking: Ducking,
+    pub aspect_ratio: AspectRatio,
+    pub background: Background,
+    pub camera: CameraTrack,
+    pub metadata: Metadata,
+    pub chapters: Chapters,
+    pub marks: Marks,
     pub selected_snippet: Option<SnippetId>,
-    pub mark: Option<Time>,
+    pub range_start: Option<Time>,
+    pub range_end: Option<Time>,
     pub time: Time,
     pub action: CurrentAction,
 }
@@ -28,8 +38,14 @@ impl UndoState {
     }
 }
 
+/// Identifies an action on the undo stack.
+#[derive(Clone, Copy, Data, Debug, PartialEq, Eq)]
+pub struct UndoId(u64);
+
 #[derive(Clone, Data)]
 struct UndoData {
+    id: UndoId,
+
     // The state to restore when redoing this operation.
     redo_state: UndoState,
     // The state to restore when undoing this operation. This is not necessarily the same as the
@@ -57,6 +73,8 @@ pub struct UndoStack {
     // The index of the current position in the stack. When this is zero, it means that nothing was
     // undone. When this is `stack.len()`, it means there is nothing left to undo.
     current_state: usize,
+    // The id of the next action to be pushed.
+    next_id: u64,
 }
 
 impl std::fmt::Debug for UndoData {
@@ -73,6 +91,7 @@ impl UndoStack {
         UndoStack {
             stack: Vector::new(),
             current_state: 0,
+            next_id: 0,
         }
     }
 
@@ -98,6 +117,7 @@ impl UndoStack {
         }
 
         let new_state = UndoData {
+            id: UndoId(self.next_id),
             undo_state,
             redo_state,
             description,
@@ -108,6 +128,7 @@ impl UndoStack {
             self.stack.pop_back();
         }
         self.current_state = 0;
+        self.next_id += 1;
     }
 
     /// Registers a new action that can be undone. Any states that were previously undone will be
@@ -135,6 +156,35 @@ impl UndoStack {
         self.do_push(undo_state, redo_state, description, true);
     }
 
+    /// Returns the id of the most recent action, unless it was undone.
+    pub fn last_id(&self) -> Option<UndoId> {
+        if self.current_state > 0 {
+            None
+        } else {
+            self.stack.front().map(|top| top.id)
+        }
+    }
+
+    /// Modifies the action with id `id` so that it also includes whatever happened since it was
+    /// pushed: `redo_state` replaces the action's redo state, and `description` replaces its
+    /// description.
+    ///
+    /// Returns `false` (and does nothing) unless that action is still the most recent one, and it
+    /// wasn't undone and isn't transient.
+    pub fn amend(&mut self, id: UndoId, redo_state: UndoState, description: String) -> bool {
+        if self.current_state > 0 {
+            return false;
+        }
+        match self.stack.front_mut() {
+            Some(top) if top.id == id && !top.transient => {
+                top.redo_state = redo_state;
+                top.description = description;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// If there is a most recent action to undo, rewinds the undo stack to that action and returns
     /// the state that should be restored.
     pub fn undo(&mut self) -> Option<UndoState> {
@@ -184,3 +234,37 @@ impl UndoStack {
         self.current_state > 0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(t: i64) -> UndoState {
+        UndoState::default().with_time(Time::from_micros(t))
+    }
+
+    #[test]
+    fn amend() {
+        let mut undo = UndoStack::new();
+        undo.push(state(0), state(1), "draw".to_owned());
+        let id = undo.last_id().unwrap();
+        assert!(undo.amend(id, state(2), "draw and talk".to_owned()));
+        assert_eq!(undo.undo_description(), Some("draw and talk"));
+        assert_eq!(undo.undo().unwrap().time, Time::from_micros(0));
+        assert_eq!(undo.redo().unwrap().time, Time::from_micros(2));
+
+        // Something else got pushed in the meantime, so the old action can't be amended.
+        undo.push(state(2), state(3), "delete".to_owned());
+        assert!(!undo.amend(id, state(4), "draw and talk".to_owned()));
+        assert_eq!(undo.undo_description(), Some("delete"));
+        assert_eq!(undo.undo().unwrap().time, Time::from_micros(2));
+        assert_eq!(undo.redo().unwrap().time, Time::from_micros(3));
+
+        // Undone actions can't be amended either.
+        let id = undo.last_id().unwrap();
+        undo.undo();
+        assert!(undo.last_id().is_none());
+        assert!(!undo.amend(id, state(4), "whatever".to_owned()));
+        assert_eq!(undo.redo().unwrap().time, Time::from_micros(3));
+    }
+}
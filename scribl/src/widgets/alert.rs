@@ -4,11 +4,13 @@ use druid::{SingleUse, Widget, WidgetExt};
 
 use scribl_widget::ModalHost;
 
+use crate::autosave::Recovery;
 use crate::{CurrentAction, EditorState};
 
 pub fn make_unsaved_changes_alert() -> impl Widget<EditorState> {
     let close =
         Button::new("Close without saving").on_click(|ctx, data: &mut EditorState, _env| {
+            crate::autosave::discard_autosaves(data.save_path.as_deref());
             data.action = CurrentAction::WaitingToExit;
             ctx.submit_command(ModalHost::DISMISS_MODAL);
             ctx.submit_command(druid::commands::CLOSE_WINDOW);
@@ -68,6 +70,53 @@ pub fn make_unsaved_changes_alert() -> impl Widget<EditorState> {
         .border(druid::theme::FOREGROUND_DARK, 1.0)
 }
 
+pub fn make_recovery_alert(recovery: Recovery) -> impl Widget<EditorState> {
+    let recovery_path = recovery.path.clone();
+    let restore = Button::new("Restore").on_click(move |ctx, data: &mut EditorState, _env| {
+        data.restore_recovered(&recovery.data);
+        // The restored changes will be autosaved again, so we don't need the old copy.
+        if let Err(e) = std::fs::remove_file(&recovery.path) {
+            log::error!("failed to remove {}: {}", recovery.path.display(), e);
+        }
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+    let discard = Button::new("Discard").on_click(move |ctx, _data, _env| {
+        if let Err(e) = std::fs::remove_file(&recovery_path) {
+            log::error!("failed to remove {}: {}", recovery_path.display(), e);
+        }
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    let button_row = Flex::row()
+        .with_child(discard)
+        .with_spacer(5.0)
+        .with_child(restore);
+
+    let label = Label::dynamic(|data: &EditorState, _| {
+        if let Some(file_name) = data
+            .save_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|f| f.to_string_lossy())
+        {
+            format!(
+                "Found unsaved changes to \"{}\" from last time. Restore them?",
+                file_name
+            )
+        } else {
+            "Found an unsaved animation from last time. Restore it?".to_owned()
+        }
+    });
+
+    Flex::column()
+        .with_child(label)
+        .with_spacer(15.0)
+        .with_child(button_row)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
 /// This controller gets instantiated when we're planning to close a window. Its job is to sit and
 /// wait until any saves and encodes in progress are finished. When they are, it sends a
 /// CLOSE_WINDOW command.
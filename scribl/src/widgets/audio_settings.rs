@@ -0,0 +1,51 @@
+use druid::widget::{Button, CrossAxisAlignment, Flex, Label, RadioGroup};
+use druid::{LensExt, Widget, WidgetExt};
+
+use scribl_widget::ModalHost;
+
+use crate::audio::AudioDevices;
+use crate::data::Settings;
+use crate::EditorState;
+
+fn device_choices(names: &[String], current: &Option<String>) -> Vec<(String, Option<String>)> {
+    let mut ret = vec![("System default".to_owned(), None)];
+    ret.extend(names.iter().map(|name| (name.clone(), Some(name.clone()))));
+    // If the chosen device has gone missing, we still show it (so that it's clear why we're
+    // using the default instead).
+    if let Some(current) = current {
+        if !names.contains(current) {
+            ret.push((format!("{} (not found)", current), Some(current.clone())));
+        }
+    }
+    ret
+}
+
+/// Makes a dialog for choosing the audio input and output devices.
+///
+/// `devices` is the list of devices to choose from, and `settings` holds the current choices.
+pub fn make_audio_settings_dialog(
+    devices: AudioDevices,
+    settings: &Settings,
+) -> impl Widget<EditorState> {
+    let inputs = RadioGroup::new(device_choices(&devices.inputs, &settings.input_device))
+        .lens(EditorState::settings.then(Settings::input_device));
+    let outputs = RadioGroup::new(device_choices(&devices.outputs, &settings.output_device))
+        .lens(EditorState::settings.then(Settings::output_device));
+
+    let done = Button::new("Done").on_click(|ctx, _data: &mut EditorState, _env| {
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("Record from"))
+        .with_child(inputs)
+        .with_spacer(10.0)
+        .with_child(Label::new("Play to"))
+        .with_child(outputs)
+        .with_spacer(15.0)
+        .with_child(done)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
@@ -1,22 +1,27 @@
 use druid::kurbo::TranslateScale;
 use druid::{
-    BoxConstraints, Color, Cursor, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
-    PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Vec2, Widget, WindowHandle,
+    BoxConstraints, Cursor, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, Rect, RenderContext, Size, TimerToken, UpdateCtx, Vec2, Widget, WindowHandle,
 };
+use std::time::Duration;
 
-use scribl_curves::{DrawCursor, Time};
+use scribl_curves::{DrawCursor, Time, Viewport};
 
 use crate::cursor::CursorCache;
-use crate::EditorState;
+use crate::undo::UndoState;
+use crate::{AspectRatio, EditorState};
 
 // The drawing coordinates are chosen so that the width of the image is always
-// 1.0. For now we also fix the height, but eventually we will support other aspect
-// ratios.
+// 1.0. The height depends on the document's aspect ratio.
 pub const DRAWING_WIDTH: f64 = 1.0;
-pub const DRAWING_HEIGHT: f64 = 0.75;
 
-const ASPECT_RATIO: f64 = DRAWING_WIDTH / DRAWING_HEIGHT;
-const PAPER_COLOR: Color = Color::rgb8(0xff, 0xff, 0xff);
+// Limits on how far the camera can zoom in or out, as a multiple of the page width.
+const MIN_VIEWPORT_WIDTH: f64 = 0.05;
+const MAX_VIEWPORT_WIDTH: f64 = 20.0;
+
+// Zooming the camera with the scroll wheel produces lots of little zoom events. We group them into
+// a single undo action, which ends once the wheel has been quiet for this long.
+const WHEEL_ZOOM_UNDO_DELAY: Duration = Duration::from_millis(500);
 
 pub struct DrawingPane {
     paper_rect: Rect,
@@ -27,23 +32,67 @@ pub struct DrawingPane {
     offset: Vec2,
     /// The last interesting position of the mouse (used for figuring out how much to pan by).
     last_mouse_pos: Point,
+    /// The part of the canvas that the camera sees at the current time. This is what gets drawn
+    /// on the paper.
+    viewport: Viewport,
+    /// If we're in the middle of moving the camera, this is the state to restore if they undo the
+    /// move.
+    camera_undo: Option<UndoState>,
+    /// Like `camera_undo`, but for zooming the camera with the scroll wheel.
+    wheel_zoom_undo: Option<UndoState>,
+    /// When this timer fires, the current scroll-wheel zoom is over.
+    wheel_zoom_timer: TimerToken,
     cursors: CursorCache,
 }
 
 impl DrawingPane {
+    fn finish_wheel_zoom(&mut self, data: &mut EditorState) {
+        self.wheel_zoom_timer = TimerToken::INVALID;
+        if let Some(prev_state) = self.wheel_zoom_undo.take() {
+            data.push_undo_state(prev_state, "zoom camera");
+        }
+    }
+
     fn to_image_coords(&self) -> TranslateScale {
         let top_left = Vec2::new(self.paper_rect.x0, self.paper_rect.y0);
         let size_ratio = DRAWING_WIDTH / self.paper_rect.width();
-        TranslateScale::scale(size_ratio) * TranslateScale::translate(-top_left)
+        self.viewport.to_page().inverse()
+            * TranslateScale::scale(size_ratio)
+            * TranslateScale::translate(-top_left)
     }
 
     fn from_image_coords(&self) -> TranslateScale {
         let top_left = Vec2::new(self.paper_rect.x0, self.paper_rect.y0);
-        TranslateScale::translate(top_left) * TranslateScale::scale(self.from_image_scale())
+        let size_ratio = self.paper_rect.width() / DRAWING_WIDTH;
+        TranslateScale::translate(top_left)
+            * TranslateScale::scale(size_ratio)
+            * self.viewport.to_page()
     }
 
     fn from_image_scale(&self) -> f64 {
-        self.paper_rect.width() / DRAWING_WIDTH
+        self.paper_rect.width() / (DRAWING_WIDTH * self.viewport.width)
+    }
+
+    // Moves the camera so that the viewport is shifted by `delta` (in screen coordinates).
+    fn pan_camera(&mut self, data: &mut EditorState, delta: Vec2) {
+        let delta = delta / self.from_image_scale();
+        let mut viewport = self.viewport;
+        viewport.x -= delta.x;
+        viewport.y -= delta.y;
+        data.set_viewport(viewport);
+    }
+
+    // Zooms the camera by `factor`, keeping the point under `pos` (in screen coordinates) fixed.
+    fn zoom_camera(&mut self, data: &mut EditorState, pos: Point, factor: f64) {
+        let fixed = self.to_image_coords() * pos;
+        let old = self.viewport;
+        let width = (old.width * factor).clamp(MIN_VIEWPORT_WIDTH, MAX_VIEWPORT_WIDTH);
+        let ratio = width / old.width;
+        data.set_viewport(Viewport {
+            x: fixed.x - (fixed.x - old.x) * ratio,
+            y: fixed.y - (fixed.y - old.y) * ratio,
+            width,
+        });
     }
 
     fn cursor(&mut self, data: &EditorState, window_id: &WindowHandle) -> &Cursor {
@@ -51,10 +100,11 @@ impl DrawingPane {
             .pen(window_id, data.settings.palette.selected_color())
     }
 
-    fn recompute_paper_rect(&mut self, size: Size, zoom: f64) {
+    fn recompute_paper_rect(&mut self, size: Size, zoom: f64, aspect_ratio: AspectRatio) {
         // Find the largest rectangle of the correct aspect ratio that will fit in the size.
-        let paper_width = size.width.min(ASPECT_RATIO * size.height);
-        let paper_height = paper_width / ASPECT_RATIO;
+        let aspect_ratio = aspect_ratio.ratio();
+        let paper_width = size.width.min(aspect_ratio * size.height);
+        let paper_height = paper_width / aspect_ratio;
         let mut rect = Size::new(paper_width, paper_height).to_rect();
 
         rect = TranslateScale::scale(zoom) * rect;
@@ -90,6 +140,10 @@ impl Default for DrawingPane {
             cursor: DrawCursor::empty(Time::ZERO),
             offset: Vec2::ZERO,
             last_mouse_pos: Point::ZERO,
+            viewport: Viewport::default(),
+            camera_undo: None,
+            wheel_zoom_undo: None,
+            wheel_zoom_timer: TimerToken::INVALID,
             // TODO: detect the default cursor size somehow
             cursors: CursorCache::new(32),
         }
@@ -116,10 +170,16 @@ impl Widget<EditorState> for DrawingPane {
                         ctx.request_paint_rect(invalid.inset(pen_width).expand());
 
                         data.add_point_to_stroke(self.to_image_coords() * ev.pos, time);
+                    } else if data.settings.camera_recording {
+                        self.pan_camera(data, ev.pos - self.last_mouse_pos);
                     } else {
                         // Pan the view.
                         self.offset -= (ev.pos - self.last_mouse_pos) / data.settings.zoom;
-                        self.recompute_paper_rect(ctx.size(), data.settings.zoom);
+                        self.recompute_paper_rect(
+                            ctx.size(),
+                            data.settings.zoom,
+                            data.scribl.aspect_ratio,
+                        );
                         ctx.request_paint();
                         // TODO: change the mouse cursor
                     }
@@ -132,12 +192,15 @@ impl Widget<EditorState> for DrawingPane {
                 }
             }
             Event::MouseDown(ev) if ev.button.is_left() => {
+                self.finish_wheel_zoom(data);
                 ctx.set_active(true);
                 self.last_mouse_pos = ev.pos;
                 if data.action.is_recording() {
                     let time = data.accurate_time();
                     data.add_point_to_stroke(self.to_image_coords() * ev.pos, time);
                     ctx.request_anim_frame();
+                } else if data.settings.camera_recording {
+                    self.camera_undo = Some(data.undo_state());
                 }
             }
             Event::MouseUp(ev) => {
@@ -145,6 +208,19 @@ impl Widget<EditorState> for DrawingPane {
                 if ev.button.is_left() && data.action.is_recording() {
                     data.finish_stroke();
                 }
+                if let Some(prev_state) = self.camera_undo.take() {
+                    data.push_undo_state(prev_state, "move camera");
+                }
+            }
+            Event::Wheel(ev) if data.settings.camera_recording => {
+                if self.wheel_zoom_undo.is_none() {
+                    self.wheel_zoom_undo = Some(data.undo_state());
+                }
+                self.zoom_camera(data, ev.pos, (ev.wheel_delta.y / 500.0).exp());
+                self.wheel_zoom_timer = ctx.request_timer(WHEEL_ZOOM_UNDO_DELAY);
+            }
+            Event::Timer(token) if *token == self.wheel_zoom_timer => {
+                self.finish_wheel_zoom(data);
             }
             Event::Wheel(ev) => {
                 let old_zoom = data.settings.zoom;
@@ -156,7 +232,7 @@ impl Widget<EditorState> for DrawingPane {
                 // currently over.
                 self.offset += ev.pos.to_vec2() / old_zoom * (zoom_factor - 1.0);
                 data.settings.zoom = zoom;
-                self.recompute_paper_rect(ctx.size(), zoom);
+                self.recompute_paper_rect(ctx.size(), zoom, data.scribl.aspect_ratio);
                 ctx.request_paint();
             }
             Event::WindowConnected => {
@@ -180,6 +256,12 @@ impl Widget<EditorState> for DrawingPane {
             ctx.set_cursor(&Cursor::Arrow);
         }
 
+        let viewport = data.viewport();
+        if viewport != self.viewport {
+            self.viewport = viewport;
+            ctx.request_paint();
+        }
+
         if !old_data.scribl.draw.same(&data.scribl.draw) {
             self.cursor = data.scribl.draw.create_cursor(data.time());
             ctx.request_paint();
@@ -237,8 +319,14 @@ impl Widget<EditorState> for DrawingPane {
             }
         }
 
-        if old_data.settings.zoom != data.settings.zoom {
-            self.recompute_paper_rect(ctx.size(), data.settings.zoom);
+        if old_data.settings.zoom != data.settings.zoom
+            || old_data.scribl.aspect_ratio != data.scribl.aspect_ratio
+        {
+            self.recompute_paper_rect(ctx.size(), data.settings.zoom, data.scribl.aspect_ratio);
+            ctx.request_paint();
+        }
+
+        if !old_data.scribl.background.same(&data.scribl.background) {
             ctx.request_paint();
         }
     }
@@ -251,6 +339,7 @@ impl Widget<EditorState> for DrawingPane {
         _env: &Env,
     ) {
         if matches!(event, LifeCycle::WidgetAdded) {
+            self.viewport = data.viewport();
             self.cursor = data.scribl.draw.create_cursor(data.time());
         }
     }
@@ -263,7 +352,7 @@ impl Widget<EditorState> for DrawingPane {
         _env: &Env,
     ) -> Size {
         let size = bc.max();
-        self.recompute_paper_rect(size, data.settings.zoom);
+        self.recompute_paper_rect(size, data.settings.zoom, data.scribl.aspect_ratio);
         size
     }
 
@@ -299,9 +388,17 @@ impl Widget<EditorState> for DrawingPane {
                     ctx.blurred_rect(self.paper_rect, shadow_radius, &shadow_color);
                 }
             }
-            ctx.fill(&self.paper_rect, &PAPER_COLOR);
-
+            // Anything outside the paper won't appear in the exported video, so don't show it here
+            // either.
+            ctx.clip(self.paper_rect);
             ctx.transform(self.from_image_coords().into());
+
+            // Only bother drawing the background in the part that needs repainting.
+            let visible = self.to_image_coords() * ctx.region().bounding_box();
+            let paper = self.viewport.rect(data.scribl.aspect_ratio.ratio());
+            data.scribl
+                .background
+                .render(ctx.render_ctx, visible.intersect(paper));
             for id in self.cursor.active_ids() {
                 data.scribl
                     .draw
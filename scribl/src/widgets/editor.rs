@@ -1,23 +1,27 @@
 use crossbeam_channel::Sender;
 use druid::widget::{Flex, Scroll};
 use druid::{
-    theme, BoxConstraints, Command, Data, Env, Event, EventCtx, ExtEventSink, KbKey, KeyEvent,
-    LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, SingleUse, Size, TimerToken, UpdateCtx, Widget,
-    WidgetExt, WidgetId, WindowId,
+    theme, BoxConstraints, Command, Data, Env, Event, EventCtx, ExtEventSink, FileInfo, KbKey,
+    KeyEvent, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, SingleUse, Size, TimerToken, UpdateCtx,
+    Widget, WidgetExt, WidgetId, WindowId,
 };
 use std::path::PathBuf;
 use std::time::Duration;
 
+use scribl_curves::Time;
 use scribl_widget::{ModalHost, RadioGroup, Separator, SunkenContainer, ToggleButton, TooltipExt};
 
-use crate::audio::AudioHandle;
-use crate::autosave::AutosaveData;
-use crate::data::Settings;
+use crate::audio::{AudioHandle, AudioProcessing, TalkSnippet};
+use crate::autosave::{AutosaveData, Recovery};
+use crate::config::AudioInput;
+use crate::data::{journal, FinishedStatus, Settings};
 use crate::widgets::{
-    alert, icons, make_status_bar, AudioIndicator, DrawingPane, Palette, Timeline,
+    alert, audio_settings, icons, make_status_bar, pauses, properties, AudioIndicator, DrawingPane,
+    Palette, Timeline,
 };
 use crate::{
     cmd, CurrentAction, DenoiseSetting, EditorState, PenSize, RecordingSpeed, SaveFileData,
+    SnippetId,
 };
 
 const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
@@ -38,6 +42,12 @@ pub struct Editor {
     // The audio state is derived from our EditorState, and our `update` method is where the actual
     // commands get sent to the audio thread.
     audio: Option<AudioHandle>,
+    // Unsaved changes from last time, which we offer to restore when the editor is first shown.
+    recovery: Option<Recovery>,
+    // True if we're showing a file whose audio is still being loaded.
+    showing_preview: bool,
+    // True if we've autosaved anything since this editor was opened.
+    autosaved: bool,
 
     inner: Box<dyn Widget<EditorState>>,
 }
@@ -294,8 +304,16 @@ impl Editor {
             audio: None,
             last_autosave_data: None,
             autosave_tx: None,
+            recovery: None,
+            showing_preview: false,
+            autosaved: false,
         }
     }
+
+    pub fn with_recovery(mut self, recovery: Option<Recovery>) -> Editor {
+        self.recovery = recovery;
+        self
+    }
 }
 
 impl Editor {
@@ -385,10 +403,29 @@ impl Editor {
         }
     }
 
-    fn export(&self, ctx: &mut EventCtx, data: &mut EditorState, export: cmd::ExportCmd) {
+    fn export(
+        &self,
+        ctx: &mut EventCtx,
+        data: &mut EditorState,
+        info: &FileInfo,
+        range: Option<(Time, Time)>,
+        default_extension: &str,
+    ) {
         if data.status.in_progress.encoding.is_some() {
             log::warn!("already encoding, not doing another one");
+        } else if self.showing_preview {
+            log::warn!("not exporting, the audio is still loading");
         } else {
+            let mut path = info.path().to_owned();
+            if path.extension().is_none() {
+                path.set_extension(default_extension);
+            }
+            let export = cmd::ExportCmd {
+                scribl: data.scribl.clone(),
+                filename: path,
+                config: data.config.export.clone(),
+                range,
+            };
             // This is a little wasteful, but it's probably fine. We spin up a thread to
             // translate between the Receiver that encode_blocking sends to, and the
             // ExtEventSink that sends commands to us.
@@ -413,10 +450,7 @@ impl Editor {
         _env: &Env,
     ) -> bool {
         let ret = if let Some(snip_cmd) = cmd.get(cmd::ADD_TALK_SNIPPET) {
-            let snip = snip_cmd.snip.clone();
-            let prev_state = data.undo_state();
-            data.selected_snippet = Some(data.scribl.add_talk_snippet(snip).into());
-            data.push_undo_state(prev_state.with_time(snip_cmd.orig_start), "add audio");
+            data.add_recorded_talk_snippet(snip_cmd.snip.clone(), snip_cmd.orig_start);
             true
         } else if let Some(time) = cmd.get(cmd::WARP_TIME_TO) {
             if data.action.is_idle() {
@@ -426,16 +460,18 @@ impl Editor {
             }
             true
         } else if let Some(info) = cmd.get(cmd::EXPORT) {
-            let mut path = info.path().to_owned();
-            if path.extension().is_none() {
-                path.set_extension("mp4");
+            self.export(ctx, data, info, None, "mp4");
+            true
+        } else if let Some(info) = cmd.get(cmd::EXPORT_AUDIO) {
+            self.export(ctx, data, info, None, "wav");
+            true
+        } else if let Some(info) = cmd.get(cmd::EXPORT_RANGE) {
+            if let Some((start, end)) = data.selected_range() {
+                let range = Some((start.min(end), start.max(end)));
+                self.export(ctx, data, info, range, "mp4");
+            } else {
+                log::error!("not exporting, no range selected");
             }
-            let export = cmd::ExportCmd {
-                scribl: data.scribl.clone(),
-                filename: path,
-                config: data.config.export.clone(),
-            };
-            self.export(ctx, data, export);
             true
         } else if cmd.is(druid::commands::SAVE_FILE_AS) || cmd.is(druid::commands::SAVE_FILE) {
             let mut path = if let Some(info) = cmd.get(druid::commands::SAVE_FILE_AS) {
@@ -446,6 +482,11 @@ impl Editor {
                 log::error!("no save path, not saving");
                 return false;
             };
+            if self.showing_preview {
+                // Saving now would throw away the audio.
+                log::error!("not saving, the audio is still loading");
+                return true;
+            }
             if path.extension().is_none() {
                 path.set_extension("scb");
             }
@@ -458,6 +499,28 @@ impl Editor {
                 ctx.window_id(),
             );
             true
+        } else if cmd.is(cmd::SHOW_PROPERTIES) {
+            ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                properties::make_properties_dialog(data.undo_state()),
+            ))));
+            true
+        } else if cmd.is(cmd::SHOW_PAUSE_SHORTENING) {
+            data.find_long_pauses();
+            ctx.submit_command(
+                ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(pauses::make_pause_dialog()))),
+            );
+            true
+        } else if cmd.is(cmd::SHOW_AUDIO_SETTINGS) {
+            ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                audio_settings::make_audio_settings_dialog(
+                    crate::audio::list_devices(),
+                    &data.settings,
+                ),
+            ))));
+            true
+        } else if let Some(msg) = cmd.get(cmd::AUDIO_DEVICE_MISSING) {
+            data.status.last_finished = Some(FinishedStatus::Error(msg.clone()));
+            true
         } else if cmd.is(druid::commands::OPEN_FILE) {
             if data.status.in_progress.loading.is_some() {
                 log::error!("not loading, already loading");
@@ -474,16 +537,91 @@ impl Editor {
             true
         } else if cmd.is(cmd::FINISHED_ASYNC_LOAD) {
             let result = cmd.get_unchecked(cmd::FINISHED_ASYNC_LOAD);
-            data.update_load_status(result);
+            let showed_preview = std::mem::replace(&mut self.showing_preview, result.preview);
+            if !result.preview {
+                data.update_load_status(result);
+            }
+            if result.save_data.is_err() && showed_preview {
+                // We've already replaced the old document with the preview, but we don't want to
+                // leave them editing a document that's missing its audio.
+                let mut new_data = EditorState::new(data.config.clone());
+                new_data.settings.input_device = data.settings.input_device.clone();
+                new_data.settings.output_device = data.settings.output_device.clone();
+                new_data.status = data.status.clone();
+                *data = new_data;
+            }
             if let Ok(save_data) = &result.save_data {
+                // The audio devices belong to this computer, not to the file, so we keep them.
+                let settings = data.settings.clone();
+                let status = data.status.clone();
                 *data = EditorState::from_save_file(save_data.clone(), data.config.clone());
+                data.settings.input_device = settings.input_device;
+                data.settings.output_device = settings.output_device;
                 data.save_path = Some(result.path.clone());
+                if result.preview {
+                    // Show the drawing, but don't let them do anything until the audio arrives.
+                    data.status = status;
+                    data.set_loading();
+                }
+                if let Some(recovery) = &result.recovery {
+                    ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                        alert::make_recovery_alert(recovery.clone()),
+                    ))));
+                }
+            }
+            true
+        } else if let Some(info) = cmd
+            .get(cmd::IMPORT_AUDIO)
+            .or_else(|| cmd.get(cmd::IMPORT_MUSIC))
+        {
+            if data.status.in_progress.importing.is_some() {
+                log::error!("not importing, already importing");
+            } else {
+                data.status.in_progress.importing = Some(info.path().to_owned());
+                spawn_async_import(
+                    ctx.get_external_handle(),
+                    info.path().to_owned(),
+                    data.time(),
+                    cmd.is(cmd::IMPORT_MUSIC),
+                    ctx.window_id(),
+                );
             }
             true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_ASYNC_IMPORT) {
+            data.finish_import(result);
+            true
+        } else if let Some(&op) = cmd.get(cmd::PROCESS_AUDIO) {
+            if data.status.in_progress.processing.is_some() {
+                log::error!("not processing, already processing");
+            } else if let Some((lane, id)) = data.selected_audio() {
+                data.status.in_progress.processing = Some(0.0);
+                spawn_async_process(
+                    ctx.get_external_handle(),
+                    lane.snippet_id(id),
+                    data.scribl.audio(lane).snippet(id).clone(),
+                    op,
+                    data.input_config(),
+                    ctx.window_id(),
+                );
+            }
+            true
+        } else if let Some(&progress) = cmd.get(cmd::AUDIO_PROCESSING_PROGRESS) {
+            if data.status.in_progress.processing.is_some() {
+                data.status.in_progress.processing = Some(progress);
+            }
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_ASYNC_PROCESS) {
+            data.finish_processing(result);
+            true
         } else if cmd.is(cmd::FINISHED_ASYNC_SAVE) {
             let result = cmd.get_unchecked(cmd::FINISHED_ASYNC_SAVE);
             data.update_save_status(result);
             if !result.autosave && result.error.is_none() {
+                // If the document was untitled (or saved somewhere else), its old autosaves are
+                // about to be orphaned. Since everything is saved now, they aren't needed anyway.
+                if data.save_path.as_deref() != Some(result.path.as_path()) {
+                    crate::autosave::discard_autosaves(data.save_path.as_deref());
+                }
                 data.save_path = Some(result.path.clone());
             }
             true
@@ -507,9 +645,59 @@ impl Editor {
     }
 }
 
+fn spawn_async_import(ext_cmd: ExtEventSink, path: PathBuf, time: Time, music: bool, id: WindowId) {
+    std::thread::spawn(move || {
+        let snip = crate::audio::decode_file(&path)
+            .map(|buf| TalkSnippet::new(buf, time, 1.0))
+            .map_err(|e| e.to_string());
+        let _ = ext_cmd.submit_command(
+            cmd::FINISHED_ASYNC_IMPORT,
+            Box::new(cmd::AsyncImportResult { path, snip, music }),
+            id,
+        );
+    });
+}
+
+fn spawn_async_process(
+    ext_cmd: ExtEventSink,
+    id: SnippetId,
+    orig: TalkSnippet,
+    op: AudioProcessing,
+    config: AudioInput,
+    window_id: WindowId,
+) {
+    std::thread::spawn(move || {
+        // Only send progress updates when there's a visible change, so that we don't flood the
+        // UI thread with commands.
+        let mut last_progress = 0.0;
+        let snip = crate::audio::process_snippet(&orig, op, &config, |progress| {
+            if progress - last_progress >= 0.01 {
+                last_progress = progress;
+                let _ = ext_cmd.submit_command(
+                    cmd::AUDIO_PROCESSING_PROGRESS,
+                    Box::new(progress),
+                    window_id,
+                );
+            }
+        })
+        .map_err(|e| e.to_string());
+        let _ = ext_cmd.submit_command(
+            cmd::FINISHED_ASYNC_PROCESS,
+            Box::new(cmd::AsyncProcessResult { id, orig, op, snip }),
+            window_id,
+        );
+    });
+}
+
 fn spawn_async_save(ext_cmd: ExtEventSink, save_data: SaveFileData, path: PathBuf, id: WindowId) {
     std::thread::spawn(move || {
         let result = save_data.save_to_path(&path);
+        // Everything in the journal is now in the file, so we don't need the journal anymore.
+        if result.is_ok() {
+            if let Err(e) = journal::remove_journal(&path) {
+                log::error!("failed to remove journal: {}", e);
+            }
+        }
         let _ = ext_cmd.submit_command(
             cmd::FINISHED_ASYNC_SAVE,
             Box::new(cmd::AsyncSaveResult {
@@ -525,9 +713,24 @@ fn spawn_async_save(ext_cmd: ExtEventSink, save_data: SaveFileData, path: PathBu
 
 fn spawn_async_load(ext_cmd: ExtEventSink, path: PathBuf, id: WindowId) {
     std::thread::spawn(move || {
+        let save_data = SaveFileData::load_from_path_with_preview(&path, |preview| {
+            let data = cmd::AsyncLoadResult {
+                path: path.clone(),
+                save_data: Ok(preview),
+                preview: true,
+                recovery: None,
+            };
+            let _ = ext_cmd.submit_command(cmd::FINISHED_ASYNC_LOAD, Box::new(data), id);
+        });
+        let recovery = save_data
+            .as_ref()
+            .ok()
+            .and_then(|data| crate::autosave::find_recovery(Some(&path), Some(data)));
         let data = cmd::AsyncLoadResult {
-            path: path.clone(),
-            save_data: SaveFileData::load_from_path(&path).map_err(|e| e.to_string()),
+            path,
+            save_data: save_data.map_err(|e| e.to_string()),
+            preview: false,
+            recovery,
         };
         let _ = ext_cmd.submit_command(cmd::FINISHED_ASYNC_LOAD, Box::new(data), id);
     });
@@ -546,13 +749,32 @@ impl Widget<EditorState> for Editor {
                     ctx.set_handled();
                 }
             }
-            Event::KeyDown(ev) => self.handle_key_down(ctx, ev, data, env),
-            Event::KeyUp(ev) => self.handle_key_up(ctx, ev, data, env),
+            // Key events go to our children first, so that typing into a text box (in a modal,
+            // say) doesn't also trigger our keyboard shortcuts.
+            Event::KeyDown(ev) => {
+                self.inner.event(ctx, event, data, env);
+                if !ctx.is_handled() {
+                    self.handle_key_down(ctx, ev, data, env);
+                }
+                return;
+            }
+            Event::KeyUp(ev) => {
+                self.inner.event(ctx, event, data, env);
+                if !ctx.is_handled() {
+                    self.handle_key_up(ctx, ev, data, env);
+                }
+                return;
+            }
             Event::Timer(tok) if tok == &self.autosave_timer_id => {
                 let autosave_data = SaveFileData::from_editor_state(data);
-                if !self.last_autosave_data.same(&Some(autosave_data.clone())) {
+                // We don't autosave documents without changes. Besides being pointless, it could
+                // overwrite changes that are waiting to be recovered.
+                if data.changed_since_last_save()
+                    && !self.last_autosave_data.same(&Some(autosave_data.clone()))
+                {
                     let autosave_data = AutosaveData {
                         data: autosave_data.clone(),
+                        base: data.saved_data.clone(),
                         path: data.save_path.clone(),
                     };
                     if let Some(tx) = &self.autosave_tx {
@@ -560,6 +782,7 @@ impl Widget<EditorState> for Editor {
                             log::error!("failed to send autosave data: {}", e);
                         }
                     }
+                    self.autosaved = true;
                 }
                 self.last_autosave_data = Some(autosave_data);
                 self.autosave_timer_id = ctx.request_timer(AUTOSAVE_INTERVAL);
@@ -569,6 +792,14 @@ impl Widget<EditorState> for Editor {
                     data.update_time();
                 }
             }
+            Event::WindowDisconnected => {
+                crate::autosave::remove_unsaved_document(ctx.window_id());
+                // If there's nothing unsaved, our autosaves are stale. (We leave alone any
+                // autosaves that we didn't write, because they might still need recovering.)
+                if self.autosaved && !data.changed_since_last_save() {
+                    crate::autosave::discard_autosaves(data.save_path.as_deref());
+                }
+            }
             Event::WindowCloseRequested => {
                 if matches!(data.action, CurrentAction::WaitingToExit) {
                     // By not handling the request, we're telling druid to really close it.
@@ -603,10 +834,23 @@ impl Widget<EditorState> for Editor {
         }
         self.inner.update(ctx, old_data, data, env);
 
+        if !old_data.scribl.same(&data.scribl)
+            || !old_data.saved_data.same(&data.saved_data)
+            || old_data.save_path != data.save_path
+        {
+            crate::autosave::update_unsaved_document(ctx.window_id(), data);
+        }
+
         let old_audio_state = old_data.audio_state();
         let new_audio_state = data.audio_state();
         if let Some(audio) = &mut self.audio {
             audio.update(old_audio_state, new_audio_state);
+            if old_data.settings.input_device != data.settings.input_device {
+                audio.set_input_device(data.settings.input_device.clone());
+            }
+            if old_data.settings.output_device != data.settings.output_device {
+                audio.set_output_device(data.settings.output_device.clone());
+            }
         }
     }
 
@@ -624,9 +868,16 @@ impl Widget<EditorState> for Editor {
                     ctx.window_id(),
                 ));
                 self.autosave_timer_id = ctx.request_timer(AUTOSAVE_INTERVAL);
+                if let Some(recovery) = self.recovery.take() {
+                    ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                        alert::make_recovery_alert(recovery),
+                    ))));
+                }
                 self.audio = Some(AudioHandle::initialize_audio(
                     ctx.get_external_handle(),
                     ctx.widget_id().into(),
+                    data.settings.input_device.clone(),
+                    data.settings.output_device.clone(),
                 ));
             }
             _ => {}
@@ -1,9 +1,12 @@
 mod alert;
 mod audio_indicator;
+mod audio_settings;
 mod drawing_pane;
 mod editor;
 pub mod icons;
 mod palette;
+mod pauses;
+mod properties;
 mod status;
 mod timeline;
 
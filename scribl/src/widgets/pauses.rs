@@ -0,0 +1,147 @@
+use druid::widget::{Button, Controller, CrossAxisAlignment, Either, Flex, Label, Scroll, Stepper};
+use druid::{Env, Event, EventCtx, Lens, LensExt, Widget, WidgetExt};
+
+use scribl_widget::ModalHost;
+
+use crate::data::Settings;
+use crate::widgets::properties::format_time;
+use crate::EditorState;
+
+const LIST_WIDTH: f64 = 250.0;
+const LIST_HEIGHT: f64 = 120.0;
+
+fn summary(data: &EditorState) -> String {
+    let target = data.settings.pause_target;
+    let saved: f64 = data
+        .long_pauses
+        .iter()
+        .map(|&(start, end)| (end - start).as_micros() as f64 / 1e6 - target)
+        .sum();
+    format!(
+        "Found {} long pauses (highlighted in the timeline).\nShortening them will save {:.1} seconds.",
+        data.long_pauses.len(),
+        saved
+    )
+}
+
+fn pause_list(data: &EditorState) -> String {
+    data.long_pauses
+        .iter()
+        .map(|&(start, end)| {
+            format!(
+                "{}: {:.1} seconds",
+                format_time(start),
+                (end - start).as_micros() as f64 / 1e6
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A row with a label and a stepper for changing one of the lengths (in seconds) in `Settings`.
+fn length_setting(
+    label: &str,
+    lens: impl Lens<Settings, f64> + 'static,
+    min: f64,
+    max: f64,
+) -> impl Widget<EditorState> {
+    let label = label.to_owned();
+    Flex::row()
+        .with_child(Label::dynamic(move |secs: &f64, _| {
+            format!("{} {:.2} seconds", label, secs)
+        }))
+        .with_spacer(5.0)
+        .with_child(Stepper::new().with_range(min, max).with_step(0.25))
+        .lens(EditorState::settings.then(lens))
+}
+
+/// Looks for the long pauses again whenever the pause settings change.
+struct PauseFinder;
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for PauseFinder {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        let threshold = data.settings.pause_threshold;
+        let target = data.settings.pause_target;
+        child.event(ctx, event, data, env);
+        if data.settings.pause_threshold != threshold || data.settings.pause_target != target {
+            data.find_long_pauses();
+        }
+    }
+}
+
+/// Makes a dialog that previews the pauses found by `EditorState::find_long_pauses`, and asks
+/// whether to shorten them. The lengths used for finding and shortening the pauses can be changed
+/// in the dialog.
+pub fn make_pause_dialog() -> impl Widget<EditorState> {
+    let cancel = Button::new("Cancel").on_click(|ctx, data: &mut EditorState, _env| {
+        data.long_pauses.clear();
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+    let shorten = Button::new("Shorten pauses").on_click(|ctx, data: &mut EditorState, _env| {
+        data.shorten_pauses();
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    let settings = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(length_setting(
+            "Look for pauses longer than",
+            Settings::pause_threshold,
+            0.5,
+            10.0,
+        ))
+        .with_child(length_setting(
+            "and shorten them to",
+            Settings::pause_target,
+            0.25,
+            5.0,
+        ));
+
+    let found = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::dynamic(|data: &EditorState, _| summary(data)))
+        .with_spacer(10.0)
+        .with_child(
+            Scroll::new(Label::dynamic(|data: &EditorState, _| pause_list(data)))
+                .vertical()
+                .fix_size(LIST_WIDTH, LIST_HEIGHT),
+        )
+        .with_spacer(15.0)
+        .with_child(
+            Flex::row()
+                .with_child(shorten)
+                .with_spacer(5.0)
+                .with_child(cancel),
+        );
+
+    let not_found = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("There are no long pauses to shorten."))
+        .with_spacer(15.0)
+        .with_child(
+            Button::new("Done").on_click(|ctx, _data: &mut EditorState, _env| {
+                ctx.submit_command(ModalHost::DISMISS_MODAL);
+            }),
+        );
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(settings)
+        .with_spacer(15.0)
+        .with_child(Either::new(
+            |data: &EditorState, _| data.long_pauses.is_empty(),
+            not_found,
+            found,
+        ))
+        .controller(PauseFinder)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
@@ -0,0 +1,144 @@
+use druid::widget::{Button, CrossAxisAlignment, Either, Flex, Label, List, Scroll, TextBox};
+use druid::{Data, LensExt, Widget, WidgetExt};
+
+use scribl_curves::Time;
+use scribl_widget::ModalHost;
+
+use crate::data::metadata::format_timestamp;
+use crate::data::{Chapter, Chapters, Mark, Marks, Metadata, ScriblState};
+use crate::undo::UndoState;
+use crate::EditorState;
+
+const LABEL_WIDTH: f64 = 100.0;
+const TEXT_BOX_WIDTH: f64 = 300.0;
+const LIST_HEIGHT: f64 = 120.0;
+
+pub fn format_time(time: Time) -> String {
+    let usecs = time.as_micros();
+    let mins = usecs / 60_000_000;
+    let secs = (usecs / 1_000_000) % 60;
+    let cents = (usecs / 10_000) % 100;
+    format!("{:02}:{:02}.{:02}", mins, secs, cents)
+}
+
+fn format_date(date: Option<i64>) -> String {
+    date.map(format_timestamp)
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+fn field(name: &str, widget: impl Widget<EditorState> + 'static) -> impl Widget<EditorState> {
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(name).fix_width(LABEL_WIDTH))
+        .with_child(widget)
+        .padding((0.0, 2.0))
+}
+
+fn make_chapter_row() -> impl Widget<Chapter> {
+    Flex::row()
+        .with_child(Label::dynamic(|c: &Chapter, _| format_time(c.time)).fix_width(LABEL_WIDTH))
+        .with_child(
+            TextBox::new()
+                .fix_width(TEXT_BOX_WIDTH)
+                .lens(Chapter::title),
+        )
+        .padding((0.0, 2.0))
+}
+
+fn make_mark_row() -> impl Widget<Mark> {
+    Flex::row()
+        .with_child(Label::dynamic(|m: &Mark, _| format_time(m.time)).fix_width(LABEL_WIDTH))
+        .with_child(TextBox::new().fix_width(TEXT_BOX_WIDTH).lens(Mark::name))
+        .padding((0.0, 2.0))
+}
+
+/// Makes a dialog for editing the title, author, chapter and mark names, etc.
+///
+/// `prev_state` is the state from before the dialog was opened: all of the changes made in the
+/// dialog get undone together.
+pub fn make_properties_dialog(prev_state: UndoState) -> impl Widget<EditorState> {
+    let title = TextBox::new().fix_width(TEXT_BOX_WIDTH).lens(
+        EditorState::scribl
+            .then(ScriblState::metadata)
+            .then(Metadata::title),
+    );
+    let author = TextBox::new().fix_width(TEXT_BOX_WIDTH).lens(
+        EditorState::scribl
+            .then(ScriblState::metadata)
+            .then(Metadata::author),
+    );
+    let description = TextBox::multiline().fix_size(TEXT_BOX_WIDTH, 80.0).lens(
+        EditorState::scribl
+            .then(ScriblState::metadata)
+            .then(Metadata::description),
+    );
+    let created = Label::dynamic(|data: &EditorState, _| format_date(data.scribl.metadata.created));
+    // The modification time gets filled in when saving, so the saved copy is the one that knows it.
+    let modified = Label::dynamic(|data: &EditorState, _| {
+        format_date(
+            data.saved_data
+                .as_ref()
+                .and_then(|d| d.metadata.modified)
+                .filter(|_| data.save_path.is_some()),
+        )
+    });
+
+    let chapter_list = Scroll::new(
+        List::new(make_chapter_row).lens(
+            EditorState::scribl
+                .then(ScriblState::chapters)
+                .then(Chapters::list),
+        ),
+    )
+    .vertical()
+    .fix_height(LIST_HEIGHT);
+    let chapters = Either::new(
+        |data: &EditorState, _| data.scribl.chapters.is_empty(),
+        Label::new("No chapters yet. Use \"Add chapter\" in the Edit menu to add one."),
+        chapter_list,
+    );
+
+    let mark_list = Scroll::new(
+        List::new(make_mark_row).lens(
+            EditorState::scribl
+                .then(ScriblState::marks)
+                .then(Marks::list),
+        ),
+    )
+    .vertical()
+    .fix_height(LIST_HEIGHT);
+    let marks = Either::new(
+        |data: &EditorState, _| data.scribl.marks.is_empty(),
+        Label::new("No marks yet. Use \"Set mark\" in the Edit menu to add one."),
+        mark_list,
+    );
+
+    let done = Button::new("Done").on_click(move |ctx, data: &mut EditorState, _env| {
+        if !prev_state.metadata.same(&data.scribl.metadata)
+            || !prev_state.chapters.same(&data.scribl.chapters)
+            || !prev_state.marks.same(&data.scribl.marks)
+        {
+            data.push_undo_state(prev_state.clone(), "edit properties");
+        }
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(field("Title", title))
+        .with_child(field("Author", author))
+        .with_child(field("Description", description))
+        .with_child(field("Created", created))
+        .with_child(field("Last saved", modified))
+        .with_spacer(10.0)
+        .with_child(Label::new("Chapters"))
+        .with_child(chapters)
+        .with_spacer(10.0)
+        .with_child(Label::new("Marks"))
+        .with_child(marks)
+        .with_spacer(15.0)
+        .with_child(done)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
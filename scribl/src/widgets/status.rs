@@ -29,10 +29,14 @@ fn status_type(status: &AsyncOpsStatus) -> StatusType {
     // We prioritize "in progress" messages.
     if let Some(x) = status.in_progress.encoding {
         StatusType::Progress("Encoding: ".to_owned(), x.0 as f64 / x.1 as f64)
+    } else if let Some(x) = status.in_progress.processing {
+        StatusType::Progress("Processing audio: ".to_owned(), x)
     } else if let Some(path) = &status.in_progress.saving {
         StatusType::Label(format!("Saving {}...", f_name(path)))
     } else if let Some(path) = &status.in_progress.loading {
         StatusType::Label(format!("Loading {}...", f_name(path)))
+    } else if let Some(path) = &status.in_progress.importing {
+        StatusType::Label(format!("Importing {}...", f_name(path)))
     } else if let Some(finished) = &status.last_finished {
         match finished {
             FinishedStatus::Saved { path, time: _ } => {
@@ -44,6 +48,13 @@ fn status_type(status: &AsyncOpsStatus) -> StatusType {
             FinishedStatus::Encoded { path, time: _ } => {
                 StatusType::Label(format!("Encoded {}", f_name(path)))
             }
+            FinishedStatus::Imported { path, time: _ } => {
+                StatusType::Label(format!("Imported {}", f_name(path)))
+            }
+            FinishedStatus::Processed {
+                description,
+                time: _,
+            } => StatusType::Label(format!("Done: {}", description)),
             FinishedStatus::Error(s) => StatusType::Label(format!("Error: {}", s)),
         }
     } else {
@@ -1,5 +1,5 @@
 use druid::kurbo::{BezPath, Line, Shape, Vec2};
-use druid::piet::StrokeStyle;
+use druid::piet::{FontFamily, StrokeStyle, Text, TextLayout, TextLayoutBuilder};
 use druid::widget::ClipBox;
 use druid::{
     Affine, BoxConstraints, Color, Data, Env, Event, EventCtx, KbKey, LayoutCtx, LifeCycle,
@@ -7,24 +7,33 @@ use druid::{
 };
 use std::collections::HashMap;
 
-use scribl_curves::{DrawSnippet, DrawSnippets, Time, TimeDiff};
+use scribl_curves::{DrawSnippet, Time, TimeDiff};
 use scribl_widget::SunkenContainer;
 
-use crate::audio::{TalkSnippet, TalkSnippets};
+use crate::audio::TalkSnippet;
+use crate::data::editor::AudioLane;
 use crate::snippet_layout::{self, SnippetShape};
-use crate::{cmd, EditorState, SnippetId};
+use crate::{cmd, EditorState, ScriblState, SnippetId};
 
 const PIXELS_PER_USEC: f64 = 40.0 / 1000000.0;
 const CURSOR_THICKNESS: f64 = 2.0;
 const SELECTION_FILL_COLOR: Color = Color::rgba8(0xff, 0xff, 0xff, 0x20);
+const LONG_PAUSE_FILL_COLOR: Color = Color::rgba8(0xff, 0x40, 0x40, 0x60);
 
 const AUDIO_SNIPPET_COLOR: Color = crate::UI_LIGHT_YELLOW;
 const AUDIO_SNIPPET_SELECTED_COLOR: Color = crate::UI_LIGHT_YELLOW;
+const MUSIC_SNIPPET_COLOR: Color = crate::UI_LIGHT_GREEN;
+const MUSIC_SNIPPET_SELECTED_COLOR: Color = crate::UI_LIGHT_GREEN;
 const SNIPPET_STROKE_COLOR: Color = Color::rgb8(0x00, 0x00, 0x00);
 const SNIPPET_SELECTED_STROKE_COLOR: Color = Color::rgb8(0xff, 0xff, 0xff);
 const SNIPPET_STROKE_THICKNESS: f64 = 1.0;
 const SNIPPET_SELECTED_STROKE_THICKNESS: f64 = 3.0;
 const SNIPPET_WAVEFORM_COLOR: Color = crate::UI_DARK_BLUE;
+const CHAPTER_COLOR: Color = crate::UI_LIGHT_YELLOW;
+const CHAPTER_TICK_HEIGHT: f64 = 8.0;
+const CHAPTER_FONT_SIZE: f64 = 10.0;
+const MARK_COLOR: Color = Color::WHITE;
+const MARK_FONT_SIZE: f64 = 10.0;
 
 const MIN_TIMELINE_HEIGHT: f64 = 100.0;
 
@@ -333,51 +342,59 @@ impl Default for TimelineInner {
 
 impl TimelineInner {
     // Recreates the child widgets, and organizes them into rows so that they don't overlap.
-    fn recreate_children(&mut self, snippets: &DrawSnippets, audio: &TalkSnippets) {
-        let draw_shapes = snippet_layout::layout(snippets.snippets(), &LAYOUT_PARAMS);
-        let audio_shapes = snippet_layout::layout(audio.snippets(), &LAYOUT_PARAMS);
-        self.height = (draw_shapes.max_y + audio_shapes.max_y).max(MIN_TIMELINE_HEIGHT);
+    //
+    // The drawings go at the top, and the audio at the bottom. The music gets its own lane, below
+    // the speech.
+    fn recreate_children(&mut self, scribl: &ScriblState) {
+        let draw_shapes = snippet_layout::layout(scribl.draw.snippets(), &LAYOUT_PARAMS);
+        let talk_shapes = snippet_layout::layout(scribl.talk.snippets(), &LAYOUT_PARAMS);
+        let music_shapes = snippet_layout::layout(scribl.music.snippets(), &LAYOUT_PARAMS);
+        self.height =
+            (draw_shapes.max_y + talk_shapes.max_y + music_shapes.max_y).max(MIN_TIMELINE_HEIGHT);
 
         self.children.clear();
         for (id, shape) in draw_shapes.positions {
-            let snip = snippets.snippet(id);
+            let snip = scribl.draw.snippet(id);
             let id = SnippetId::Draw(id);
             let interior = SnippetInterior::Drawing(DrawingWaveform::new(&snip));
-            let path = shape.to_path(LAYOUT_PARAMS.overlap);
-            self.children.insert(
-                id,
-                WidgetPod::new(TimelineSnippet {
-                    id,
-                    bbox: path.bounding_box(),
-                    path,
-                    hot: false,
-                    drag_start: None,
-                    drag_shift: None,
-                    shape,
-                    interior,
-                }),
-            );
+            self.add_child(id, shape, interior);
+        }
+
+        let audio_lanes = vec![
+            (
+                AudioLane::Talk,
+                talk_shapes,
+                self.height - music_shapes.max_y,
+            ),
+            (AudioLane::Music, music_shapes, self.height),
+        ];
+        for (lane, shapes, bottom) in audio_lanes {
+            let audio = scribl.audio(lane);
+            for (id, mut shape) in shapes.positions {
+                shape.reflect_y(bottom);
+                let audio_data = audio.snippet(id);
+                let interior =
+                    SnippetInterior::Audio(AudioWaveform::new(audio_data.clone(), &shape));
+                self.add_child(lane.snippet_id(id), shape, interior);
+            }
         }
-        for (id, mut shape) in audio_shapes.positions {
-            shape.reflect_y(self.height);
-            let audio_data = audio.snippet(id);
-            let id = SnippetId::Talk(id);
-            let interior = SnippetInterior::Audio(AudioWaveform::new(audio_data.clone(), &shape));
-            let path = shape.to_path(LAYOUT_PARAMS.overlap);
-            self.children.insert(
+    }
+
+    fn add_child(&mut self, id: SnippetId, shape: SnippetShape, interior: SnippetInterior) {
+        let path = shape.to_path(LAYOUT_PARAMS.overlap);
+        self.children.insert(
+            id,
+            WidgetPod::new(TimelineSnippet {
                 id,
-                WidgetPod::new(TimelineSnippet {
-                    id,
-                    bbox: path.bounding_box(),
-                    path,
-                    hot: false,
-                    drag_start: None,
-                    drag_shift: None,
-                    shape: shape.clone(),
-                    interior,
-                }),
-            );
-        }
+                bbox: path.bounding_box(),
+                path,
+                hot: false,
+                drag_start: None,
+                drag_shift: None,
+                shape,
+                interior,
+            }),
+        );
     }
 
     fn invalid_rect(s: Time, t: Time, height: f64) -> Rect {
@@ -411,6 +428,7 @@ impl TimelineSnippet {
         match self.id {
             SnippetId::Draw(id) => Snip::Drawing(data.scribl.draw.snippet(id).clone()),
             SnippetId::Talk(id) => Snip::Audio(data.scribl.talk.snippet(id).clone()),
+            SnippetId::Music(id) => Snip::Audio(data.scribl.music.snippet(id).clone()),
         }
     }
 
@@ -424,6 +442,13 @@ impl TimelineSnippet {
                     Some(AUDIO_SNIPPET_COLOR)
                 }
             }
+            SnippetId::Music(_) => {
+                if data.selected_snippet == Some(self.id) {
+                    Some(MUSIC_SNIPPET_SELECTED_COLOR)
+                } else {
+                    Some(MUSIC_SNIPPET_COLOR)
+                }
+            }
         }
     }
 
@@ -768,9 +793,10 @@ impl Widget<EditorState> for TimelineInner {
     ) {
         if !data.scribl.draw.same(&old_data.scribl.draw)
             || !data.scribl.talk.same(&old_data.scribl.talk)
+            || !data.scribl.music.same(&old_data.scribl.music)
         {
             ctx.request_layout();
-            self.recreate_children(&data.scribl.draw, &data.scribl.talk);
+            self.recreate_children(&data.scribl);
             ctx.children_changed();
         } else {
             // Don't call update on the children if we just changed them -- we need to let
@@ -780,7 +806,12 @@ impl Widget<EditorState> for TimelineInner {
             }
         }
 
-        if old_data.mark != data.mark {
+        if old_data.range_start != data.range_start
+            || old_data.range_end != data.range_end
+            || old_data.long_pauses != data.long_pauses
+            || !old_data.scribl.marks.same(&data.scribl.marks)
+            || !old_data.scribl.chapters.same(&data.scribl.chapters)
+        {
             ctx.request_paint();
         }
         if old_data.time() != data.time() {
@@ -799,7 +830,7 @@ impl Widget<EditorState> for TimelineInner {
     ) {
         match event {
             LifeCycle::WidgetAdded => {
-                self.recreate_children(&data.scribl.draw, &data.scribl.talk);
+                self.recreate_children(&data.scribl);
                 ctx.children_changed();
             }
             _ => {}
@@ -844,21 +875,60 @@ impl Widget<EditorState> for TimelineInner {
             }
         }
 
+        // Draw a tick at the start of each chapter, labelled with its title.
+        for chapter in data.scribl.chapters.iter() {
+            let x = pix_x(chapter.time);
+            ctx.stroke(
+                Line::new((x, 0.0), (x, CHAPTER_TICK_HEIGHT)),
+                &CHAPTER_COLOR,
+                CURSOR_THICKNESS,
+            );
+            if let Ok(layout) = ctx
+                .text()
+                .new_text_layout(chapter.title.clone())
+                .font(FontFamily::SYSTEM_UI, CHAPTER_FONT_SIZE)
+                .text_color(CHAPTER_COLOR)
+                .build()
+            {
+                ctx.draw_text(&layout, (x + 3.0, 0.0));
+            }
+        }
+
         let cursor_x = pix_x(data.time());
 
-        // Draw the mark.
-        if let Some(mark_time) = data.mark {
-            let mark_x = pix_x(mark_time);
-            let rect = Rect::new(cursor_x, 0.0, mark_x, size.height);
+        // Draw the selected range.
+        if let Some((start, end)) = data.selected_range() {
+            let rect = Rect::new(pix_x(start), 0.0, pix_x(end), size.height);
             ctx.fill(rect, &SELECTION_FILL_COLOR);
+        }
+
+        // Draw the pauses that are about to be shortened.
+        for &(start, end) in &data.long_pauses {
+            let rect = Rect::new(pix_x(start), 0.0, pix_x(end), size.height);
+            ctx.fill(rect, &LONG_PAUSE_FILL_COLOR);
+        }
+
+        // Draw the marks, with their names along the bottom.
+        for mark in data.scribl.marks.iter() {
+            let mark_x = pix_x(mark.time);
             let mark_line = Line::new((mark_x, 0.0), (mark_x, size.height));
             ctx.stroke(mark_line, &Color::BLACK, CURSOR_THICKNESS);
             ctx.stroke_styled(
                 mark_line,
-                &Color::WHITE,
+                &MARK_COLOR,
                 1.0,
                 &StrokeStyle::new().dash_pattern(&[2.0, 2.0]),
             );
+            if let Ok(layout) = ctx
+                .text()
+                .new_text_layout(mark.name.clone())
+                .font(FontFamily::SYSTEM_UI, MARK_FONT_SIZE)
+                .text_color(MARK_COLOR)
+                .build()
+            {
+                let y = size.height - layout.size().height;
+                ctx.draw_text(&layout, (mark_x + 3.0, y));
+            }
         }
 
         let cursor_line = Line::new((cursor_x, 0.0), (cursor_x, size.height));